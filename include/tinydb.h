@@ -0,0 +1,63 @@
+#ifndef TINYDB_H
+#define TINYDB_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define TINYDB_OK 0
+#define TINYDB_ERROR 1
+#define TINYDB_ROW 100
+#define TINYDB_DONE 101
+
+/* A database connection. */
+typedef struct Tinydb tinydb;
+
+/* Rows returned by a query. */
+typedef struct TinydbResult tinydb_result;
+
+/* Open db_name stored on db_data, initializing both if needed. Return NULL on error. */
+tinydb *tinydb_open(const char *db_data, const char *db_name);
+
+/*
+ * Close a connection, flushing all changes to disk. Return TINYDB_ERROR if they could not be
+ * flushed, freeing the connection anyway.
+ */
+int tinydb_close(tinydb *db);
+
+/*
+ * Execute the given SQL statements. If result is not NULL it is set to the rows of the last query,
+ * or NULL if no query was executed, and must be freed with tinydb_result_free.
+ */
+int tinydb_exec(tinydb *db, const char *sql, tinydb_result **result);
+
+/* Command tag of the last statement executed, e.g "INSERT 0 3", or NULL if tinydb_exec failed. */
+const char *tinydb_command_tag(const tinydb *db);
+
+/* Number of rows inserted or returned by the last statement executed. */
+uint64_t tinydb_changes(const tinydb *db);
+
+size_t tinydb_result_column_count(const tinydb_result *result);
+const char *tinydb_result_column_name(const tinydb_result *result, size_t column);
+/* Oid of the type of a column, e.g 23 for int4, or 0 if the column does not exist. */
+uint64_t tinydb_result_column_type(const tinydb_result *result, size_t column);
+
+/* Advance to the next row, returning TINYDB_ROW or TINYDB_DONE. */
+int tinydb_result_next(tinydb_result *result);
+
+/* Value of a column on the current row formatted as text. */
+const char *tinydb_result_value(const tinydb_result *result, size_t column);
+
+void tinydb_result_free(tinydb_result *result);
+
+/* Message of the last error on the calling thread, or NULL. */
+const char *tinydb_errmsg(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TINYDB_H */
@@ -1,17 +1,46 @@
 use crate::{
     catalog::pg_attribute::PgAttribute,
     storage::{
-        bufpage::{page_add_item, ItemId, PageHeader, ITEM_ID_SIZE, PAGE_HEADER_SIZE},
+        buffer::Page,
+        bufpage::{
+            page_add_item, page_remove_item, ItemId, ItemPointer, PageHeader, ITEM_ID_SIZE,
+            PAGE_HEADER_SIZE,
+        },
         freespace,
+        pager::{PageNumber, INVALID_PAGE_NUMBER},
         rel::Relation,
         BufferPool,
     },
+    Oid,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
+
+use super::metapage::{MetaPage, METAPAGE_NUMBER};
+use super::progress::{ProgressCommand, ProgressTracker};
+
+/// Errors related with heap relation operations.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("tuple {0} does not exist")]
+    TupleNotFound(ItemPointer),
+}
 
 /// HeapTuple is an in-memory data structure that points to a tuple on some page.
 pub struct HeapTuple {
     pub data: Vec<u8>,
+
+    /// Location of the tuple on relation, if it was read from a page.
+    pub ctid: ItemPointer,
+}
+
+impl HeapTuple {
+    /// Create a new tuple with the given data that is not stored on any page yet.
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            ctid: ItemPointer::default(),
+        }
+    }
 }
 
 /// Describe tuple attributes of single relation.
@@ -20,23 +49,75 @@ pub struct TupleDesc {
     pub attrs: Vec<PgAttribute>,
 }
 
-/// Insert a new tuple into a heap page of the given relation.
+/// Insert a new tuple into a heap page of the given relation. The metapage is updated if the
+/// tuple was added to a new page.
 pub fn heap_insert(buffer_pool: &mut BufferPool, rel: &Relation, tuple: &HeapTuple) -> Result<()> {
-    let buffer = freespace::get_page_with_free_space(buffer_pool, rel)?;
+    let mut meta = MetaPage::read(buffer_pool, rel)?;
+
+    // Pages are only filled up to the fillfactor of the relation, but a tuple is always added to
+    // a new page even if it does not leave the reserved space free.
+    let reserved = rel.borrow().options.free_space_reserved();
+    let (page_num, buffer) = freespace::get_page_with_free_space(
+        buffer_pool,
+        rel,
+        meta.free_space_page,
+        tuple.data.len() + reserved,
+    )?;
     let page = buffer_pool.get_page(&buffer);
 
     page_add_item(&page, &tuple.data)?;
 
     buffer_pool.unpin_buffer(buffer, true)?;
 
+    if page_num != meta.free_space_page {
+        meta.free_space_page = page_num;
+        meta.total_pages = meta.total_pages.max(page_num);
+        meta.write(buffer_pool, rel)?;
+    }
+
     Ok(())
 }
 
+/// Remove the tuple at the given location of a heap relation. Its line pointer is marked as
+/// unused, so scans skip it, but the tuple space is not reused by new inserts.
+///
+/// Return error if there is no tuple at the given location.
+pub fn heap_delete(buffer_pool: &mut BufferPool, rel: &Relation, ctid: ItemPointer) -> Result<()> {
+    let meta = MetaPage::read(buffer_pool, rel)?;
+    if ctid.page_num <= METAPAGE_NUMBER || ctid.page_num > meta.total_pages || ctid.offset == 0 {
+        bail!(Error::TupleNotFound(ctid));
+    }
+
+    let buffer = buffer_pool.fetch_buffer(rel, ctid.page_num)?;
+    let page = buffer_pool.get_page(&buffer);
+    let page_header = PageHeader::new(&page)?;
+
+    let item_id_offset = PAGE_HEADER_SIZE + (ctid.offset as usize - 1) * ITEM_ID_SIZE;
+    let found = item_id_offset < page_header.start_free_space as usize
+        && !bincode::deserialize::<ItemId>(&page.borrow().bytes()[item_id_offset..])?.is_unused();
+    if found {
+        page_remove_item(&page, ctid.offset)?;
+    }
+
+    buffer_pool.unpin_buffer(buffer, found)?;
+    if !found {
+        bail!(Error::TupleNotFound(ctid));
+    }
+    Ok(())
+}
+
+/// Initialize the storage of a new heap relation owned by the given access method, writing its
+/// metapage. Data pages are allocated by inserts.
+pub fn heap_create_storage(buffer_pool: &mut BufferPool, rel: &Relation, relam: Oid) -> Result<()> {
+    MetaPage::new(relam).init(buffer_pool, rel)
+}
+
 pub fn heap_scan(buffer_pool: &mut BufferPool, rel: &Relation) -> Result<Vec<HeapTuple>> {
     let mut tuples = Vec::new();
-    heap_iter(buffer_pool, rel, |tuple| -> Result<()> {
+    heap_iter_items(buffer_pool, rel, |ctid, tuple| -> Result<()> {
         tuples.push(HeapTuple {
             data: tuple.to_vec(),
+            ctid,
         });
         Ok(())
     })?;
@@ -49,10 +130,41 @@ pub fn heap_iter<F>(buffer_pool: &mut BufferPool, rel: &Relation, mut f: F) -> R
 where
     F: FnMut(&[u8]) -> Result<()>,
 {
-    // TODO: Iterate over all pages on relation
-    let buffer = buffer_pool.fetch_buffer(rel, 1)?;
-    let page = buffer_pool.get_page(&buffer);
-    let page_header = PageHeader::new(&page)?;
+    heap_iter_items(buffer_pool, rel, |_, tuple| f(tuple))
+}
+
+/// Like heap_iter, but also pass the location of each tuple to function f.
+///
+/// The scan reports its progress and can be interrupted by the progress handler. The page being
+/// scanned is always unpinned, even if function f or the progress handler return an error.
+pub fn heap_iter_items<F>(buffer_pool: &mut BufferPool, rel: &Relation, mut f: F) -> Result<()>
+where
+    F: FnMut(ItemPointer, &[u8]) -> Result<()>,
+{
+    let meta = MetaPage::read(buffer_pool, rel)?;
+    let mut progress = ProgressTracker::new(
+        ProgressCommand::Scan,
+        rel,
+        meta.total_pages.saturating_sub(METAPAGE_NUMBER),
+    );
+    for page_num in METAPAGE_NUMBER + 1..=meta.total_pages {
+        let buffer = buffer_pool.fetch_buffer(rel, page_num)?;
+        let result = page_iter_items(&buffer_pool.get_page(&buffer), page_num, &mut f);
+        buffer_pool.unpin_buffer(buffer, false)?;
+        result?;
+
+        progress.page_done()?;
+    }
+
+    Ok(())
+}
+
+/// Call function f to each tuple of the given heap page.
+fn page_iter_items<F>(page: &Page, page_num: PageNumber, f: &mut F) -> Result<()>
+where
+    F: FnMut(ItemPointer, &[u8]) -> Result<()>,
+{
+    let page_header = PageHeader::new(page)?;
 
     let page_data = page.borrow().bytes();
 
@@ -62,16 +174,63 @@ where
     // Split the raw item_id_data to a list of ItemId.
     let (item_id_data, _) = item_id_data.as_chunks::<ITEM_ID_SIZE>();
 
-    for data in item_id_data {
+    for (i, data) in item_id_data.iter().enumerate() {
         // Deserialize a single ItemId from the list item_id_data.
-        let item_id = bincode::deserialize::<ItemId>(&data.to_vec())?;
+        let item_id = bincode::deserialize::<ItemId>(data.as_ref())?;
+        if item_id.is_unused() {
+            continue;
+        }
 
         // Slice the raw page to get a refenrece to a tuple inside the page.
         let data = &page_data[item_id.offset as usize..(item_id.offset + item_id.length) as usize];
-        f(data)?;
+        let ctid = ItemPointer {
+            page_num,
+            offset: i as u16 + 1,
+        };
+        f(ctid, data)?;
     }
 
-    buffer_pool.unpin_buffer(buffer, false)?;
-
     Ok(())
 }
+
+/// Remove the empty pages at the end of the given relation, returning their space to the
+/// operating system. Pages beyond the metapage page count, allocated by inserts that failed
+/// before updating the metapage, are removed too. The metapage is always kept.
+///
+/// Return the number of removed pages.
+pub fn heap_truncate(buffer_pool: &mut BufferPool, rel: &Relation) -> Result<PageNumber> {
+    let total_pages = rel.borrow().pager.size()?;
+    let mut meta = MetaPage::read(buffer_pool, rel)?;
+
+    let mut new_total_pages = meta.total_pages.min(total_pages);
+    let mut progress = ProgressTracker::new(
+        ProgressCommand::Vacuum,
+        rel,
+        new_total_pages.saturating_sub(METAPAGE_NUMBER),
+    );
+    while new_total_pages > METAPAGE_NUMBER {
+        let buffer = buffer_pool.fetch_buffer(rel, new_total_pages)?;
+        let page_header = PageHeader::new(&buffer_pool.get_page(&buffer));
+        buffer_pool.unpin_buffer(buffer, false)?;
+        let page_header = page_header?;
+        progress.page_done()?;
+
+        // Pages without line pointers don't have any tuple. Pages allocated but never
+        // initialized have a zeroed header.
+        if page_header.start_free_space as usize > PAGE_HEADER_SIZE {
+            break;
+        }
+        new_total_pages -= 1;
+    }
+
+    if new_total_pages != meta.total_pages {
+        meta.total_pages = new_total_pages;
+        if meta.free_space_page > new_total_pages {
+            meta.free_space_page = INVALID_PAGE_NUMBER;
+        }
+        meta.write(buffer_pool, rel)?;
+    }
+    buffer_pool.truncate_relation(rel, new_total_pages)?;
+
+    Ok(total_pages - new_total_pages)
+}
@@ -0,0 +1,130 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{
+    pager::{PageNumber, INVALID_PAGE_NUMBER, PAGE_SIZE},
+    rel::Relation,
+    BufferPool,
+};
+use crate::Oid;
+
+/// Page number of the metapage, which is always the first page of a heap relation.
+pub const METAPAGE_NUMBER: PageNumber = 1;
+
+/// Magic number stored at the start of every metapage.
+const METAPAGE_MAGIC: u32 = 0x54444d50;
+
+/// Errors related with relation metapages.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("relation {0} does not have a valid metapage")]
+    InvalidMetaPage(String),
+}
+
+/// Structural information of a heap relation stored on its first page, so inserts and scans
+/// don't need to infer it from the relation file size.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MetaPage {
+    /// Identify the page as a metapage.
+    magic: u32,
+
+    /// Oid of the table access method that owns the relation storage.
+    pub relam: Oid,
+
+    /// Number of pages used by the relation, including the metapage.
+    pub total_pages: PageNumber,
+
+    /// Last page known to have free space for new tuples, or [INVALID_PAGE_NUMBER] if the
+    /// relation does not have data pages yet.
+    pub free_space_page: PageNumber,
+}
+
+impl MetaPage {
+    /// Create the metapage of an empty relation owned by the given access method.
+    pub fn new(relam: Oid) -> Self {
+        Self {
+            magic: METAPAGE_MAGIC,
+            relam,
+            total_pages: METAPAGE_NUMBER,
+            free_space_page: INVALID_PAGE_NUMBER,
+        }
+    }
+
+    /// Deserialize a metapage from raw page data, returning None if the page is not a metapage.
+    pub fn from_page(page: &[u8]) -> Option<Self> {
+        bincode::deserialize::<Self>(page)
+            .ok()
+            .filter(|meta| meta.magic == METAPAGE_MAGIC)
+    }
+
+    /// Allocate the metapage of a new relation, which should not have any page yet.
+    pub fn init(&self, buffer_pool: &mut BufferPool, rel: &Relation) -> Result<()> {
+        let buffer = buffer_pool.alloc_buffer(rel)?;
+        assert_eq!(
+            rel.borrow().pager.size()?,
+            METAPAGE_NUMBER,
+            "Expected metapage to be the first relation page"
+        );
+        buffer_pool.unpin_buffer(buffer, false)?;
+
+        self.write(buffer_pool, rel)
+    }
+
+    /// Read the metapage of the given relation through the buffer pool.
+    ///
+    /// Return error if the first relation page is not a metapage.
+    pub fn read(buffer_pool: &mut BufferPool, rel: &Relation) -> Result<Self> {
+        let buffer = buffer_pool.fetch_buffer(rel, METAPAGE_NUMBER)?;
+        let page = buffer_pool.get_page(&buffer).borrow().bytes();
+        buffer_pool.unpin_buffer(buffer, false)?;
+
+        match Self::from_page(&page) {
+            Some(meta) => Ok(meta),
+            None => bail!(Error::InvalidMetaPage(rel.borrow().rel_name.clone())),
+        }
+    }
+
+    /// Write the metapage of the given relation on buffer pool. It will be written to disk when
+    /// the buffer is flushed.
+    pub fn write(&self, buffer_pool: &mut BufferPool, rel: &Relation) -> Result<()> {
+        let mut data = bincode::serialize(self)?;
+        data.resize(PAGE_SIZE, u8::default());
+
+        let buffer = buffer_pool.fetch_buffer(rel, METAPAGE_NUMBER)?;
+        buffer_pool
+            .get_page(&buffer)
+            .borrow_mut()
+            .write_from_vec(data);
+        buffer_pool.unpin_buffer(buffer, true)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::rel::{database_path, RelationData};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_metapage_write_read() -> Result<()> {
+        let dir = tempdir()?;
+        let db_data = dir.path().to_string_lossy();
+        std::fs::create_dir(database_path(&db_data, 1))?;
+        let rel = RelationData::open(10, &db_data, 1, "t")?;
+        let mut buffer_pool = BufferPool::new(10);
+
+        let mut meta = MetaPage::new(2);
+        meta.init(&mut buffer_pool, &rel)?;
+        assert_eq!(MetaPage::read(&mut buffer_pool, &rel)?, meta);
+
+        meta.total_pages = 3;
+        meta.free_space_page = 3;
+        meta.write(&mut buffer_pool, &rel)?;
+        assert_eq!(MetaPage::read(&mut buffer_pool, &rel)?, meta);
+
+        assert_eq!(MetaPage::from_page(&[0; PAGE_SIZE]), None);
+        Ok(())
+    }
+}
@@ -1,2 +1,6 @@
 pub mod heap;
+pub mod metapage;
+pub mod progress;
+pub mod reloptions;
+pub mod tableam;
 pub mod tuple;
@@ -0,0 +1,144 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::storage::{pager::PageNumber, rel::Relation};
+use crate::Oid;
+
+/// Number of pages processed between two progress reports of the same operation.
+pub const PROGRESS_INTERVAL_PAGES: PageNumber = 128;
+
+/// Function called with the progress of long running operations. Returning an error interrupts
+/// the operation, which fails with the returned error.
+pub type ProgressHandler = Box<dyn FnMut(&Progress) -> Result<()>>;
+
+thread_local! {
+    /// Handler that receives the progress reports of the operations of this thread.
+    static HANDLER: RefCell<Option<ProgressHandler>> = RefCell::new(None);
+
+    /// Progress of the operations running on this thread, in the order that they were started.
+    ///
+    /// Like the sessions registry, operations of other threads or processes are not visible.
+    static ACTIVE: RefCell<Vec<Progress>> = RefCell::default();
+}
+
+/// Kind of a long running operation that reports its progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressCommand {
+    /// Sequential scan of all pages of a heap relation.
+    Scan,
+
+    /// Vacuum of a heap relation.
+    Vacuum,
+}
+
+impl fmt::Display for ProgressCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scan => write!(f, "SCAN"),
+            Self::Vacuum => write!(f, "VACUUM"),
+        }
+    }
+}
+
+/// Progress of a long running operation over a relation, like a row of the pg_stat_progress_*
+/// views of Postgres.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// Operation that is running.
+    pub command: ProgressCommand,
+
+    /// Oid of the relation processed by the operation.
+    pub relid: Oid,
+
+    /// Name of the relation processed by the operation.
+    pub rel_name: String,
+
+    /// Number of pages already processed.
+    pub pages_done: PageNumber,
+
+    /// Total number of pages that the operation is expected to process.
+    pub pages_total: PageNumber,
+}
+
+/// Set the handler of the progress reports of this thread, returning the previous one. A None
+/// handler disables progress reports.
+pub fn set_handler(handler: Option<ProgressHandler>) -> Option<ProgressHandler> {
+    HANDLER.with(|current| current.replace(handler))
+}
+
+/// Return the progress of all operations running on this thread.
+pub fn active() -> Vec<Progress> {
+    ACTIVE.with(|active| active.borrow().clone())
+}
+
+/// Track the progress of a single operation, reporting it to the progress handler every
+/// [PROGRESS_INTERVAL_PAGES] pages and once all pages are processed.
+///
+/// The operation is listed by [active] until the tracker is dropped.
+pub struct ProgressTracker {
+    /// Number of pages processed when the next report is made.
+    next_report: PageNumber,
+}
+
+impl ProgressTracker {
+    /// Start tracking a new operation that will process the given total of pages of a relation.
+    pub fn new(command: ProgressCommand, rel: &Relation, pages_total: PageNumber) -> Self {
+        let rel = rel.borrow();
+        let progress = Progress {
+            command,
+            relid: rel.oid,
+            rel_name: rel.rel_name.clone(),
+            pages_done: 0,
+            pages_total,
+        };
+        ACTIVE.with(|active| active.borrow_mut().push(progress));
+
+        Self {
+            next_report: PROGRESS_INTERVAL_PAGES.min(pages_total),
+        }
+    }
+
+    /// Record that another page was processed, reporting the progress if needed.
+    ///
+    /// Return error if the progress handler interrupted the operation.
+    pub fn page_done(&mut self) -> Result<()> {
+        let progress = ACTIVE.with(|active| {
+            let mut active = active.borrow_mut();
+            let progress = active
+                .last_mut()
+                .expect("progress tracker without active operation");
+            progress.pages_done += 1;
+            // Only reported progress is copied, so most pages don't clone the relation name.
+            (progress.pages_done == self.next_report).then(|| progress.clone())
+        });
+        let progress = match progress {
+            Some(progress) => progress,
+            None => return Ok(()),
+        };
+        self.next_report = (self.next_report + PROGRESS_INTERVAL_PAGES).min(progress.pages_total);
+
+        // The handler is taken while it runs, so operations started by the handler itself don't
+        // call it again.
+        let handler = HANDLER.with(|handler| handler.borrow_mut().take());
+        if let Some(mut handler) = handler {
+            let result = handler(&progress);
+            HANDLER.with(|current| {
+                let mut current = current.borrow_mut();
+                if current.is_none() {
+                    *current = Some(handler);
+                }
+            });
+            result?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for ProgressTracker {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| active.borrow_mut().pop());
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::{bail, Result};
+
+use crate::storage::pager::PAGE_SIZE;
+
+/// Default fillfactor of heap relations, which fills the pages completely.
+pub const HEAP_DEFAULT_FILLFACTOR: u8 = 100;
+
+/// Smallest fillfactor allowed on heap relations.
+pub const HEAP_MIN_FILLFACTOR: u8 = 10;
+
+/// Errors related with relation options.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("unrecognized parameter \"{0}\"")]
+    UnrecognizedParameter(String),
+
+    #[error("invalid value for integer option \"{0}\": {1}")]
+    InvalidValue(String, String),
+
+    #[error("value {value} out of bounds for option \"{name}\"")]
+    OutOfBounds { name: String, value: String },
+}
+
+/// Options of a relation parsed from the `name=value` entries stored on pg_class.reloptions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelOptions {
+    /// Percentage of each heap page filled by inserts. The rest is left free for future versions
+    /// of the rows of the page, like the space used by HOT updates on Postgres.
+    pub fillfactor: u8,
+}
+
+impl Default for RelOptions {
+    fn default() -> Self {
+        Self {
+            fillfactor: HEAP_DEFAULT_FILLFACTOR,
+        }
+    }
+}
+
+impl RelOptions {
+    /// Parse the options stored on pg_class.reloptions. Options not stored have their default
+    /// values.
+    ///
+    /// Return error if any option is unknown or has an invalid value.
+    pub fn parse(reloptions: &[String]) -> Result<Self> {
+        let mut options = Self::default();
+        for option in reloptions {
+            let (name, value) = option.split_once('=').unwrap_or((option, ""));
+            match name {
+                "fillfactor" => options.fillfactor = parse_fillfactor(value)?,
+                _ => bail!(Error::UnrecognizedParameter(name.to_string())),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Return the bytes of each heap page that inserts should leave free.
+    pub fn free_space_reserved(&self) -> usize {
+        PAGE_SIZE * (100 - self.fillfactor as usize) / 100
+    }
+}
+
+/// Set the given option on the reloptions of pg_class, replacing its previous value.
+///
+/// Return error if the option is unknown or the value is invalid.
+pub fn set_reloption(reloptions: &mut Vec<String>, name: &str, value: &str) -> Result<()> {
+    let name = name.to_lowercase();
+    let option = format!("{}={}", name, value);
+    RelOptions::parse(std::slice::from_ref(&option))?;
+
+    reset_reloption(reloptions, &name)?;
+    reloptions.push(option);
+    Ok(())
+}
+
+/// Remove the given option from the reloptions of pg_class, so it has its default value again.
+///
+/// Return error if the option is unknown.
+pub fn reset_reloption(reloptions: &mut Vec<String>, name: &str) -> Result<()> {
+    let name = name.to_lowercase();
+    if name != "fillfactor" {
+        bail!(Error::UnrecognizedParameter(name));
+    }
+    reloptions.retain(|option| option.split('=').next() != Some(name.as_str()));
+    Ok(())
+}
+
+/// Format reloptions like Postgres shows the text array of pg_class.reloptions, e.g
+/// `{fillfactor=70}`.
+pub fn format_reloptions(reloptions: &[String]) -> String {
+    format!("{{{}}}", reloptions.join(","))
+}
+
+fn parse_fillfactor(value: &str) -> Result<u8> {
+    let fillfactor = match value.parse::<i64>() {
+        Ok(fillfactor) => fillfactor,
+        Err(_) => bail!(Error::InvalidValue(
+            String::from("fillfactor"),
+            value.to_string()
+        )),
+    };
+    if fillfactor < HEAP_MIN_FILLFACTOR as i64 || fillfactor > HEAP_DEFAULT_FILLFACTOR as i64 {
+        bail!(Error::OutOfBounds {
+            name: String::from("fillfactor"),
+            value: value.to_string(),
+        });
+    }
+    Ok(fillfactor as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reloptions() -> Result<()> {
+        assert_eq!(RelOptions::parse(&[])?, RelOptions::default());
+        assert_eq!(RelOptions::default().free_space_reserved(), 0);
+
+        let mut reloptions = Vec::new();
+        set_reloption(&mut reloptions, "FillFactor", "70")?;
+        set_reloption(&mut reloptions, "fillfactor", "50")?;
+        assert_eq!(reloptions, vec!["fillfactor=50"]);
+        assert_eq!(format_reloptions(&reloptions), "{fillfactor=50}");
+
+        let options = RelOptions::parse(&reloptions)?;
+        assert_eq!(options.fillfactor, 50);
+        assert_eq!(options.free_space_reserved(), PAGE_SIZE / 2);
+
+        for (name, value, err) in [
+            (
+                "fillfactor",
+                "5",
+                Error::OutOfBounds {
+                    name: String::from("fillfactor"),
+                    value: String::from("5"),
+                },
+            ),
+            (
+                "fillfactor",
+                "full",
+                Error::InvalidValue(String::from("fillfactor"), String::from("full")),
+            ),
+            (
+                "autovacuum",
+                "on",
+                Error::UnrecognizedParameter(String::from("autovacuum")),
+            ),
+        ] {
+            let result = set_reloption(&mut reloptions, name, value);
+            assert_eq!(result.unwrap_err().downcast::<Error>()?, err);
+        }
+        assert_eq!(reloptions, vec!["fillfactor=50"]);
+
+        reset_reloption(&mut reloptions, "fillfactor")?;
+        assert!(reloptions.is_empty());
+        assert!(reset_reloption(&mut reloptions, "autovacuum").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    storage::{rel::Relation, BufferPool},
+    Oid,
+};
+
+use super::heap::{heap_create_storage, heap_insert, heap_scan, heap_truncate, HeapTuple};
+
+/// Fixed oid of the builtin heap table access method.
+pub const HEAP_TABLE_AM_OID: Oid = 2;
+
+/// Errors related with table access methods.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("table access method {0} does not exist")]
+    AccessMethodNotFound(Oid),
+
+    #[error("table access method {0} already exists")]
+    DuplicatedAccessMethod(Oid),
+}
+
+/// Interface that storage engines implement to store and retrieve the tuples of a relation.
+///
+/// Each relation records on pg_class the oid of the access method that owns its storage, so
+/// alternative formats (e.g an append-only or columnar heap) can be plugged in without changing
+/// the engine.
+pub trait TableAccessMethod {
+    /// Name of the access method.
+    fn name(&self) -> &str;
+
+    /// Initialize the storage of a relation that was just created.
+    fn relation_create(&self, buffer_pool: &mut BufferPool, rel: &Relation) -> Result<()>;
+
+    /// Insert a new tuple into the given relation.
+    fn tuple_insert(
+        &self,
+        buffer_pool: &mut BufferPool,
+        rel: &Relation,
+        tuple: &HeapTuple,
+    ) -> Result<()>;
+
+    /// Return all tuples stored on the given relation.
+    fn scan(&self, buffer_pool: &mut BufferPool, rel: &Relation) -> Result<Vec<HeapTuple>>;
+
+    /// Reclaim the storage space of the given relation that is not used anymore. Access methods
+    /// that never leave unused space can keep the default, which does nothing.
+    fn vacuum(&self, _buffer_pool: &mut BufferPool, _rel: &Relation) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The builtin access method that store tuples on slotted heap pages.
+pub struct HeapAccessMethod;
+
+impl TableAccessMethod for HeapAccessMethod {
+    fn name(&self) -> &str {
+        "heap"
+    }
+
+    fn relation_create(&self, buffer_pool: &mut BufferPool, rel: &Relation) -> Result<()> {
+        heap_create_storage(buffer_pool, rel, HEAP_TABLE_AM_OID)
+    }
+
+    fn tuple_insert(
+        &self,
+        buffer_pool: &mut BufferPool,
+        rel: &Relation,
+        tuple: &HeapTuple,
+    ) -> Result<()> {
+        heap_insert(buffer_pool, rel, tuple)
+    }
+
+    fn scan(&self, buffer_pool: &mut BufferPool, rel: &Relation) -> Result<Vec<HeapTuple>> {
+        heap_scan(buffer_pool, rel)
+    }
+
+    fn vacuum(&self, buffer_pool: &mut BufferPool, rel: &Relation) -> Result<()> {
+        heap_truncate(buffer_pool, rel)?;
+        Ok(())
+    }
+}
+
+/// Hold all table access methods available, indexed by their oid.
+pub struct TableAmRegistry {
+    methods: HashMap<Oid, Box<dyn TableAccessMethod>>,
+}
+
+impl TableAmRegistry {
+    /// Register a new table access method using the given oid.
+    ///
+    /// Return error if the oid is already used by another access method.
+    pub fn register(&mut self, oid: Oid, am: Box<dyn TableAccessMethod>) -> Result<()> {
+        if self.methods.contains_key(&oid) {
+            bail!(Error::DuplicatedAccessMethod(oid));
+        }
+        self.methods.insert(oid, am);
+        Ok(())
+    }
+
+    /// Return the table access method of the given oid.
+    pub fn get(&self, oid: Oid) -> Result<&dyn TableAccessMethod> {
+        match self.methods.get(&oid) {
+            Some(am) => Ok(am.as_ref()),
+            None => bail!(Error::AccessMethodNotFound(oid)),
+        }
+    }
+}
+
+impl Default for TableAmRegistry {
+    /// Create a new registry containing only the builtin heap access method.
+    fn default() -> Self {
+        let mut methods: HashMap<Oid, Box<dyn TableAccessMethod>> = HashMap::new();
+        methods.insert(HEAP_TABLE_AM_OID, Box::new(HeapAccessMethod));
+        Self { methods }
+    }
+}
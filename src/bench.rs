@@ -0,0 +1,207 @@
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+
+use crate::engine::Engine;
+use crate::initdb::init_database;
+use crate::storage::BufferPool;
+
+/// Name of database created to run the benchmark.
+const BENCH_DB_NAME: &str = "bench";
+
+/// Errors related with benchmark runs.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("data directory {0} already exists, the benchmark should run on a new one")]
+    DataDirectoryExists(String),
+
+    #[error("select percent should be between 0 and 100, got {0}")]
+    InvalidSelectPercent(u32),
+}
+
+/// Options of a benchmark run.
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Total number of transactions to execute.
+    pub transactions: usize,
+
+    /// Percentage of transactions that are selects, the others are inserts.
+    pub select_percent: u32,
+
+    /// Size of buffer pool used by the engine.
+    pub buffer_pool_size: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            transactions: 1000,
+            select_percent: 50,
+            buffer_pool_size: 120,
+        }
+    }
+}
+
+/// Latencies of all transactions of the same kind.
+#[derive(Debug, Default)]
+pub struct Latencies {
+    durations: Vec<Duration>,
+}
+
+impl Latencies {
+    /// Return the number of transactions executed.
+    pub fn count(&self) -> usize {
+        self.durations.len()
+    }
+
+    /// Return the latency that p percent of transactions did not exceed, or zero if no transaction
+    /// was executed.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.durations.is_empty() {
+            return Duration::ZERO;
+        }
+        let mut durations = self.durations.clone();
+        durations.sort();
+
+        let rank = (p / 100.0 * durations.len() as f64).ceil() as usize;
+        durations[rank.clamp(1, durations.len()) - 1]
+    }
+}
+
+/// Result of a benchmark run.
+#[derive(Debug, Default)]
+pub struct BenchReport {
+    /// Wall time spent executing all transactions.
+    pub duration: Duration,
+
+    /// Latencies of select transactions.
+    pub selects: Latencies,
+
+    /// Latencies of insert transactions.
+    pub inserts: Latencies,
+}
+
+impl BenchReport {
+    /// Return the number of transactions executed per second.
+    pub fn tps(&self) -> f64 {
+        let transactions = self.selects.count() + self.inserts.count();
+        transactions as f64 / self.duration.as_secs_f64()
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "number of transactions: {}",
+            self.selects.count() + self.inserts.count()
+        )?;
+        writeln!(f, "duration: {:.3} s", self.duration.as_secs_f64())?;
+        writeln!(f, "tps: {:.2}", self.tps())?;
+        for (kind, latencies) in [("select", &self.selects), ("insert", &self.inserts)] {
+            writeln!(
+                f,
+                "{}: count {}, latency p50 {:.3} ms, p90 {:.3} ms, p99 {:.3} ms",
+                kind,
+                latencies.count(),
+                millis(latencies.percentile(50.0)),
+                millis(latencies.percentile(90.0)),
+                millis(latencies.percentile(99.0)),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Initialize the benchmark tables on a new db_data and run a mix of selects and inserts using a
+/// single client, reporting the throughput and latencies.
+///
+/// Transactions are interleaved deterministically, so runs with the same options execute the
+/// same statements in the same order.
+pub fn run(db_data: &Path, options: &BenchOptions) -> Result<BenchReport> {
+    if db_data.exists() {
+        bail!(Error::DataDirectoryExists(
+            db_data.to_string_lossy().to_string()
+        ));
+    }
+    if options.select_percent > 100 {
+        bail!(Error::InvalidSelectPercent(options.select_percent));
+    }
+
+    init_database(&db_data.to_path_buf(), BENCH_DB_NAME)?;
+
+    let buffer = BufferPool::new(options.buffer_pool_size);
+    let mut engine = Engine::new(buffer, &db_data.to_string_lossy())?;
+    engine.use_database(BENCH_DB_NAME)?;
+    engine.exec("CREATE TABLE bench_accounts(aid int, bid int, abalance int);")?;
+
+    let mut report = BenchReport::default();
+    let start = Instant::now();
+
+    for i in 0..options.transactions {
+        let is_select = is_select_transaction(i, options.select_percent);
+        let command = if is_select {
+            String::from("SELECT * FROM bench_accounts;")
+        } else {
+            format!(
+                "INSERT INTO bench_accounts(aid, bid, abalance) VALUES({}, {}, 0);",
+                i,
+                i % 10
+            )
+        };
+
+        let transaction_start = Instant::now();
+        engine.exec(&command)?;
+        let latency = transaction_start.elapsed();
+
+        if is_select {
+            report.selects.durations.push(latency);
+        } else {
+            report.inserts.durations.push(latency);
+        }
+    }
+
+    report.duration = start.elapsed();
+
+    Ok(report)
+}
+
+/// Return true if the transaction i should be a select, spreading select_percent selects evenly
+/// on each 100 transactions.
+fn is_select_transaction(i: usize, select_percent: u32) -> bool {
+    let select_percent = select_percent as usize;
+    (i + 1) * select_percent / 100 != i * select_percent / 100
+}
+
+/// Return the given duration in milliseconds.
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_bench_run() -> Result<()> {
+        let dir = tempdir()?;
+        let db_data = dir.path().join("data");
+
+        let options = BenchOptions {
+            transactions: 20,
+            select_percent: 25,
+            ..BenchOptions::default()
+        };
+        let report = run(&db_data, &options)?;
+
+        assert_eq!(report.selects.count(), 5);
+        assert_eq!(report.inserts.count(), 15);
+        assert!(report.selects.percentile(50.0) <= report.selects.percentile(99.0));
+
+        assert!(run(&db_data, &options).is_err());
+        Ok(())
+    }
+}
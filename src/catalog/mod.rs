@@ -1,34 +1,86 @@
 use anyhow::{bail, Result};
-use std::path::Path;
 
-use crate::{access::heap::heap_iter, new_object_id, storage::BufferPool, Oid};
+use crate::{
+    access::{
+        heap::{heap_delete, heap_insert, heap_iter, heap_iter_items, HeapTuple},
+        reloptions::RelOptions,
+        tableam::HEAP_TABLE_AM_OID,
+    },
+    new_object_id,
+    storage::{
+        rel::{relation_path, Relation, RelationData},
+        BufferPool,
+    },
+    Oid,
+};
 
-use self::{pg_attribute::PgAttribute, pg_class::PgClass};
+use self::{
+    pg_attribute::PgAttribute, pg_class::PgClass, pg_database::PgDatabase, pg_policy::PgPolicy,
+    pg_proc::PgProc,
+};
 
 pub mod heap;
 pub mod pg_attribute;
+pub mod pg_audit;
 pub mod pg_class;
+pub mod pg_database;
+pub mod pg_index;
+pub mod pg_policy;
+pub mod pg_proc;
+pub mod pg_type;
 
-/// Genereate a new relation oid that is unique within the database of the given db data.
-pub fn new_relation_oid(db_data: &str, db_name: &str) -> Oid {
-    let dbpath = Path::new(db_data).join(db_name);
+/// Name of the directory inside db data that stores the catalogs shared across all databases.
+pub const GLOBAL_DB_NAME: &str = "global";
+
+/// Oid used as database oid by relations shared across all databases, which are stored on the
+/// [GLOBAL_DB_NAME] directory.
+pub const GLOBAL_DB_OID: Oid = 0;
 
+/// Genereate a new relation oid that is unique within the database of the given db data.
+pub fn new_relation_oid(db_data: &str, db_oid: Oid) -> Oid {
     loop {
         let oid = new_object_id();
-        if !dbpath.join(oid.to_string()).exists() {
+        if !relation_path(db_data, db_oid, oid).exists() {
             return oid;
         }
     }
 }
 
+/// Generate a new database oid that is not used by any database registered on pg_database.
+pub fn new_database_oid(buffer_pool: &mut BufferPool, db_data: &str) -> Result<Oid> {
+    let pg_database = PgDatabase::get_relation(db_data)?;
+
+    let mut used_oids = Vec::new();
+    heap_iter(buffer_pool, &pg_database, |tuple| -> Result<()> {
+        used_oids.push(bincode::deserialize::<PgDatabase>(tuple)?.oid);
+        Ok(())
+    })?;
+
+    loop {
+        let oid = new_object_id();
+        if !used_oids.contains(&oid) {
+            return Ok(oid);
+        }
+    }
+}
+
 /// Errors related with system catalog relation operations.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("relation {0} does not exist")]
     RelationNotFound(String),
+
+    #[error("database {0} does not exist")]
+    DatabaseNotFound(String),
+
+    #[error("permission denied: \"{0}\" is a system catalog")]
+    SystemCatalog(String),
 }
 
 /// Struct catalog hold rountines and utilities to deal with system catalog relations.
+///
+/// Only the catalogs shared across all databases are read directly. Catalogs of a single database
+/// are read through the [DatabaseCatalog] returned by [Catalog::open_database].
 pub struct Catalog {
     /// Base data directoy.
     db_data: String,
@@ -42,16 +94,102 @@ impl Catalog {
         }
     }
 
+    /// Open the catalogs of the given database, e.g when a session connects to it.
+    ///
+    /// Return error if the database does not exist.
+    pub fn open_database(
+        &self,
+        buffer_pool: &mut BufferPool,
+        db_name: &str,
+    ) -> Result<DatabaseCatalog> {
+        Ok(DatabaseCatalog {
+            db_data: self.db_data.clone(),
+            db_oid: self.get_database_oid(buffer_pool, db_name)?,
+            db_name: db_name.to_string(),
+        })
+    }
+
+    /// Return the oid of the given database name.
+    pub fn get_database_oid(&self, buffer_pool: &mut BufferPool, db_name: &str) -> Result<Oid> {
+        let pg_database = PgDatabase::get_relation(&self.db_data)?;
+
+        let mut oid = None;
+
+        heap_iter(buffer_pool, &pg_database, |tuple| -> Result<()> {
+            if oid.is_none() {
+                let database = bincode::deserialize::<PgDatabase>(tuple)?;
+                if database.datname == db_name {
+                    oid = Some(database.oid);
+                }
+            }
+            Ok(())
+        })?;
+
+        match oid {
+            Some(oid) => Ok(oid),
+            None => bail!(Error::DatabaseNotFound(db_name.to_string())),
+        }
+    }
+}
+
+/// Handle to the catalogs of a single database. The database oid is resolved once when the
+/// handle is opened, so every lookup reads the catalogs of the same database even if another
+/// database with the same name is created later.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatabaseCatalog {
+    /// Base data directoy.
+    db_data: String,
+
+    /// Oid of the database.
+    db_oid: Oid,
+
+    /// Name of the database when the handle was opened.
+    db_name: String,
+}
+
+impl DatabaseCatalog {
+    /// Return the oid of the database.
+    pub fn db_oid(&self) -> Oid {
+        self.db_oid
+    }
+
+    /// Return the name of the database.
+    pub fn db_name(&self) -> &str {
+        &self.db_name
+    }
+
+    /// Return the oid of the database that stores the files of the given relation, which is
+    /// [GLOBAL_DB_OID] for relations shared across all databases.
+    pub fn relation_db_oid(&self, pg_class: &PgClass) -> Oid {
+        if pg_class.relisshared {
+            GLOBAL_DB_OID
+        } else {
+            self.db_oid
+        }
+    }
+
+    /// Open the relation described by the given pg_class entry, refreshing the options of the
+    /// cached relation with its reloptions.
+    pub fn open_relation(&self, pg_class: &PgClass) -> Result<Relation> {
+        let rel = RelationData::open(
+            pg_class.oid,
+            &self.db_data,
+            self.relation_db_oid(pg_class),
+            &pg_class.relname,
+        )?;
+        rel.borrow_mut().options = RelOptions::parse(&pg_class.reloptions)?;
+        Ok(rel)
+    }
+
     /// Return all attributes to the given relation name.
     pub fn get_attributes_from_relation(
         &self,
         buffer_pool: &mut BufferPool,
-        db_name: &str,
         rel_name: &str,
     ) -> Result<Vec<PgAttribute>> {
-        let pg_attribute = PgAttribute::get_relation(&self.db_data, db_name)?;
+        let pg_attribute = PgAttribute::get_relation(&self.db_data, self.db_oid)?;
 
-        let rel_oid = self.get_oid_relation(buffer_pool, db_name, rel_name)?;
+        let rel_oid = self.get_oid_relation(buffer_pool, rel_name)?;
 
         let mut attributes = Vec::new();
 
@@ -68,37 +206,147 @@ impl Catalog {
     }
 
     /// Return the oid of the given relation name.
-    pub fn get_oid_relation(
-        &self,
-        buffer_pool: &mut BufferPool,
-        db_name: &str,
-        rel_name: &str,
-    ) -> Result<Oid> {
+    pub fn get_oid_relation(&self, buffer_pool: &mut BufferPool, rel_name: &str) -> Result<Oid> {
+        Ok(self.get_pg_class(buffer_pool, rel_name)?.oid)
+    }
+
+    /// Return the pg_class entry of the given relation name.
+    pub fn get_pg_class(&self, buffer_pool: &mut BufferPool, rel_name: &str) -> Result<PgClass> {
         // TODO: The catalog relations should also be stored inside pg_class.
+        let system_catalog = |oid, relisshared| {
+            Ok(PgClass {
+                oid,
+                relname: rel_name.to_string(),
+                relam: HEAP_TABLE_AM_OID,
+                relisshared,
+                reloptions: Vec::new(),
+            })
+        };
+
         match rel_name {
-            "pg_class" => Ok(pg_class::RELATION_OID),
-            "pg_attribute" => Ok(pg_attribute::RELATION_OID),
+            pg_class::RELATION_NAME => system_catalog(pg_class::RELATION_OID, false),
+            pg_attribute::RELATION_NAME => system_catalog(pg_attribute::RELATION_OID, false),
+            pg_type::RELATION_NAME => system_catalog(pg_type::RELATION_OID, false),
+            pg_index::RELATION_NAME => system_catalog(pg_index::RELATION_OID, false),
+            pg_proc::RELATION_NAME => system_catalog(pg_proc::RELATION_OID, false),
+            pg_policy::RELATION_NAME => system_catalog(pg_policy::RELATION_OID, false),
+            pg_audit::RELATION_NAME => system_catalog(pg_audit::RELATION_OID, false),
+            pg_database::RELATION_NAME => system_catalog(pg_database::RELATION_OID, true),
             _ => {
-                let pg_class_rel = PgClass::get_relation(&self.db_data, db_name)?;
+                let pg_class_rel = PgClass::get_relation(&self.db_data, self.db_oid)?;
 
-                let mut oid = None;
+                let mut entry = None;
 
                 heap_iter(buffer_pool, &pg_class_rel, |tuple| -> Result<()> {
-                    // Do nothing if the oid is already founded.
-                    if oid.is_none() {
+                    // Do nothing if the relation is already founded.
+                    if entry.is_none() {
                         let pg_class = bincode::deserialize::<PgClass>(&tuple)?;
                         if pg_class.relname == rel_name {
-                            oid = Some(pg_class.oid);
+                            entry = Some(pg_class);
                         }
                     }
                     Ok(())
                 })?;
 
-                match oid {
-                    Some(oid) => Ok(oid),
+                match entry {
+                    Some(entry) => Ok(entry),
                     None => bail!(Error::RelationNotFound(rel_name.to_string())),
                 }
             }
         }
     }
+
+    /// Replace the reloptions stored on the pg_class entry of the given relation, which should be
+    /// already validated.
+    ///
+    /// Return error if the relation does not exist or it is a system catalog, whose entries are not
+    /// stored on pg_class.
+    pub fn set_reloptions(
+        &self,
+        buffer_pool: &mut BufferPool,
+        rel_name: &str,
+        reloptions: Vec<String>,
+    ) -> Result<()> {
+        let pg_class_rel = PgClass::get_relation(&self.db_data, self.db_oid)?;
+
+        let mut entry = None;
+        heap_iter_items(buffer_pool, &pg_class_rel, |ctid, tuple| -> Result<()> {
+            if entry.is_none() {
+                let pg_class = bincode::deserialize::<PgClass>(tuple)?;
+                if pg_class.relname == rel_name {
+                    entry = Some((ctid, pg_class));
+                }
+            }
+            Ok(())
+        })?;
+
+        let (ctid, mut pg_class) = match entry {
+            Some(entry) => entry,
+            None => {
+                self.get_pg_class(buffer_pool, rel_name)?;
+                bail!(Error::SystemCatalog(rel_name.to_string()));
+            }
+        };
+
+        // There is no heap update yet, so the entry is replaced by a new version of it.
+        pg_class.reloptions = reloptions;
+        heap_delete(buffer_pool, &pg_class_rel, ctid)?;
+        heap_insert(
+            buffer_pool,
+            &pg_class_rel,
+            &HeapTuple::new(bincode::serialize(&pg_class)?),
+        )
+    }
+
+    /// Return the pg_class entries of all relations created on the database.
+    pub fn get_relations(&self, buffer_pool: &mut BufferPool) -> Result<Vec<PgClass>> {
+        let pg_class_rel = PgClass::get_relation(&self.db_data, self.db_oid)?;
+
+        let mut relations = Vec::new();
+
+        heap_iter(buffer_pool, &pg_class_rel, |tuple| -> Result<()> {
+            relations.push(bincode::deserialize::<PgClass>(tuple)?);
+            Ok(())
+        })?;
+
+        Ok(relations)
+    }
+
+    /// Return the row-level security policies of the given relation.
+    pub fn get_policies(&self, buffer_pool: &mut BufferPool, relid: Oid) -> Result<Vec<PgPolicy>> {
+        let pg_policy_rel = PgPolicy::get_relation(&self.db_data, self.db_oid)?;
+
+        let mut policies = Vec::new();
+
+        heap_iter(buffer_pool, &pg_policy_rel, |tuple| -> Result<()> {
+            let policy = bincode::deserialize::<PgPolicy>(tuple)?;
+            if policy.polrelid == relid {
+                policies.push(policy);
+            }
+            Ok(())
+        })?;
+
+        Ok(policies)
+    }
+
+    /// Return the pg_proc entries of all overloads of the given function name.
+    pub fn get_functions(
+        &self,
+        buffer_pool: &mut BufferPool,
+        proname: &str,
+    ) -> Result<Vec<PgProc>> {
+        let pg_proc_rel = PgProc::get_relation(&self.db_data, self.db_oid)?;
+
+        let mut functions = Vec::new();
+
+        heap_iter(buffer_pool, &pg_proc_rel, |tuple| -> Result<()> {
+            let function = bincode::deserialize::<PgProc>(tuple)?;
+            if function.proname == proname {
+                functions.push(function);
+            }
+            Ok(())
+        })?;
+
+        Ok(functions)
+    }
 }
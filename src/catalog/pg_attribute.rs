@@ -29,11 +29,11 @@ pub struct PgAttribute {
 
 impl PgAttribute {
     /// Return the pg_attribute Relation.
-    pub fn get_relation(db_data: &str, db_name: &str) -> Result<Relation> {
+    pub fn get_relation(db_data: &str, db_oid: Oid) -> Result<Relation> {
         Ok(RelationData::open(
             RELATION_OID,
             db_data,
-            db_name,
+            db_oid,
             RELATION_NAME,
         )?)
     }
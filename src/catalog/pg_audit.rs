@@ -0,0 +1,39 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    storage::rel::{Relation, RelationData},
+    Oid,
+};
+
+/// Fixed oid of pg_audit relation.
+pub const RELATION_OID: Oid = 9410;
+
+pub const RELATION_NAME: &str = "pg_audit";
+
+/// The catalog pg_audit is an append-only trail of the statements that modified a database, which
+/// are recorded only while auditing is enabled on the engine.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PgAudit {
+    /// Time when the statement was executed, in microseconds since the Unix epoch.
+    pub audtime: u64,
+
+    /// Role of the session that executed the statement. None is the owner of all relations.
+    pub audrole: Option<String>,
+
+    /// Command tag of the statement, e.g `INSERT` or `CREATE TABLE`.
+    pub audcommand: String,
+
+    /// SQL text of the statement.
+    pub audstatement: String,
+
+    /// Number of rows affected by the statement, if the command handles rows.
+    pub audrows: Option<u64>,
+}
+
+impl PgAudit {
+    /// Return the pg_audit Relation.
+    pub fn get_relation(db_data: &str, db_oid: Oid) -> Result<Relation> {
+        RelationData::open(RELATION_OID, db_data, db_oid, RELATION_NAME)
+    }
+}
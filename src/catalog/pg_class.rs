@@ -19,15 +19,24 @@ pub struct PgClass {
 
     /// Relation name.
     pub relname: String,
+
+    /// Oid of the table access method used to store the relation.
+    pub relam: Oid,
+
+    /// True if the relation is shared across all databases of the cluster.
+    pub relisshared: bool,
+
+    /// Access-method-specific options, as `name=value` strings.
+    pub reloptions: Vec<String>,
 }
 
 impl PgClass {
     /// Return the pg_class Relation.
-    pub fn get_relation(db_data: &str, db_name: &str) -> Result<Relation> {
+    pub fn get_relation(db_data: &str, db_oid: Oid) -> Result<Relation> {
         Ok(RelationData::open(
             RELATION_OID,
             db_data,
-            db_name,
+            db_oid,
             RELATION_NAME,
         )?)
     }
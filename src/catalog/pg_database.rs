@@ -0,0 +1,32 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    storage::rel::{Relation, RelationData},
+    Oid,
+};
+
+use super::GLOBAL_DB_OID;
+
+/// Fixed oid of pg_database relation.
+pub const RELATION_OID: Oid = 1262;
+
+pub const RELATION_NAME: &str = "pg_database";
+
+/// The catalog pg_database stores information about the available databases. Unlike most system
+/// catalogs, pg_database is shared across all databases of a cluster.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PgDatabase {
+    /// OID of database.
+    pub oid: Oid,
+
+    /// Database name.
+    pub datname: String,
+}
+
+impl PgDatabase {
+    /// Return the pg_database Relation.
+    pub fn get_relation(db_data: &str) -> Result<Relation> {
+        RelationData::open(RELATION_OID, db_data, GLOBAL_DB_OID, RELATION_NAME)
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    storage::rel::{Relation, RelationData},
+    Oid,
+};
+
+/// Fixed oid of pg_index relation.
+pub const RELATION_OID: Oid = 2610;
+
+pub const RELATION_NAME: &str = "pg_index";
+
+/// The catalog pg_index contains part of the information about indexes. The rest is mostly in
+/// pg_class.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PgIndex {
+    /// The OID of the pg_class entry for this index.
+    pub indexrelid: Oid,
+
+    /// The OID of the pg_class entry for the table this index is for.
+    pub indrelid: Oid,
+
+    /// The attribute numbers of table columns that this index indexes.
+    pub indkey: Vec<usize>,
+}
+
+impl PgIndex {
+    /// Return the pg_index Relation.
+    pub fn get_relation(db_data: &str, db_oid: Oid) -> Result<Relation> {
+        RelationData::open(RELATION_OID, db_data, db_oid, RELATION_NAME)
+    }
+}
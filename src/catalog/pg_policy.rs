@@ -0,0 +1,43 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    storage::rel::{Relation, RelationData},
+    Oid,
+};
+
+/// Fixed oid of pg_policy relation.
+pub const RELATION_OID: Oid = 3256;
+
+pub const RELATION_NAME: &str = "pg_policy";
+
+/// The catalog pg_policy stores the row-level security policies of relations.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PgPolicy {
+    /// OID of policy.
+    pub oid: Oid,
+
+    /// Name of the policy, unique within its relation.
+    pub polname: String,
+
+    /// OID of the relation that the policy applies to.
+    pub polrelid: Oid,
+
+    /// Roles that the policy applies to. Empty means all roles, like the PUBLIC role.
+    pub polroles: Vec<String>,
+
+    /// SQL text of the USING expression that rows should satisfy to be visible.
+    pub polqual: String,
+}
+
+impl PgPolicy {
+    /// Return the pg_policy Relation.
+    pub fn get_relation(db_data: &str, db_oid: Oid) -> Result<Relation> {
+        RelationData::open(RELATION_OID, db_data, db_oid, RELATION_NAME)
+    }
+
+    /// Return true if the policy applies to sessions using the given role.
+    pub fn applies_to(&self, role: &str) -> bool {
+        self.polroles.is_empty() || self.polroles.iter().any(|polrole| polrole == role)
+    }
+}
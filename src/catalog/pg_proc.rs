@@ -0,0 +1,113 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    catalog::pg_type::{ANYARRAY_OID, ANYELEMENT_OID, INT4_OID, INT8_OID, RECORD_OID, TEXT_OID},
+    storage::rel::{Relation, RelationData},
+    Oid,
+};
+
+/// Value of prokind for functions.
+pub const PROKIND_FUNCTION: char = 'f';
+
+/// Value of prokind for procedures, which are executed by CALL.
+pub const PROKIND_PROCEDURE: char = 'p';
+
+/// Fixed oid of pg_proc relation.
+pub const RELATION_OID: Oid = 1255;
+
+pub const RELATION_NAME: &str = "pg_proc";
+
+/// The catalog pg_proc stores information about functions and procedures. A name can have multiple
+/// entries with different argument types, which are chosen by overload resolution.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PgProc {
+    /// OID of function.
+    pub oid: Oid,
+
+    /// Name of the function.
+    pub proname: String,
+
+    /// Kind of the entry, [PROKIND_FUNCTION] or [PROKIND_PROCEDURE].
+    pub prokind: char,
+
+    /// Data types of the function arguments.
+    pub proargtypes: Vec<Oid>,
+
+    /// Names of the function arguments, or empty if they are not named.
+    pub proargnames: Vec<String>,
+
+    /// Data type of the return value.
+    pub prorettype: Oid,
+
+    /// Function returns a set of rows instead of a single row.
+    pub proretset: bool,
+
+    /// Data type of the arguments that can be repeated after the declared arguments, or 0 if the
+    /// function is not variadic.
+    pub provariadic: Oid,
+
+    /// Name of the implementation that executes a builtin function, which can be shared by its
+    /// overloads, or the source code of a procedure.
+    pub prosrc: String,
+}
+
+impl PgProc {
+    /// Return the pg_proc Relation.
+    pub fn get_relation(db_data: &str, db_oid: Oid) -> Result<Relation> {
+        RelationData::open(RELATION_OID, db_data, db_oid, RELATION_NAME)
+    }
+
+    /// Return the builtin functions that are stored on pg_proc when a database is created.
+    pub fn builtin_functions() -> Vec<PgProc> {
+        let function = |oid, proname: &str, proargtypes: &[Oid], prorettype, proretset| PgProc {
+            oid,
+            proname: proname.to_string(),
+            prokind: PROKIND_FUNCTION,
+            proargtypes: proargtypes.to_vec(),
+            proargnames: Vec::new(),
+            prorettype,
+            proretset,
+            provariadic: 0,
+            prosrc: proname.to_string(),
+        };
+        vec![
+            function(1066, "generate_series", &[INT4_OID; 3], INT4_OID, true),
+            function(1067, "generate_series", &[INT4_OID; 2], INT4_OID, true),
+            function(1068, "generate_series", &[INT8_OID; 3], INT8_OID, true),
+            function(1069, "generate_series", &[INT8_OID; 2], INT8_OID, true),
+            // Like on FROM clause of Postgres, multiple arrays are expanded side by side.
+            PgProc {
+                provariadic: ANYARRAY_OID,
+                ..function(2331, "unnest", &[ANYARRAY_OID], ANYELEMENT_OID, true)
+            },
+            function(
+                9000,
+                "page_header",
+                &[TEXT_OID, INT4_OID],
+                RECORD_OID,
+                false,
+            ),
+            function(
+                9001,
+                "heap_page_items",
+                &[TEXT_OID, INT4_OID],
+                RECORD_OID,
+                true,
+            ),
+        ]
+    }
+
+    /// Return the types of the arguments that a call with the given number of arguments should
+    /// have, or None if the function can't be called with that many arguments.
+    pub fn arg_types(&self, nargs: usize) -> Option<Vec<Oid>> {
+        let declared = self.proargtypes.len();
+        if nargs == declared || (self.provariadic != 0 && nargs > declared) {
+            let mut types = self.proargtypes.clone();
+            types.resize(nargs, self.provariadic);
+            Some(types)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    storage::rel::{Relation, RelationData},
+    Oid,
+};
+
+/// Fixed oid of pg_type relation.
+pub const RELATION_OID: Oid = 1247;
+
+pub const RELATION_NAME: &str = "pg_type";
+
+/// Fixed oid of bool type.
+pub const BOOL_OID: Oid = 16;
+
+/// Fixed oid of int8 type.
+pub const INT8_OID: Oid = 20;
+
+/// Fixed oid of int4 type.
+pub const INT4_OID: Oid = 23;
+
+/// Fixed oid of text type.
+pub const TEXT_OID: Oid = 25;
+
+/// Fixed oid of unknown type, which is the type of string literals and NULL until they are coerced
+/// to the type expected by their context.
+pub const UNKNOWN_OID: Oid = 705;
+
+/// Fixed oid of bool[] type.
+pub const BOOL_ARRAY_OID: Oid = 1000;
+
+/// Fixed oid of int4[] type.
+pub const INT4_ARRAY_OID: Oid = 1007;
+
+/// Fixed oid of text[] type.
+pub const TEXT_ARRAY_OID: Oid = 1009;
+
+/// Fixed oid of int8[] type.
+pub const INT8_ARRAY_OID: Oid = 1016;
+
+/// Fixed oid of record pseudo-type, returned by functions with multiple output columns.
+pub const RECORD_OID: Oid = 2249;
+
+/// Fixed oid of anyarray pseudo-type, accepted by polymorphic functions for any array type.
+pub const ANYARRAY_OID: Oid = 2277;
+
+/// Fixed oid of void pseudo-type, returned by procedures.
+pub const VOID_OID: Oid = 2278;
+
+/// Fixed oid of anyelement pseudo-type, returned by polymorphic functions.
+pub const ANYELEMENT_OID: Oid = 2283;
+
+/// Value of typlen for variable length types.
+pub const VARLENA_TYPLEN: i16 = -1;
+
+/// The catalog pg_type stores information about data types.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PgType {
+    /// OID of type.
+    pub oid: Oid,
+
+    /// Data type name.
+    pub typname: String,
+
+    /// The number of bytes in the internal representation of the type, or [VARLENA_TYPLEN] for
+    /// variable length types.
+    pub typlen: i16,
+
+    /// Type of the elements of an array type, or 0 if the type is not an array.
+    pub typelem: Oid,
+}
+
+impl PgType {
+    /// Return the pg_type Relation.
+    pub fn get_relation(db_data: &str, db_oid: Oid) -> Result<Relation> {
+        RelationData::open(RELATION_OID, db_data, db_oid, RELATION_NAME)
+    }
+
+    /// Return the builtin types that are stored on pg_type when a database is created.
+    pub fn builtin_types() -> Vec<PgType> {
+        let typ = |oid, typname: &str, typlen, typelem| PgType {
+            oid,
+            typname: typname.to_string(),
+            typlen,
+            typelem,
+        };
+        vec![
+            typ(BOOL_OID, "bool", 1, 0),
+            typ(INT8_OID, "int8", 8, 0),
+            typ(INT4_OID, "int4", 4, 0),
+            typ(TEXT_OID, "text", VARLENA_TYPLEN, 0),
+            typ(UNKNOWN_OID, "unknown", -2, 0),
+            typ(BOOL_ARRAY_OID, "_bool", VARLENA_TYPLEN, BOOL_OID),
+            typ(INT4_ARRAY_OID, "_int4", VARLENA_TYPLEN, INT4_OID),
+            typ(TEXT_ARRAY_OID, "_text", VARLENA_TYPLEN, TEXT_OID),
+            typ(INT8_ARRAY_OID, "_int8", VARLENA_TYPLEN, INT8_OID),
+            typ(RECORD_OID, "record", VARLENA_TYPLEN, 0),
+            typ(ANYARRAY_OID, "anyarray", VARLENA_TYPLEN, 0),
+            typ(VOID_OID, "void", 4, 0),
+            typ(ANYELEMENT_OID, "anyelement", 4, 0),
+        ]
+    }
+
+    /// Return the builtin type of the given oid, if any.
+    pub fn builtin_type(oid: Oid) -> Option<PgType> {
+        Self::builtin_types().into_iter().find(|typ| typ.oid == oid)
+    }
+
+    /// Return the array type whose elements are of the given type, if any.
+    pub fn array_type(elem: Oid) -> Option<Oid> {
+        Self::builtin_types()
+            .into_iter()
+            .find(|typ| typ.typelem == elem)
+            .map(|typ| typ.oid)
+    }
+}
+
+/// Return the SQL name of a builtin type, as used on error messages, e.g integer[] for _int4.
+pub fn format_type(oid: Oid) -> String {
+    match PgType::builtin_type(oid) {
+        Some(typ) if typ.typelem != 0 => format!("{}[]", format_type(typ.typelem)),
+        Some(typ) => match typ.typname.as_str() {
+            "bool" => String::from("boolean"),
+            "int4" => String::from("integer"),
+            "int8" => String::from("bigint"),
+            typname => typname.to_string(),
+        },
+        None => oid.to_string(),
+    }
+}
@@ -0,0 +1,544 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+
+use crate::access::metapage::{MetaPage, METAPAGE_NUMBER};
+use crate::access::tableam::HEAP_TABLE_AM_OID;
+use crate::catalog::pg_attribute::{self, PgAttribute};
+use crate::catalog::pg_audit::{self, PgAudit};
+use crate::catalog::pg_class::{self, PgClass};
+use crate::catalog::pg_database::{self, PgDatabase};
+use crate::catalog::pg_index::{self, PgIndex};
+use crate::catalog::pg_policy::{self, PgPolicy};
+use crate::catalog::pg_proc::{self, PgProc};
+use crate::catalog::pg_type::{self, PgType};
+use crate::catalog::{GLOBAL_DB_NAME, GLOBAL_DB_OID};
+use crate::control::ControlFile;
+use crate::storage::bufpage::{ItemId, PageHeader, ITEM_ID_SIZE, PAGE_HEADER_SIZE};
+use crate::storage::pager::{truncated_len, PageNumber, PAGE_SIZE};
+use crate::storage::rel::{relation_path, RelationData};
+use crate::Oid;
+
+/// A corruption found while checking a data directory.
+#[derive(Debug, PartialEq)]
+pub struct Corruption {
+    /// Name of database that the corrupted relation belongs.
+    pub db_name: String,
+
+    /// Name of the corrupted relation.
+    pub rel_name: String,
+
+    /// Page number where the corruption was found, if it is related with a page.
+    pub page: Option<PageNumber>,
+
+    /// Line pointer number (starting at 1) where the corruption was found, if it is related with
+    /// a tuple.
+    pub item: Option<usize>,
+
+    /// Description of the corruption.
+    pub message: String,
+}
+
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.db_name, self.rel_name)?;
+        if let Some(page) = self.page {
+            write!(f, ": page {}", page)?;
+        }
+        if let Some(item) = self.item {
+            write!(f, ", item {}", item)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// A tuple read from a checked heap page and its location.
+struct CheckedTuple {
+    page: PageNumber,
+    item: usize,
+    data: Vec<u8>,
+}
+
+/// Check the integrity of all databases stored on the given db_data.
+///
+/// Metapages, page headers, line pointer bounds, tuple lengths against the catalog attribute lengths and
+/// the references between system catalogs are validated. Pages are read directly from disk
+/// without the buffer pool, so the data directory should not be in use.
+///
+/// Return the list of corruptions found, which is empty for a consistent data directory.
+pub fn check_data_directory(db_data: &Path) -> Result<Vec<Corruption>> {
+    ControlFile::read(db_data)?.validate()?;
+
+    let mut checker = Checker {
+        db_data: db_data.to_string_lossy().to_string(),
+        corruptions: Vec::new(),
+    };
+
+    let databases = checker.check_catalog::<PgDatabase>(
+        GLOBAL_DB_NAME,
+        GLOBAL_DB_OID,
+        pg_database::RELATION_OID,
+        pg_database::RELATION_NAME,
+    )?;
+    for database in databases {
+        checker.check_database(&database.datname, database.oid)?;
+    }
+
+    Ok(checker.corruptions)
+}
+
+/// Hold the state of a data directory check.
+struct Checker {
+    /// Path where database files are stored.
+    db_data: String,
+
+    /// Corruptions found so far.
+    corruptions: Vec<Corruption>,
+}
+
+impl Checker {
+    /// Check the system catalogs and all relations of a single database.
+    fn check_database(&mut self, db_name: &str, db_oid: Oid) -> Result<()> {
+        let relations = self.check_catalog::<PgClass>(
+            db_name,
+            db_oid,
+            pg_class::RELATION_OID,
+            pg_class::RELATION_NAME,
+        )?;
+        let attributes = self.check_catalog::<PgAttribute>(
+            db_name,
+            db_oid,
+            pg_attribute::RELATION_OID,
+            pg_attribute::RELATION_NAME,
+        )?;
+        let indexes = self.check_catalog::<PgIndex>(
+            db_name,
+            db_oid,
+            pg_index::RELATION_OID,
+            pg_index::RELATION_NAME,
+        )?;
+        let types = self.check_catalog::<PgType>(
+            db_name,
+            db_oid,
+            pg_type::RELATION_OID,
+            pg_type::RELATION_NAME,
+        )?;
+        let functions = self.check_catalog::<PgProc>(
+            db_name,
+            db_oid,
+            pg_proc::RELATION_OID,
+            pg_proc::RELATION_NAME,
+        )?;
+        let policies = self.check_catalog::<PgPolicy>(
+            db_name,
+            db_oid,
+            pg_policy::RELATION_OID,
+            pg_policy::RELATION_NAME,
+        )?;
+        self.check_catalog::<PgAudit>(
+            db_name,
+            db_oid,
+            pg_audit::RELATION_OID,
+            pg_audit::RELATION_NAME,
+        )?;
+
+        let rel_oids: HashSet<Oid> = relations.iter().map(|rel| rel.oid).collect();
+
+        for attr in &attributes {
+            if !rel_oids.contains(&attr.attrelid) {
+                self.report(
+                    db_name,
+                    pg_attribute::RELATION_NAME,
+                    None,
+                    None,
+                    format!(
+                        "attribute {} references relation {} that does not exist in pg_class",
+                        attr.attname, attr.attrelid
+                    ),
+                );
+            }
+        }
+
+        for index in &indexes {
+            for oid in [index.indexrelid, index.indrelid] {
+                if !rel_oids.contains(&oid) {
+                    self.report(
+                        db_name,
+                        pg_index::RELATION_NAME,
+                        None,
+                        None,
+                        format!(
+                            "index entry references relation {} that does not exist in pg_class",
+                            oid
+                        ),
+                    );
+                }
+            }
+        }
+
+        for policy in &policies {
+            if !rel_oids.contains(&policy.polrelid) {
+                self.report(
+                    db_name,
+                    pg_policy::RELATION_NAME,
+                    None,
+                    None,
+                    format!(
+                        "policy {} references relation {} that does not exist in pg_class",
+                        policy.polname, policy.polrelid
+                    ),
+                );
+            }
+        }
+
+        let type_oids: HashSet<Oid> = types.iter().map(|typ| typ.oid).collect();
+
+        for function in &functions {
+            let mut types = function.proargtypes.clone();
+            types.push(function.prorettype);
+            if function.provariadic != 0 {
+                types.push(function.provariadic);
+            }
+            for oid in types {
+                if !type_oids.contains(&oid) {
+                    self.report(
+                        db_name,
+                        pg_proc::RELATION_NAME,
+                        None,
+                        None,
+                        format!(
+                            "function {} references type {} that does not exist in pg_type",
+                            function.proname, oid
+                        ),
+                    );
+                }
+            }
+        }
+
+        for rel in &relations {
+            // Only heap pages have a known layout, other access methods are not checked.
+            if rel.relam != HEAP_TABLE_AM_OID {
+                continue;
+            }
+
+            let mut attrs: Vec<&PgAttribute> = attributes
+                .iter()
+                .filter(|attr| attr.attrelid == rel.oid)
+                .collect();
+            attrs.sort_by_key(|attr| attr.attnum);
+
+            // Insert store only the leading attributes that have values, so a valid tuple length
+            // is the sum of the length of some leading attributes.
+            let mut valid_lengths = vec![0];
+            for attr in attrs {
+                valid_lengths.push(valid_lengths[valid_lengths.len() - 1] + attr.attlen);
+            }
+
+            let tuples = match self.check_heap(db_name, db_oid, rel.oid, &rel.relname)? {
+                Some(tuples) => tuples,
+                None => continue,
+            };
+            for tuple in tuples {
+                if !valid_lengths.contains(&tuple.data.len()) {
+                    self.report(
+                        db_name,
+                        &rel.relname,
+                        Some(tuple.page),
+                        Some(tuple.item),
+                        format!(
+                            "tuple length {} does not match the relation attribute lengths",
+                            tuple.data.len()
+                        ),
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the heap pages of a system catalog and return all tuples that could be decoded as T.
+    fn check_catalog<T: DeserializeOwned>(
+        &mut self,
+        db_name: &str,
+        db_oid: Oid,
+        oid: Oid,
+        rel_name: &str,
+    ) -> Result<Vec<T>> {
+        let mut values = Vec::new();
+
+        let tuples = match self.check_heap(db_name, db_oid, oid, rel_name)? {
+            Some(tuples) => tuples,
+            None => return Ok(values),
+        };
+
+        for tuple in tuples {
+            match bincode::deserialize::<T>(&tuple.data) {
+                Ok(value) => values.push(value),
+                Err(err) => self.report(
+                    db_name,
+                    rel_name,
+                    Some(tuple.page),
+                    Some(tuple.item),
+                    format!("invalid catalog tuple: {}", err),
+                ),
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Check all pages of a heap relation, returning the tuples pointed by valid line pointers.
+    /// Return None if the relation file does not exist or is truncated.
+    fn check_heap(
+        &mut self,
+        db_name: &str,
+        db_oid: Oid,
+        oid: Oid,
+        rel_name: &str,
+    ) -> Result<Option<Vec<CheckedTuple>>> {
+        let path = relation_path(&self.db_data, db_oid, oid);
+        if !path.exists() {
+            self.report(
+                db_name,
+                rel_name,
+                None,
+                None,
+                format!("relation file {} does not exist", oid),
+            );
+            return Ok(None);
+        }
+
+        // Pages can't be read from files that end before the header region or in the middle of a
+        // page, so they are reported before opening the relation.
+        let len = fs::metadata(&path)?.len();
+        if let Some(expected) = truncated_len(len) {
+            self.report(
+                db_name,
+                rel_name,
+                None,
+                None,
+                format!(
+                    "relation file {} is truncated or has a partial page: {} bytes, expected at least {}",
+                    oid, len, expected
+                ),
+            );
+            return Ok(None);
+        }
+
+        let rel = RelationData::open(oid, &self.db_data, db_oid, rel_name)?;
+        let mut rel = rel.borrow_mut();
+
+        let mut tuples = Vec::new();
+
+        let total_pages = rel.pager.size()?;
+        if total_pages < METAPAGE_NUMBER {
+            self.report(
+                db_name,
+                rel_name,
+                None,
+                None,
+                String::from("relation file does not have a metapage"),
+            );
+            return Ok(Some(tuples));
+        }
+
+        let mut page = [0; PAGE_SIZE];
+        rel.pager.read_page(METAPAGE_NUMBER, &mut page)?;
+        match MetaPage::from_page(&page) {
+            Some(meta) if meta.total_pages > total_pages => self.report(
+                db_name,
+                rel_name,
+                Some(METAPAGE_NUMBER),
+                None,
+                format!(
+                    "metapage has {} pages, but relation file has only {}",
+                    meta.total_pages, total_pages
+                ),
+            ),
+            Some(_) => {}
+            None => self.report(
+                db_name,
+                rel_name,
+                Some(METAPAGE_NUMBER),
+                None,
+                String::from("invalid metapage"),
+            ),
+        }
+
+        for page_num in METAPAGE_NUMBER + 1..=total_pages {
+            let mut page = [0; PAGE_SIZE];
+            rel.pager.read_page(page_num, &mut page)?;
+
+            let header = bincode::deserialize::<PageHeader>(&page[..PAGE_HEADER_SIZE])?;
+            let start = header.start_free_space as usize;
+            let end = header.end_free_space as usize;
+
+            if start < PAGE_HEADER_SIZE
+                || end > PAGE_SIZE
+                || start > end
+                || (start - PAGE_HEADER_SIZE) % ITEM_ID_SIZE != 0
+            {
+                self.report(
+                    db_name,
+                    rel_name,
+                    Some(page_num),
+                    None,
+                    format!("invalid page header free space {}..{}", start, end),
+                );
+                continue;
+            }
+
+            let item_id_data = &page[PAGE_HEADER_SIZE..start];
+            for (i, data) in item_id_data.chunks(ITEM_ID_SIZE).enumerate() {
+                let item_id = bincode::deserialize::<ItemId>(data)?;
+                if item_id.is_unused() {
+                    continue;
+                }
+                let offset = item_id.offset as usize;
+                let length = item_id.length as usize;
+
+                if offset < end || offset + length > PAGE_SIZE {
+                    self.report(
+                        db_name,
+                        rel_name,
+                        Some(page_num),
+                        Some(i + 1),
+                        format!(
+                            "line pointer {}..{} is out of the page tuple area {}..{}",
+                            offset,
+                            offset + length,
+                            end,
+                            PAGE_SIZE
+                        ),
+                    );
+                    continue;
+                }
+
+                tuples.push(CheckedTuple {
+                    page: page_num,
+                    item: i + 1,
+                    data: page[offset..offset + length].to_vec(),
+                });
+            }
+        }
+
+        Ok(Some(tuples))
+    }
+
+    /// Add a new corruption found on the given relation location.
+    fn report(
+        &mut self,
+        db_name: &str,
+        rel_name: &str,
+        page: Option<PageNumber>,
+        item: Option<usize>,
+        message: String,
+    ) {
+        self.corruptions.push(Corruption {
+            db_name: db_name.to_string(),
+            rel_name: rel_name.to_string(),
+            page,
+            item,
+            message,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::Catalog;
+    use crate::engine::Engine;
+    use crate::initdb::init_database;
+    use crate::storage::BufferPool;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_consistent_data_directory() -> Result<()> {
+        let db_data = tempdir()?;
+        create_test_table(db_data.path())?;
+
+        assert_eq!(check_data_directory(db_data.path())?, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_invalid_line_pointer() -> Result<()> {
+        let db_data = tempdir()?;
+        let (db_oid, oid) = create_test_table(db_data.path())?;
+
+        // Make the first line pointer of the first data page point outside of the page.
+        {
+            let rel = RelationData::open(oid, &db_data.path().to_string_lossy(), db_oid, "t")?;
+            let mut rel = rel.borrow_mut();
+            let mut page = [0; PAGE_SIZE];
+            rel.pager.read_page(2, &mut page)?;
+            let item_id = bincode::serialize(&ItemId {
+                offset: PAGE_SIZE as u16 - 1,
+                length: 10,
+            })?;
+            page[PAGE_HEADER_SIZE..PAGE_HEADER_SIZE + ITEM_ID_SIZE].copy_from_slice(&item_id);
+            rel.pager.write_page(2, &page)?;
+        }
+
+        let corruptions = check_data_directory(db_data.path())?;
+        assert_eq!(corruptions.len(), 1);
+        assert_eq!(corruptions[0].rel_name, "t");
+        assert_eq!(corruptions[0].page, Some(2));
+        assert_eq!(corruptions[0].item, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_truncated_relation_file() -> Result<()> {
+        let db_data = tempdir()?;
+        let (db_oid, oid) = create_test_table(db_data.path())?;
+
+        let path = relation_path(&db_data.path().to_string_lossy(), db_oid, oid);
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)?
+            .set_len(4000)?;
+
+        let corruptions = check_data_directory(db_data.path())?;
+        assert_eq!(corruptions.len(), 1);
+        assert_eq!(corruptions[0].rel_name, "t");
+        assert_eq!(corruptions[0].page, None);
+        assert_eq!(
+            corruptions[0].message,
+            format!(
+                "relation file {} is truncated or has a partial page: 4000 bytes, expected at least {}",
+                oid,
+                crate::storage::pager::HEADER_REGION_SIZE
+            )
+        );
+        Ok(())
+    }
+
+    /// Create a new table t on database db and return the oids of database and table.
+    fn create_test_table(db_data: &Path) -> Result<(Oid, Oid)> {
+        init_database(&db_data.to_path_buf(), "db")?;
+        {
+            let buffer = BufferPool::new(120);
+            let mut engine = Engine::new(buffer, &db_data.to_string_lossy())?;
+            engine.use_database("db")?;
+            engine.exec("CREATE TABLE t(a int, b int);")?;
+            engine.exec("INSERT INTO t(a, b) VALUES(1, 2);")?;
+            engine.exec("INSERT INTO t(a) VALUES(1);")?;
+        }
+
+        let mut buffer = BufferPool::new(120);
+        let catalog = Catalog::new(&db_data.to_string_lossy());
+        Ok((
+            catalog.get_database_oid(&mut buffer, "db")?,
+            catalog
+                .open_database(&mut buffer, "db")?
+                .get_oid_relation(&mut buffer, "t")?,
+        ))
+    }
+}
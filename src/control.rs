@@ -0,0 +1,167 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::catalog::GLOBAL_DB_NAME;
+use crate::storage::pager::{FORMAT_VERSION, PAGE_SIZE};
+
+/// Version of the system catalogs layout. Any change on the structure of a system catalog should
+/// bump this number, since data directories initialized with another version can't be read.
+pub const CATALOG_VERSION: u32 = 202610167;
+
+/// Name of the control file inside the global directory of db_data.
+pub const CONTROL_FILE_NAME: &str = "pg_control";
+
+/// Errors related with the control file.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("database files are incompatible with server: written with format version {found}, but compiled with format version {expected}; there is no migration between format versions, so the data directory must be recreated")]
+    FormatVersionMismatch { found: u32, expected: u32 },
+
+    #[error("database files are incompatible with server: initialized with catalog version {found}, but compiled with catalog version {expected}")]
+    CatalogVersionMismatch { found: u32, expected: u32 },
+
+    #[error("database files are incompatible with server: initialized with page size {found}, but compiled with page size {expected}")]
+    PageSizeMismatch { found: u32, expected: u32 },
+}
+
+/// Cluster wide information written when a data directory is initialized.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ControlFile {
+    /// Format version of the data directory. It must be the first field, so it can be read even
+    /// if the rest of the control file layout changes between versions.
+    pub format_version: u32,
+
+    /// Unique identifier of the data directory.
+    pub system_identifier: u64,
+
+    /// Catalog version that the data directory was initialized with.
+    pub catalog_version: u32,
+
+    /// Page size that the data directory was initialized with.
+    pub page_size: u32,
+}
+
+impl Default for ControlFile {
+    /// Create a new control file using the current catalog version and page size.
+    fn default() -> Self {
+        // Like Postgres, build the system identifier from the initialization time and process id,
+        // which is unique enough to distinguish data directories.
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        Self {
+            format_version: FORMAT_VERSION,
+            system_identifier: (now << 32) | process::id() as u64,
+            catalog_version: CATALOG_VERSION,
+            page_size: PAGE_SIZE as u32,
+        }
+    }
+}
+
+impl ControlFile {
+    /// Read the control file from the given db_data path.
+    ///
+    /// Return error if the control file was written with another format version, since its
+    /// layout may be different.
+    pub fn read(db_data: &Path) -> Result<Self> {
+        let data = fs::read(Self::path(db_data))?;
+
+        let format_version = bincode::deserialize::<u32>(&data)?;
+        if format_version != FORMAT_VERSION {
+            bail!(Error::FormatVersionMismatch {
+                found: format_version,
+                expected: FORMAT_VERSION,
+            });
+        }
+
+        Ok(bincode::deserialize(&data)?)
+    }
+
+    /// Write the control file on the given db_data path, overriding the current one if exists.
+    pub fn write(&self, db_data: &Path) -> Result<()> {
+        fs::write(Self::path(db_data), bincode::serialize(self)?)?;
+        Ok(())
+    }
+
+    /// Check that the data directory can be read by this build.
+    pub fn validate(&self) -> Result<()> {
+        if self.catalog_version != CATALOG_VERSION {
+            bail!(Error::CatalogVersionMismatch {
+                found: self.catalog_version,
+                expected: CATALOG_VERSION,
+            });
+        }
+        if self.page_size != PAGE_SIZE as u32 {
+            bail!(Error::PageSizeMismatch {
+                found: self.page_size,
+                expected: PAGE_SIZE as u32,
+            });
+        }
+        Ok(())
+    }
+
+    /// Return the path of control file inside the given db_data.
+    pub fn path(db_data: &Path) -> PathBuf {
+        db_data.join(GLOBAL_DB_NAME).join(CONTROL_FILE_NAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_control_file_write_read() -> Result<()> {
+        let db_data = tempdir()?;
+        fs::create_dir(db_data.path().join(GLOBAL_DB_NAME))?;
+
+        let control = ControlFile::default();
+        control.write(db_data.path())?;
+
+        assert_eq!(control, ControlFile::read(db_data.path())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_control_file_format_version_mismatch() -> Result<()> {
+        let db_data = tempdir()?;
+        fs::create_dir(db_data.path().join(GLOBAL_DB_NAME))?;
+
+        let mut control = ControlFile::default();
+        control.format_version = FORMAT_VERSION + 1;
+        control.write(db_data.path())?;
+
+        let err = ControlFile::read(db_data.path()).unwrap_err();
+        assert_eq!(
+            Error::FormatVersionMismatch {
+                found: FORMAT_VERSION + 1,
+                expected: FORMAT_VERSION
+            },
+            err.downcast::<Error>().unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_control_file_catalog_version_mismatch() {
+        let mut control = ControlFile::default();
+        control.catalog_version = CATALOG_VERSION - 1;
+
+        let err = control.validate().unwrap_err();
+        assert_eq!(
+            Error::CatalogVersionMismatch {
+                found: CATALOG_VERSION - 1,
+                expected: CATALOG_VERSION
+            },
+            err.downcast::<Error>().unwrap()
+        );
+    }
+}
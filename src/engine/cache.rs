@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use crate::lru::LRU;
+use crate::Oid;
+
+use super::result::QueryResult;
+
+/// Identify the results of a query. The same query text returns different rows on other
+/// databases or for other roles, since row-level security policies depend on the session role.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub db_name: String,
+    pub role: Option<String>,
+    pub query: String,
+}
+
+/// Counters of the lookups made on a result cache since it was created.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ResultCacheStats {
+    /// Number of queries answered from the cache.
+    pub hits: u64,
+
+    /// Number of cacheable queries executed because their results were not cached or stale.
+    pub misses: u64,
+
+    /// Number of results currently cached.
+    pub entries: usize,
+}
+
+/// Results of a query with the versions of the relations that it read.
+struct CacheEntry {
+    results: Vec<QueryResult>,
+    versions: Vec<(Oid, u64)>,
+}
+
+/// Cache the results of queries, so read-mostly workloads that repeat the same queries don't
+/// scan the relations every time.
+///
+/// Each relation has a version that is incremented by every statement that changes its rows or
+/// its row-level security policies. Cached results are valid while all relations read by the
+/// query keep the versions that they had when the query was executed.
+pub struct ResultCache {
+    /// Maximum number of cached results.
+    size: usize,
+
+    /// Cached results indexed by the query that produced them.
+    entries: HashMap<CacheKey, CacheEntry>,
+
+    /// Replacer used to find the results to remove when the cache is full.
+    lru: LRU<CacheKey>,
+
+    /// Versions indexed by database name and relation oid, since relation oids are only unique
+    /// within a database. Relations never changed have version zero.
+    versions: HashMap<(String, Oid), u64>,
+
+    hits: u64,
+    misses: u64,
+}
+
+impl ResultCache {
+    /// Create a new empty cache that holds the results of at most size queries.
+    pub fn new(size: usize) -> Self {
+        Self {
+            size,
+            entries: HashMap::with_capacity(size),
+            lru: LRU::new(size),
+            versions: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Return the maximum number of cached results.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Return the cached results of the given query, marking them as recently used. Results of a
+    /// relation that changed after they were cached are removed.
+    pub fn get(&mut self, key: &CacheKey) -> Option<Vec<QueryResult>> {
+        let valid = match self.entries.get(key) {
+            Some(entry) => entry
+                .versions
+                .iter()
+                .all(|(relid, version)| self.version(&key.db_name, *relid) == *version),
+            None => false,
+        };
+        if !valid {
+            self.remove(key);
+            self.misses += 1;
+            return None;
+        }
+
+        self.hits += 1;
+        self.touch(key);
+        self.entries.get(key).map(|entry| entry.results.clone())
+    }
+
+    /// Add the results of a query that read the given relations, removing the least recently
+    /// used results if the cache is full.
+    pub fn insert(&mut self, key: CacheKey, relids: &[Oid], results: Vec<QueryResult>) {
+        if self.size == 0 {
+            return;
+        }
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.size {
+            if let Some(victim) = self.lru.victim() {
+                self.entries.remove(&victim);
+            }
+        }
+
+        let versions = relids
+            .iter()
+            .map(|relid| (*relid, self.version(&key.db_name, *relid)))
+            .collect();
+        self.touch(&key);
+        self.entries.insert(key, CacheEntry { results, versions });
+    }
+
+    /// Invalidate the cached results that read the given relation, e.g because rows were
+    /// inserted on it.
+    pub fn invalidate(&mut self, db_name: &str, relid: Oid) {
+        *self
+            .versions
+            .entry((db_name.to_string(), relid))
+            .or_default() += 1;
+    }
+
+    /// Remove all cached results of the given database, e.g because it was dropped.
+    pub fn remove_database(&mut self, db_name: &str) {
+        let keys: Vec<CacheKey> = self
+            .entries
+            .keys()
+            .filter(|key| key.db_name == db_name)
+            .cloned()
+            .collect();
+        for key in keys {
+            self.remove(&key);
+        }
+        self.versions.retain(|(name, _), _| name != db_name);
+    }
+
+    /// Return the counters of the lookups made on the cache.
+    pub fn stats(&self) -> ResultCacheStats {
+        ResultCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            entries: self.entries.len(),
+        }
+    }
+
+    fn version(&self, db_name: &str, relid: Oid) -> u64 {
+        self.versions
+            .get(&(db_name.to_string(), relid))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        self.entries.remove(key);
+        // Pin removes the key from the replacer, so it is never choosen for victim.
+        self.lru.pin(key);
+    }
+
+    /// Move the given key to the most recently used position of replacer.
+    fn touch(&mut self, key: &CacheKey) {
+        self.lru.pin(key);
+        self.lru.unpin(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(db_name: &str, query: &str) -> CacheKey {
+        CacheKey {
+            db_name: db_name.to_string(),
+            role: None,
+            query: query.to_string(),
+        }
+    }
+
+    fn result(value: &str) -> Vec<QueryResult> {
+        vec![QueryResult {
+            columns: Vec::new(),
+            rows: vec![vec![value.to_string()]],
+        }]
+    }
+
+    #[test]
+    fn test_result_cache() {
+        let mut cache = ResultCache::new(2);
+        assert_eq!(cache.get(&key("db", "q1")), None);
+
+        cache.insert(key("db", "q1"), &[1], result("a"));
+        cache.insert(key("db", "q2"), &[1, 2], result("b"));
+        assert_eq!(cache.get(&key("db", "q1")), Some(result("a")));
+        assert_eq!(cache.get(&key("other", "q1")), None);
+
+        // Changes on a relation only invalidate the results that read it.
+        cache.invalidate("db", 2);
+        assert_eq!(cache.get(&key("db", "q2")), None);
+        assert_eq!(cache.get(&key("db", "q1")), Some(result("a")));
+
+        // Results cached after a change are valid with the new version.
+        cache.insert(key("db", "q2"), &[2], result("c"));
+        assert_eq!(cache.get(&key("db", "q2")), Some(result("c")));
+
+        // q1 is the least recently used results.
+        cache.insert(key("db", "q3"), &[3], result("d"));
+        assert_eq!(cache.get(&key("db", "q1")), None);
+        assert_eq!(cache.get(&key("db", "q2")), Some(result("c")));
+
+        cache.remove_database("db");
+        assert_eq!(
+            cache.stats(),
+            ResultCacheStats {
+                hits: 4,
+                misses: 4,
+                entries: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::fmt;
+
+/// Information reported with a failed statement to help finding and fixing the error, like the
+/// position and hint fields of Postgres error reports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Primary error message.
+    pub message: String,
+
+    /// Position of the offending token on the SQL text, as a character index starting at 1.
+    pub position: Option<usize>,
+
+    /// Suggestion about how to fix the error.
+    pub hint: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Diagnostic {
+    /// Create a new diagnostic pointing to the byte offset of the given SQL text, if any.
+    pub fn new(message: String, sql: &str, offset: Option<usize>, hint: Option<String>) -> Self {
+        Self {
+            message,
+            position: offset.map(|offset| sql[..offset].chars().count() + 1),
+            hint,
+        }
+    }
+
+    /// Format the diagnostic like psql, showing the line of the SQL text where the error
+    /// happened with a marker under the offending token.
+    pub fn render(&self, sql: &str) -> String {
+        let mut output = format!("ERROR:  {}", self.message);
+
+        if let Some(position) = self.position {
+            let before: String = sql.chars().take(position - 1).collect();
+            let line_num = before.matches('\n').count() + 1;
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            let column = before[line_start..].chars().count();
+            let line = sql[line_start..].lines().next().unwrap_or_default();
+
+            let prefix = format!("LINE {}: ", line_num);
+            output.push_str(&format!(
+                "\n{}{}\n{}^",
+                prefix,
+                line,
+                " ".repeat(prefix.len() + column)
+            ));
+        }
+
+        if let Some(hint) = &self.hint {
+            output.push_str(&format!("\nHINT:  {}", hint));
+        }
+        output
+    }
+}
+
+/// Return the byte offset of the first occurrence of token on the SQL text, ignoring case. Word
+/// tokens only match whole words, so a column a is not found inside another identifier.
+pub fn find_token(sql: &str, token: &str) -> Option<usize> {
+    if token.is_empty() {
+        return None;
+    }
+
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let haystack = sql.to_ascii_lowercase();
+    let needle = token.to_ascii_lowercase();
+
+    haystack.match_indices(&needle).map(|(i, _)| i).find(|&i| {
+        let end = i + needle.len();
+        let starts_word = needle.starts_with(is_word);
+        let ends_word = needle.ends_with(is_word);
+
+        !(starts_word && sql[..i].ends_with(is_word))
+            && !(ends_word && sql[end..].starts_with(is_word))
+    })
+}
+
+/// Return the candidate closest to name, if it is close enough to be a probable typo.
+pub fn closest_match<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .map(|candidate| (levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Return the minimum number of single character insertions, deletions or substitutions to change
+/// a into b.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = distances[0];
+        distances[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(distances[j + 1] + 1);
+        }
+    }
+    distances[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let sql = "SELECT a,\n  bb FROM t;";
+        let diagnostic = Diagnostic::new(
+            String::from("column \"bb\" does not exist"),
+            sql,
+            find_token(sql, "bb"),
+            Some(String::from(
+                "Perhaps you meant to reference the column \"b\".",
+            )),
+        );
+        assert_eq!(diagnostic.position, Some(13));
+        assert_eq!(
+            diagnostic.render(sql),
+            "ERROR:  column \"bb\" does not exist\n\
+             LINE 2:   bb FROM t;\n\
+             \x20         ^\n\
+             HINT:  Perhaps you meant to reference the column \"b\"."
+        );
+    }
+
+    #[test]
+    fn test_find_token() {
+        assert_eq!(find_token("SELECT ab, a FROM t", "a"), Some(11));
+        assert_eq!(find_token("SELECT ab, A FROM t", "a"), Some(11));
+        assert_eq!(find_token("SELECT (a FROM t", "("), Some(7));
+        assert_eq!(find_token("SELECT ab FROM t", "a"), None);
+    }
+
+    #[test]
+    fn test_closest_match() {
+        let columns = ["name", "age", "id"];
+        assert_eq!(closest_match("nme", columns), Some("name"));
+        assert_eq!(closest_match("agee", columns), Some("age"));
+        assert_eq!(closest_match("email", columns), None);
+    }
+}
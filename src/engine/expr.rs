@@ -0,0 +1,245 @@
+use anyhow::{bail, Result};
+use sqlparser::ast::{self, BinaryOperator, UnaryOperator};
+
+use super::diagnostic::closest_match;
+use super::Error;
+
+/// Value produced by evaluating an expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Datum {
+    Int(i32),
+    Bool(bool),
+    Null,
+}
+
+impl Datum {
+    /// Return the name of the value type, used on error messages.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Datum::Int(_) => "integer",
+            Datum::Bool(_) => "boolean",
+            Datum::Null => "unknown",
+        }
+    }
+}
+
+impl From<i32> for Datum {
+    fn from(value: i32) -> Self {
+        Datum::Int(value)
+    }
+}
+
+impl From<bool> for Datum {
+    fn from(value: bool) -> Self {
+        Datum::Bool(value)
+    }
+}
+
+/// A row that expressions are evaluated on, with the values formatted as text like the rows of a
+/// [super::QueryResult].
+#[derive(Default)]
+pub struct Row<'a> {
+    pub columns: &'a [String],
+    pub values: &'a [String],
+}
+
+/// Evaluate a boolean expression used by the given clause on a row, e.g a policy USING
+/// expression. NULL is handled as false, like Postgres does for WHERE.
+///
+/// Return error if the expression is not boolean.
+pub fn eval_predicate(expr: &ast::Expr, row: &Row, clause: &str) -> Result<bool> {
+    match eval(expr, row)? {
+        Datum::Bool(value) => Ok(value),
+        Datum::Null => Ok(false),
+        datum => bail!(Error::NotBoolean(
+            clause.to_string(),
+            datum.type_name().to_string()
+        )),
+    }
+}
+
+/// Evaluate an expression on a row. Only integer and boolean column references, literals and
+/// operators are supported.
+pub fn eval(expr: &ast::Expr, row: &Row) -> Result<Datum> {
+    match expr {
+        ast::Expr::Identifier(ident) => column_value(row, &ident.value),
+        ast::Expr::CompoundIdentifier(idents) if !idents.is_empty() => {
+            column_value(row, &idents[idents.len() - 1].value)
+        }
+        ast::Expr::Value(value) => match value {
+            ast::Value::Number(number, _) => match number.parse::<i32>() {
+                Ok(number) => Ok(Datum::Int(number)),
+                Err(_) => bail!(Error::InvalidInteger(value.to_string())),
+            },
+            ast::Value::Boolean(value) => Ok(Datum::Bool(*value)),
+            ast::Value::Null => Ok(Datum::Null),
+            _ => bail!(Error::UnsupportedExpression(expr.to_string())),
+        },
+        ast::Expr::Nested(expr) => eval(expr, row),
+        ast::Expr::IsNull(expr) => Ok(Datum::Bool(eval(expr, row)? == Datum::Null)),
+        ast::Expr::IsNotNull(expr) => Ok(Datum::Bool(eval(expr, row)? != Datum::Null)),
+        ast::Expr::UnaryOp { op, expr: operand } => match (op, eval(operand, row)?) {
+            (_, Datum::Null) => Ok(Datum::Null),
+            (UnaryOperator::Not, Datum::Bool(value)) => Ok(Datum::Bool(!value)),
+            (UnaryOperator::Minus, Datum::Int(value)) => int_result(value.checked_neg()),
+            (UnaryOperator::Plus, Datum::Int(value)) => Ok(Datum::Int(value)),
+            (UnaryOperator::Not, datum) => bail!(Error::NotBoolean(
+                String::from("NOT"),
+                datum.type_name().to_string()
+            )),
+            (op, datum) => bail!(Error::OperatorNotFound(format!(
+                "{} {}",
+                op,
+                datum.type_name()
+            ))),
+        },
+        ast::Expr::BinaryOp { left, op, right } => {
+            binary_op(eval(left, row)?, op, eval(right, row)?)
+        }
+        _ => bail!(Error::UnsupportedExpression(expr.to_string())),
+    }
+}
+
+/// Return the value of the given column of row. NULL values are formatted as `NULL`.
+fn column_value(row: &Row, name: &str) -> Result<Datum> {
+    let i = match row.columns.iter().position(|column| column == name) {
+        Some(i) => i,
+        None => {
+            let similar = closest_match(name, row.columns.iter().map(String::as_str));
+            bail!(Error::ColumnNotFound(
+                name.to_string(),
+                similar.map(str::to_string)
+            ));
+        }
+    };
+
+    match row.values[i].as_str() {
+        "NULL" => Ok(Datum::Null),
+        "true" => Ok(Datum::Bool(true)),
+        "false" => Ok(Datum::Bool(false)),
+        value => match value.parse::<i32>() {
+            Ok(value) => Ok(Datum::Int(value)),
+            Err(_) => bail!(Error::InvalidInteger(value.to_string())),
+        },
+    }
+}
+
+/// Apply a binary operator. AND and OR use three-valued logic, and any other operator returns
+/// NULL if any of its operands is NULL.
+fn binary_op(left: Datum, op: &BinaryOperator, right: Datum) -> Result<Datum> {
+    let not_boolean =
+        |datum: Datum| Error::NotBoolean(op.to_string(), datum.type_name().to_string());
+
+    match op {
+        BinaryOperator::And | BinaryOperator::Or => {
+            let is_and = *op == BinaryOperator::And;
+            let mut has_null = false;
+            for datum in [left, right] {
+                match datum {
+                    Datum::Bool(value) if value != is_and => return Ok(Datum::Bool(value)),
+                    Datum::Bool(_) => {}
+                    Datum::Null => has_null = true,
+                    datum => bail!(not_boolean(datum)),
+                }
+            }
+            Ok(if has_null {
+                Datum::Null
+            } else {
+                Datum::Bool(is_and)
+            })
+        }
+        _ => match (left, right) {
+            (Datum::Null, _) | (_, Datum::Null) => Ok(Datum::Null),
+            (Datum::Int(left), Datum::Int(right)) => match op {
+                BinaryOperator::Plus => int_result(left.checked_add(right)),
+                BinaryOperator::Minus => int_result(left.checked_sub(right)),
+                BinaryOperator::Multiply => int_result(left.checked_mul(right)),
+                BinaryOperator::Divide | BinaryOperator::Modulo if right == 0 => {
+                    bail!(Error::DivisionByZero)
+                }
+                BinaryOperator::Divide => int_result(left.checked_div(right)),
+                BinaryOperator::Modulo => int_result(left.checked_rem(right)),
+                op => compare(left, op, right),
+            },
+            (Datum::Bool(left), Datum::Bool(right)) => compare(left, op, right),
+            (left, right) => bail!(Error::OperatorNotFound(format!(
+                "{} {} {}",
+                left.type_name(),
+                op,
+                right.type_name()
+            ))),
+        },
+    }
+}
+
+/// Apply a comparison operator.
+fn compare<T: Ord + Into<Datum>>(left: T, op: &BinaryOperator, right: T) -> Result<Datum> {
+    let result = match op {
+        BinaryOperator::Eq => left == right,
+        BinaryOperator::NotEq => left != right,
+        BinaryOperator::Lt => left < right,
+        BinaryOperator::LtEq => left <= right,
+        BinaryOperator::Gt => left > right,
+        BinaryOperator::GtEq => left >= right,
+        op => bail!(Error::OperatorNotFound(format!(
+            "{} {} {}",
+            left.into().type_name(),
+            op,
+            right.into().type_name()
+        ))),
+    };
+    Ok(Datum::Bool(result))
+}
+
+/// Return the result of an integer operation, which is None if it overflowed.
+fn int_result(value: Option<i32>) -> Result<Datum> {
+    match value {
+        Some(value) => Ok(Datum::Int(value)),
+        None => bail!(Error::IntegerOutOfRange),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::parser::parse_expr;
+
+    fn eval_sql(sql: &str) -> Result<Datum> {
+        let columns = vec![String::from("a"), String::from("b")];
+        let values = vec![String::from("2"), String::from("NULL")];
+        let row = Row {
+            columns: &columns,
+            values: &values,
+        };
+        eval(&parse_expr(sql)?, &row)
+    }
+
+    #[test]
+    fn test_eval() -> Result<()> {
+        assert_eq!(eval_sql("a * 3 + 1")?, Datum::Int(7));
+        assert_eq!(eval_sql("-a % 3")?, Datum::Int(-2));
+        assert_eq!(eval_sql("a = 2 AND NOT a > 2")?, Datum::Bool(true));
+        assert_eq!(eval_sql("b + 1")?, Datum::Null);
+        assert_eq!(eval_sql("b = 1 OR a = 2")?, Datum::Bool(true));
+        assert_eq!(eval_sql("b = 1 AND a = 2")?, Datum::Null);
+        assert_eq!(eval_sql("b = 1 AND a = 1")?, Datum::Bool(false));
+        assert_eq!(eval_sql("b IS NULL")?, Datum::Bool(true));
+
+        let err = eval_sql("a / 0").unwrap_err();
+        assert!(matches!(err.downcast::<Error>()?, Error::DivisionByZero));
+        let err = eval_sql("2147483647 + a").unwrap_err();
+        assert!(matches!(err.downcast::<Error>()?, Error::IntegerOutOfRange));
+        let err = eval_sql("a AND true").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "argument of AND must be type boolean, not type integer"
+        );
+        let err = eval_sql("aa = 1").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::ColumnNotFound(_, Some(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,316 @@
+use anyhow::{bail, Result};
+use sqlparser::ast;
+
+use crate::catalog::pg_proc::PgProc;
+use crate::catalog::pg_type::{
+    format_type, PgType, ANYARRAY_OID, BOOL_OID, INT4_OID, INT8_OID, TEXT_OID, UNKNOWN_OID,
+};
+use crate::Oid;
+
+use super::srf::Arg;
+use super::Error;
+
+/// Return the constant values of the given function arguments with their types. Arguments can be
+/// literals, array literals or literals with an explicit cast.
+///
+/// Return error if any argument is not a constant, since functions are only called on FROM clause.
+pub fn function_args(func_name: &str, args: &[ast::FunctionArg]) -> Result<Vec<(Arg, Oid)>> {
+    let mut values = Vec::new();
+    for arg in args {
+        match arg {
+            ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(expr)) => {
+                values.push(function_arg(func_name, expr)?)
+            }
+            _ => bail!(Error::InvalidArguments(func_name.to_string())),
+        }
+    }
+    Ok(values)
+}
+
+fn function_arg(func_name: &str, expr: &ast::Expr) -> Result<(Arg, Oid)> {
+    match expr {
+        ast::Expr::Array(ast::Array { elem, .. }) => {
+            let mut array = Vec::new();
+            for expr in elem {
+                array.push(constant_value(func_name, expr)?);
+            }
+            let arg = Arg::Array(array);
+            let typ = arg_type(&arg)?;
+            Ok((coerce_arg(arg, typ, typ)?, typ))
+        }
+        ast::Expr::Cast { expr, data_type } => {
+            let (arg, from) = function_arg(func_name, expr)?;
+            let to = type_oid(data_type)?;
+            if !can_cast(from, to) {
+                bail!(Error::CannotCast(format_type(from), format_type(to)));
+            }
+            Ok((coerce_arg(arg, from, to)?, to))
+        }
+        expr => {
+            let arg = Arg::Value(constant_value(func_name, expr)?);
+            let typ = arg_type(&arg)?;
+            Ok((arg, typ))
+        }
+    }
+}
+
+/// Return the literal value of a constant function argument, folding negative numbers.
+fn constant_value(func_name: &str, expr: &ast::Expr) -> Result<ast::Value> {
+    match expr {
+        ast::Expr::Value(value) => Ok(value.clone()),
+        ast::Expr::UnaryOp {
+            op: ast::UnaryOperator::Minus,
+            expr,
+        } => match expr.as_ref() {
+            ast::Expr::Value(ast::Value::Number(value, long)) => {
+                Ok(ast::Value::Number(format!("-{}", value), *long))
+            }
+            _ => bail!(Error::InvalidArguments(func_name.to_string())),
+        },
+        _ => bail!(Error::InvalidArguments(func_name.to_string())),
+    }
+}
+
+/// Return the type of a constant argument. Integer literals are int4, or int8 if they don't fit,
+/// and string literals and NULL are unknown until they are coerced to the type of the parameter.
+/// Like Postgres, arrays of unknown elements are text arrays.
+///
+/// Return error if the elements of an array have types that can't be matched.
+pub fn arg_type(arg: &Arg) -> Result<Oid> {
+    match arg {
+        Arg::Value(value) => value_type(value),
+        Arg::Array(values) => {
+            let mut elem = UNKNOWN_OID;
+            for value in values {
+                let typ = value_type(value)?;
+                if typ == UNKNOWN_OID || can_coerce(typ, elem) {
+                    continue;
+                }
+                if !can_coerce(elem, typ) {
+                    bail!(Error::ArrayTypeMismatch(
+                        format_type(elem),
+                        format_type(typ)
+                    ));
+                }
+                elem = typ;
+            }
+            if values.is_empty() {
+                bail!(Error::EmptyArray);
+            }
+            if elem == UNKNOWN_OID {
+                elem = TEXT_OID;
+            }
+            match PgType::array_type(elem) {
+                Some(typ) => Ok(typ),
+                None => bail!(Error::TypeNotFound(format!("{}[]", format_type(elem)))),
+            }
+        }
+    }
+}
+
+fn value_type(value: &ast::Value) -> Result<Oid> {
+    match value {
+        ast::Value::Number(number, _) if number.parse::<i32>().is_ok() => Ok(INT4_OID),
+        ast::Value::Number(number, _) if number.parse::<i64>().is_ok() => Ok(INT8_OID),
+        ast::Value::Boolean(_) => Ok(BOOL_OID),
+        ast::Value::SingleQuotedString(_) | ast::Value::Null => Ok(UNKNOWN_OID),
+        value => bail!(Error::UnsupportedExpression(value.to_string())),
+    }
+}
+
+/// Return the oid of a type used on an explicit cast.
+pub fn type_oid(data_type: &ast::DataType) -> Result<Oid> {
+    match data_type.to_string().to_lowercase().as_str() {
+        "int" | "integer" | "int4" => Ok(INT4_OID),
+        "bigint" | "int8" => Ok(INT8_OID),
+        "boolean" | "bool" => Ok(BOOL_OID),
+        "text" => Ok(TEXT_OID),
+        typname => bail!(Error::TypeNotFound(typname.to_string())),
+    }
+}
+
+/// Return the element type of an array type, or 0 if the type is not an array.
+fn elem_type(oid: Oid) -> Oid {
+    PgType::builtin_type(oid).map_or(0, |typ| typ.typelem)
+}
+
+/// Return true if a value of type from can be implicitly coerced to type to, e.g when passing it
+/// to a function. Unknown literals can be coerced to any scalar type, int4 can be widened to int8
+/// and arrays can be coerced if their elements can.
+pub fn can_coerce(from: Oid, to: Oid) -> bool {
+    match (from, to) {
+        _ if from == to => true,
+        (_, ANYARRAY_OID) => elem_type(from) != 0,
+        (UNKNOWN_OID, BOOL_OID | INT4_OID | INT8_OID | TEXT_OID) => true,
+        (INT4_OID, INT8_OID) => true,
+        _ => {
+            let (from, to) = (elem_type(from), elem_type(to));
+            from != 0 && to != 0 && can_coerce(from, to)
+        }
+    }
+}
+
+/// Return true if a value of type from can be explicitly casted to type to. Besides implicit
+/// coercions, int8 can be narrowed to int4 and values can be converted from and to text.
+fn can_cast(from: Oid, to: Oid) -> bool {
+    let scalar = |oid| matches!(oid, BOOL_OID | INT4_OID | INT8_OID);
+    can_coerce(from, to)
+        || (from, to) == (INT8_OID, INT4_OID)
+        || (scalar(from) && to == TEXT_OID)
+        || (from == TEXT_OID && scalar(to))
+}
+
+/// Convert an argument of type from to type to, which should be allowed by [can_coerce] or
+/// [can_cast]. Elements of arrays passed as anyarray are converted to the array element type.
+pub fn coerce_arg(arg: Arg, from: Oid, to: Oid) -> Result<Arg> {
+    match arg {
+        Arg::Value(value) => Ok(Arg::Value(coerce_value(value, to)?)),
+        Arg::Array(values) => {
+            let elem = match to {
+                ANYARRAY_OID => elem_type(from),
+                to => elem_type(to),
+            };
+            let values = values
+                .into_iter()
+                .map(|value| coerce_value(value, elem))
+                .collect::<Result<_>>()?;
+            Ok(Arg::Array(values))
+        }
+    }
+}
+
+fn coerce_value(value: ast::Value, to: Oid) -> Result<ast::Value> {
+    let number = |value: String| ast::Value::Number(value, false);
+    match (value, to) {
+        (ast::Value::Number(value, _), INT4_OID) => match value.parse::<i32>() {
+            Ok(_) => Ok(number(value)),
+            Err(_) => bail!(Error::IntegerOutOfRange),
+        },
+        (ast::Value::SingleQuotedString(value), INT4_OID) => match value.trim().parse::<i32>() {
+            Ok(parsed) => Ok(number(parsed.to_string())),
+            Err(_) => bail!(Error::InvalidInteger(value)),
+        },
+        (ast::Value::SingleQuotedString(value), INT8_OID) => match value.trim().parse::<i64>() {
+            Ok(parsed) => Ok(number(parsed.to_string())),
+            Err(_) => bail!(Error::InvalidInteger(value)),
+        },
+        (ast::Value::SingleQuotedString(value), BOOL_OID) => {
+            match value.trim().to_lowercase().as_str() {
+                "t" | "true" | "yes" | "on" | "1" => Ok(ast::Value::Boolean(true)),
+                "f" | "false" | "no" | "off" | "0" => Ok(ast::Value::Boolean(false)),
+                _ => bail!(Error::InvalidBoolean(value)),
+            }
+        }
+        (ast::Value::Number(value, _), TEXT_OID) => Ok(ast::Value::SingleQuotedString(value)),
+        (ast::Value::Boolean(value), TEXT_OID) => {
+            Ok(ast::Value::SingleQuotedString(value.to_string()))
+        }
+        (value, _) => Ok(value),
+    }
+}
+
+/// Choose which overload of a function is called with arguments of the given types, returning it
+/// with the types that the arguments should be coerced to. Like Postgres, candidates that accept
+/// the arguments through implicit coercions are considered, and the candidate with the most
+/// arguments matching exactly is chosen.
+///
+/// Return error if no candidate accepts the arguments, or if multiple candidates are equally good.
+pub fn resolve_function<'a>(
+    func_name: &str,
+    candidates: &'a [PgProc],
+    arg_types: &[Oid],
+) -> Result<(&'a PgProc, Vec<Oid>)> {
+    let mut best = Vec::new();
+    let mut best_exact = 0;
+
+    for candidate in candidates {
+        let types = match candidate.arg_types(arg_types.len()) {
+            Some(types) => types,
+            None => continue,
+        };
+        if !arg_types
+            .iter()
+            .zip(&types)
+            .all(|(from, to)| can_coerce(*from, *to))
+        {
+            continue;
+        }
+
+        let exact = arg_types
+            .iter()
+            .zip(&types)
+            .filter(|(from, to)| from == to)
+            .count();
+        if best.is_empty() || exact > best_exact {
+            best = vec![(candidate, types)];
+            best_exact = exact;
+        } else if exact == best_exact {
+            best.push((candidate, types));
+        }
+    }
+
+    match best.len() {
+        0 => bail!(Error::FunctionNotFound(format_signature(
+            func_name, arg_types
+        ))),
+        1 => Ok(best.remove(0)),
+        _ => bail!(Error::AmbiguousFunction(format_signature(
+            func_name, arg_types
+        ))),
+    }
+}
+
+/// Return the signature of a function call used on error messages, e.g f(integer, unknown).
+pub fn format_signature(func_name: &str, arg_types: &[Oid]) -> String {
+    let types: Vec<String> = arg_types.iter().map(|oid| format_type(*oid)).collect();
+    format!("{}({})", func_name, types.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::pg_type::INT4_ARRAY_OID;
+
+    #[test]
+    fn test_resolve_function() -> Result<()> {
+        let functions = PgProc::builtin_functions();
+        let candidates: Vec<PgProc> = functions
+            .into_iter()
+            .filter(|function| function.proname == "generate_series")
+            .collect();
+        let resolve = |arg_types: &[Oid]| -> Result<Oid> {
+            Ok(resolve_function("generate_series", &candidates, arg_types)?
+                .0
+                .oid)
+        };
+
+        assert_eq!(resolve(&[INT4_OID, INT4_OID])?, 1067);
+        assert_eq!(resolve(&[INT4_OID, INT8_OID])?, 1069);
+        assert_eq!(resolve(&[INT4_OID, INT4_OID, INT8_OID])?, 1068);
+        assert_eq!(resolve(&[UNKNOWN_OID, INT4_OID])?, 1067);
+        assert_eq!(
+            resolve(&[UNKNOWN_OID, UNKNOWN_OID])
+                .unwrap_err()
+                .to_string(),
+            "function generate_series(unknown, unknown) is not unique"
+        );
+        assert_eq!(
+            resolve(&[INT4_OID, TEXT_OID]).unwrap_err().to_string(),
+            "function generate_series(integer, text) does not exist"
+        );
+        assert!(resolve(&[INT4_OID]).is_err());
+
+        let number = |n: &str| ast::Value::Number(n.to_string(), false);
+        let string = |s: &str| ast::Value::SingleQuotedString(s.to_string());
+        let array = Arg::Array(vec![number("1"), string("2"), ast::Value::Null]);
+        assert_eq!(arg_type(&array)?, INT4_ARRAY_OID);
+        assert_eq!(
+            coerce_arg(array, INT4_ARRAY_OID, ANYARRAY_OID)?,
+            Arg::Array(vec![number("1"), number("2"), ast::Value::Null])
+        );
+        assert!(arg_type(&Arg::Array(vec![number("1"), ast::Value::Boolean(true)])).is_err());
+        assert!(coerce_arg(Arg::Value(string("x")), UNKNOWN_OID, INT4_OID).is_err());
+
+        Ok(())
+    }
+}
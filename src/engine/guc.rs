@@ -0,0 +1,170 @@
+use anyhow::{bail, Result};
+
+use crate::catalog::pg_type::TEXT_OID;
+
+use super::result::{Column, QueryResult};
+use super::vtab::{Rows, ScanContext, VirtualTable};
+use super::workmem::format_work_mem;
+use super::Error;
+
+/// Name of the virtual table exposing the runtime settings of the engine.
+pub const PG_SETTINGS: &str = "pg_settings";
+
+/// A runtime setting of the engine, like a row of the pg_settings view of Postgres.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    /// Name of the setting, in lowercase.
+    pub name: &'static str,
+
+    /// Current value, in unit if the setting has one.
+    pub setting: String,
+
+    /// Unit of the value, like `kB` or `ms`.
+    pub unit: Option<&'static str>,
+
+    /// Value of the setting when the engine is created.
+    pub boot_val: String,
+
+    /// Short description of what the setting does.
+    pub short_desc: &'static str,
+}
+
+impl Setting {
+    /// Return the current value as shown by SHOW, with the largest unit that represents it
+    /// exactly for memory settings. Negative durations disable the setting, so they have no unit.
+    pub fn show(&self) -> String {
+        match (self.unit, self.setting.parse::<usize>()) {
+            (Some("kB"), Ok(kb)) => format_work_mem(kb * 1024),
+            (Some(unit), Ok(value)) => format!("{}{}", value, unit),
+            _ => self.setting.clone(),
+        }
+    }
+}
+
+/// Return the setting with the given name, ignoring case.
+///
+/// Return error if there is no setting with the name.
+pub fn find<'a>(settings: &'a [Setting], name: &str) -> Result<&'a Setting> {
+    match settings
+        .iter()
+        .find(|setting| setting.name.eq_ignore_ascii_case(name))
+    {
+        Some(setting) => Ok(setting),
+        None => bail!(Error::UnrecognizedParameter(name.to_string())),
+    }
+}
+
+/// The pg_settings view, with one row for each runtime setting of the engine.
+pub struct PgSettings;
+
+impl VirtualTable for PgSettings {
+    fn name(&self) -> &str {
+        PG_SETTINGS
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![
+            Column::new("name", TEXT_OID).not_null(),
+            Column::new("setting", TEXT_OID).not_null(),
+            Column::new("unit", TEXT_OID),
+            Column::new("boot_val", TEXT_OID).not_null(),
+            Column::new("short_desc", TEXT_OID).not_null(),
+        ]
+    }
+
+    fn scan(&self, ctx: &mut ScanContext) -> Result<Rows> {
+        let rows: Vec<Vec<String>> = ctx
+            .settings
+            .iter()
+            .map(|setting| {
+                vec![
+                    setting.name.to_string(),
+                    setting.setting.clone(),
+                    setting.unit.unwrap_or("NULL").to_string(),
+                    setting.boot_val.clone(),
+                    setting.short_desc.to_string(),
+                ]
+            })
+            .collect();
+        Ok(Box::new(rows.into_iter()))
+    }
+}
+
+/// Return the result of SHOW name, a single row with the value of the setting on a column named
+/// after it.
+///
+/// Return error if there is no setting with the name.
+pub fn show(settings: &[Setting], name: &str) -> Result<QueryResult> {
+    let setting = find(settings, name)?;
+    Ok(QueryResult {
+        columns: vec![Column::new(setting.name, TEXT_OID).not_null()],
+        rows: vec![vec![setting.show()]],
+    })
+}
+
+/// Return the result of SHOW ALL, with the name, value and description of each setting.
+pub fn show_all(settings: &[Setting]) -> QueryResult {
+    let columns = vec![
+        Column::new("name", TEXT_OID).not_null(),
+        Column::new("setting", TEXT_OID).not_null(),
+        Column::new("description", TEXT_OID).not_null(),
+    ];
+    let rows = settings
+        .iter()
+        .map(|setting| {
+            vec![
+                setting.name.to_string(),
+                setting.show(),
+                setting.short_desc.to_string(),
+            ]
+        })
+        .collect();
+
+    QueryResult { columns, rows }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings() -> Result<()> {
+        let settings = vec![
+            Setting {
+                name: "log_min_duration_statement",
+                setting: String::from("-1"),
+                unit: Some("ms"),
+                boot_val: String::from("-1"),
+                short_desc:
+                    "Sets the minimum execution time above which statements will be logged.",
+            },
+            Setting {
+                name: "work_mem",
+                setting: String::from("65536"),
+                unit: Some("kB"),
+                boot_val: String::from("4096"),
+                short_desc: "Sets the maximum memory to be used for query workspaces.",
+            },
+        ];
+
+        assert_eq!(settings[0].show(), "-1");
+        assert_eq!(settings[1].show(), "64MB");
+        assert_eq!(find(&settings, "WORK_MEM")?.setting, "65536");
+
+        let result = show(&settings, "work_mem")?;
+        assert_eq!(result.column_names(), vec!["work_mem"]);
+        assert_eq!(result.rows, vec![vec!["64MB"]]);
+        let err = show(&settings, "fsync").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::UnrecognizedParameter(_)
+        ));
+
+        assert_eq!(
+            show_all(&settings).rows[0][..2],
+            vec!["log_min_duration_statement", "-1"]
+        );
+
+        Ok(())
+    }
+}
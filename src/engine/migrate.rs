@@ -0,0 +1,26 @@
+/// Name of the relation that records the migrations applied to a database.
+pub const SCHEMA_MIGRATIONS: &str = "schema_migrations";
+
+/// A schema change of an application, applied by [super::Engine::migrate] at most once on each
+/// database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    /// Unique version of the migration. Migrations are applied in increasing version order.
+    pub version: i32,
+
+    /// Short description of the migration, used on error messages.
+    pub name: String,
+
+    /// SQL script executed to apply the migration.
+    pub sql: String,
+}
+
+impl Migration {
+    pub fn new(version: i32, name: &str, sql: &str) -> Self {
+        Self {
+            version,
+            name: name.to_string(),
+            sql: sql.to_string(),
+        }
+    }
+}
@@ -1,29 +1,320 @@
+use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::access::heap::{heap_insert, heap_scan, HeapTuple, TupleDesc};
+use crate::access::heap::{heap_delete, heap_insert, heap_iter_items, HeapTuple, TupleDesc};
+use crate::access::reloptions;
+use crate::access::tableam::{TableAccessMethod, TableAmRegistry, HEAP_TABLE_AM_OID};
 use crate::catalog::pg_attribute::PgAttribute;
+use crate::catalog::pg_audit::PgAudit;
 use crate::catalog::pg_class::PgClass;
-use crate::catalog::{heap, Catalog};
-use crate::storage::rel::{Relation, RelationData};
+use crate::catalog::pg_database::PgDatabase;
+use crate::catalog::pg_index::PgIndex;
+use crate::catalog::pg_policy::PgPolicy;
+use crate::catalog::pg_proc::{PgProc, PROKIND_FUNCTION, PROKIND_PROCEDURE};
+use crate::catalog::pg_type::{
+    format_type, PgType, BOOL_OID, INT4_OID, INT8_OID, TEXT_OID, UNKNOWN_OID, VOID_OID,
+};
+use crate::catalog::{self, heap, Catalog, DatabaseCatalog};
+use crate::control::ControlFile;
+use crate::initdb::bootstrap_database;
+use crate::storage::bufpage::ItemPointer;
+use crate::storage::pager::PageNumber;
+use crate::storage::rel::{self, database_path, Relation};
+use crate::storage::temp::{self, TempFile};
 use crate::storage::BufferPool;
-use anyhow::Result;
-use sqlparser::ast::{self, ColumnDef, ObjectName, Statement};
+use crate::{new_object_id, Oid};
+use anyhow::{bail, Result};
+use cache::{CacheKey, ResultCache, ResultCacheStats};
+use diagnostic::{closest_match, find_token};
+use guc::Setting;
+use log::{info, warn};
+use migrate::SCHEMA_MIGRATIONS;
+use parser::{ParsedStatement, Statement};
+use pgstat::{RelationStats, StatsCollector};
+use rewrite::Rewriter;
+use serde::{de::DeserializeOwned, Serialize};
+use sessions::Session;
+use sqlparser::ast::{self, ColumnDef, ObjectName};
 use sqlparser::dialect::PostgreSqlDialect;
-use sqlparser::parser::Parser;
-use tabled::builder::Builder;
-use tabled::Style;
+use srf::{Arg, FunctionRegistry, SetReturningFunction};
+use tracing::{debug_span, info_span};
+use types::{TypeIo, TypeRegistry};
+use vtab::{VirtualTable, VirtualTableRegistry};
+use workmem::{MemoryAccountant, SpillBuffer, DEFAULT_WORK_MEM};
+
+pub mod cache;
+pub mod diagnostic;
+mod expr;
+mod func;
+pub mod guc;
+pub mod migrate;
+mod pageinspect;
+mod parser;
+pub mod pgstat;
+mod plpgsql;
+mod projection;
+pub mod result;
+pub mod rewrite;
+pub mod script;
+pub mod sessions;
+pub mod srf;
+pub mod types;
+pub mod vtab;
+pub mod workmem;
+
+pub use diagnostic::Diagnostic;
+pub use migrate::Migration;
+pub use result::{Column, CommandTag, DisplayOptions, QueryResult, StatementResult};
+pub use rewrite::RewriteRule;
+pub use script::{OnError, ScriptResult, StatementError};
 
 const DIALECT: PostgreSqlDialect = PostgreSqlDialect {};
 
+/// Size of buffer pool used by engines that are not given one.
+pub const DEFAULT_BUFFER_POOL_SIZE: usize = 120;
+
+/// Maximum number of nested procedure calls, e.g of a procedure calling itself.
+const MAX_CALL_DEPTH: usize = 32;
+
+/// Errors related with statement execution.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("cannot execute {0} in a read-only engine")]
+    ReadOnly(String),
+
+    #[error("database \"{0}\" already exists")]
+    DuplicateDatabase(String),
+
+    #[error("relation \"{0}\" already exists")]
+    DuplicateRelation(String),
+
+    #[error("CREATE TABLE AS specifies {0} columns, but the query returns {1} columns")]
+    ColumnCountMismatch(usize, usize),
+
+    #[error("cannot drop the currently open database")]
+    DropCurrentDatabase,
+
+    #[error("database \"{0}\" is being accessed by other sessions")]
+    DatabaseInUse(String),
+
+    #[error("no database selected")]
+    NoDatabaseSelected,
+
+    #[error("cross-database references are not implemented: {0}")]
+    CrossDatabaseReference(String),
+
+    #[error("function {0} does not exist")]
+    FunctionNotFound(String),
+
+    #[error("function {0} is not unique")]
+    AmbiguousFunction(String),
+
+    #[error("function {0} already exists")]
+    DuplicateFunction(String),
+
+    #[error("procedure {0} does not exist")]
+    ProcedureNotFound(String),
+
+    #[error("procedure {0} is not unique")]
+    AmbiguousProcedure(String),
+
+    #[error("procedure parameters of type {0} are not supported")]
+    UnsupportedParameterType(String),
+
+    #[error("stack depth limit exceeded calling procedure {0}")]
+    StackDepthExceeded(String),
+
+    #[error("rewrite rule {0} already exists")]
+    DuplicateRewriteRule(String),
+
+    #[error("invalid arguments for function {0}")]
+    InvalidArguments(String),
+
+    /// A missing column, with the most similar column name if any.
+    #[error("column \"{0}\" does not exist")]
+    ColumnNotFound(String, Option<String>),
+
+    #[error("invalid input syntax for type integer: {0}")]
+    InvalidInteger(String),
+
+    #[error("invalid input syntax for type boolean: {0}")]
+    InvalidBoolean(String),
+
+    #[error("type \"{0}\" does not exist")]
+    TypeNotFound(String),
+
+    #[error("type \"{0}\" already exists")]
+    DuplicateType(String),
+
+    #[error("incorrect binary data format for type {0}")]
+    InvalidBinaryFormat(String),
+
+    #[error("cannot cast type {0} to {1}")]
+    CannotCast(String, String),
+
+    #[error("ARRAY types {0} and {1} cannot be matched")]
+    ArrayTypeMismatch(String, String),
+
+    #[error("cannot determine type of empty array")]
+    EmptyArray,
+
+    #[error("expression {0} is not supported in target list")]
+    UnsupportedTargetEntry(String),
+
+    #[error("policy \"{0}\" for table \"{1}\" already exists")]
+    DuplicatePolicy(String, String),
+
+    #[error("policy \"{0}\" for table \"{1}\" does not exist")]
+    PolicyNotFound(String, String),
+
+    #[error("new row violates row-level security policy for table \"{0}\"")]
+    PolicyViolation(String),
+
+    #[error("argument of {0} must be type boolean, not type {1}")]
+    NotBoolean(String, String),
+
+    #[error("operator does not exist: {0}")]
+    OperatorNotFound(String),
+
+    #[error("expression {0} is not supported")]
+    UnsupportedExpression(String),
+
+    #[error("{0} is not supported yet")]
+    Unsupported(String),
+
+    #[error("INSERT has {0} target columns but {1} expressions")]
+    InsertColumnMismatch(usize, usize),
+
+    #[error("division by zero")]
+    DivisionByZero,
+
+    #[error("integer out of range")]
+    IntegerOutOfRange,
+
+    #[error("syntax error at line {0} of DO block: {1}")]
+    DoBlockSyntax(usize, String),
+
+    #[error("\"{0}\" is not a known variable")]
+    VariableNotFound(String),
+
+    #[error("variable \"{0}\" is of type {1} but expression is of type {2}")]
+    VariableTypeMismatch(String, String, String),
+
+    #[error("record \"{0}\" is not assigned yet")]
+    RecordNotAssigned(String),
+
+    #[error("record \"{0}\" has no field \"{1}\"")]
+    RecordFieldNotFound(String, String),
+
+    #[error("query of FOR loop does not return rows: {0}")]
+    NotAQuery(String),
+
+    /// Error raised by RAISE EXCEPTION on a DO block, with its message.
+    #[error("{0}")]
+    RaiseException(String),
+
+    #[error("cannot roll back, there are no transactions yet")]
+    RollbackNotSupported,
+
+    #[error("migration {0} is out of order, versions should be unique and increasing")]
+    MigrationOutOfOrder(i32),
+
+    #[error("migration {0} ({1}) failed")]
+    MigrationFailed(i32, String),
+
+    #[error("invalid value for parameter \"work_mem\": \"{0}\"")]
+    InvalidWorkMem(String),
+
+    #[error("unrecognized configuration parameter \"{0}\"")]
+    UnrecognizedParameter(String),
+}
+
 pub struct Engine {
     buffer_pool: BufferPool,
     catalog: Catalog,
     db_data: String,
+
+    /// Table access methods available to store relations.
+    table_ams: TableAmRegistry,
+
+    /// Oid of the table access method used when creating new relations.
+    default_table_am: Oid,
+
+    /// Set-returning functions that can be used as row sources on FROM clause.
+    functions: FunctionRegistry,
+
+    /// Support functions of the data types, which convert values from and to text and binary.
+    types: TypeRegistry,
+
+    /// Rules applied to statements between parsing and execution.
+    rewriter: Rewriter,
+
+    /// Synthetic relations computed when scanned, like pg_settings and the pg_stat views.
+    virtual_tables: VirtualTableRegistry,
+
+    /// If true only queries are allowed and nothing is written back to disk.
+    read_only: bool,
+
+    /// If true the engine was opened by [Engine::open_read_only], so relation files are opened
+    /// without write permissions and the data directory is never changed.
+    read_only_files: bool,
+
+    /// If true the statements that modify a database are recorded on its pg_audit catalog.
+    audit: bool,
+
+    /// Catalogs of the database that the session is connected to.
+    database: Option<DatabaseCatalog>,
+
+    /// Registration of the session on the database that it is connected to, which prevents other
+    /// engines from dropping it. Read-only engines don't register their sessions.
+    session: Option<Session>,
+
+    /// Role of the session, which selects the row-level security policies applied. None is the
+    /// owner of all relations, which bypasses the policies.
+    role: Option<String>,
+
+    /// Minimum duration that a statement should take to be logged. If None no statement is logged.
+    log_min_duration: Option<Duration>,
+
+    /// Activity counters of relations accessed by this engine.
+    stats: StatsCollector,
+
+    /// Notices raised by the statements of the last exec call.
+    notices: Vec<String>,
+
+    /// Number of procedures being called, which is limited to catch infinite recursion.
+    call_depth: usize,
+
+    /// Accountant of the memory used by the operators of the statements, limited by work_mem.
+    memory: MemoryAccountant,
+
+    /// Cache of query results, or None if queries are always executed.
+    result_cache: Option<ResultCache>,
 }
 
 impl Drop for Engine {
     fn drop(&mut self) {
+        if self.read_only_files {
+            rel::release_read_only(&self.db_data);
+        }
+
+        // Every statement should unpin the pages that it used. Leaked pins are reported, and
+        // fail debug builds unless the engine is already being dropped by a panic.
+        let pinned = self.buffer_pool.pinned_buffers();
+        for buffer in &pinned {
+            warn!("buffer pin leak: {}", buffer);
+        }
+        debug_assert!(
+            pinned.is_empty() || std::thread::panicking(),
+            "{} buffers are still pinned when closing the engine",
+            pinned.len()
+        );
+
+        if self.read_only {
+            return;
+        }
         self.buffer_pool
             .flush_all_buffers()
             .expect("failed to flush all buffers to disk");
@@ -31,244 +322,3506 @@ impl Drop for Engine {
 }
 
 impl Engine {
-    pub fn new(buffer_pool: BufferPool, db_data: &str) -> Self {
-        Self {
+    /// Create a new engine over the given db data directory, which should be already initialized.
+    ///
+    /// Return error if the db data was initialized by an incompatible version.
+    pub fn new(buffer_pool: BufferPool, db_data: &str) -> Result<Self> {
+        let engine = Self::open(buffer_pool, db_data, false)?;
+
+        // Data directories may not allow removing files, which is not worth failing.
+        match temp::remove_temp_files(db_data) {
+            Ok(0) => {}
+            Ok(removed) => info!(
+                "removed {} temporary files left by processes that are not running",
+                removed
+            ),
+            Err(err) => warn!("could not remove temporary files: {}", err),
+        }
+
+        Ok(engine)
+    }
+
+    /// Open the given db data directory only for queries, e.g to run analytics over a snapshot
+    /// copied from another server. Data files are not required to be writable and any statement
+    /// that would modify the database is rejected with [Error::ReadOnly].
+    ///
+    /// Nothing on the data directory is changed: relation files are opened without write
+    /// permissions, so missing files are errors instead of being created, temporary files left by
+    /// other processes are kept and sessions are not registered, so other engines can drop the
+    /// database that the engine is connected to. While the engine is open, other engines of the
+    /// same thread over the same db data open its relation files only for reading too.
+    pub fn open_read_only(db_data: &str) -> Result<Self> {
+        let buffer_pool = BufferPool::new(DEFAULT_BUFFER_POOL_SIZE);
+        let mut engine = Self::open(buffer_pool, db_data, true)?;
+        engine.set_read_only(true);
+        Ok(engine)
+    }
+
+    fn open(buffer_pool: BufferPool, db_data: &str, read_only_files: bool) -> Result<Self> {
+        ControlFile::read(Path::new(db_data))?.validate()?;
+        if read_only_files {
+            rel::acquire_read_only(db_data);
+        }
+
+        Ok(Self {
             buffer_pool,
             catalog: Catalog::new(db_data),
             db_data: db_data.to_string(),
+            table_ams: TableAmRegistry::default(),
+            default_table_am: HEAP_TABLE_AM_OID,
+            functions: FunctionRegistry::default(),
+            types: TypeRegistry::default(),
+            rewriter: Rewriter::default(),
+            virtual_tables: VirtualTableRegistry::default(),
+            read_only: false,
+            read_only_files,
+            audit: false,
+            database: None,
+            session: None,
+            role: None,
+            log_min_duration: None,
+            stats: StatsCollector::default(),
+            notices: Vec::new(),
+            call_depth: 0,
+            memory: MemoryAccountant::new(DEFAULT_WORK_MEM),
+            result_cache: None,
+        })
+    }
+
+    /// Connect the session to the given database. All statements executed after this call
+    /// operate on relations of this database.
+    ///
+    /// Return error if the database does not exist.
+    pub fn use_database(&mut self, db_name: &str) -> Result<()> {
+        let database = self.catalog.open_database(&mut self.buffer_pool, db_name)?;
+        if !self.read_only_files {
+            self.session = Some(Session::connect(&self.db_data, database.db_oid())?);
         }
+
+        self.database = Some(database);
+        Ok(())
     }
 
-    pub fn exec(&mut self, command: &str, db_name: &str) -> Result<()> {
-        let ast = Parser::parse_sql(&DIALECT, command)?;
+    /// Return the name of database that the session is connected to.
+    pub fn current_database(&self) -> Option<&str> {
+        self.database.as_ref().map(DatabaseCatalog::db_name)
+    }
 
-        for stmt in ast {
-            self.exec_stmt(db_name, stmt)?;
+    /// Return the catalogs of the database that the session is connected to.
+    fn database(&self) -> Result<DatabaseCatalog> {
+        match &self.database {
+            Some(database) => Ok(database.clone()),
+            None => bail!(Error::NoDatabaseSelected),
         }
+    }
+
+    /// Set the role of the session, like SET ROLE. Relations with row-level security policies
+    /// only show to a role the rows allowed by the policies that apply to it, and reject inserts
+    /// of other rows. None bypasses all policies.
+    pub fn set_role(&mut self, role: Option<&str>) {
+        self.role = role.map(str::to_string);
+    }
+
+    /// Return the role of the session, if any.
+    pub fn current_role(&self) -> Option<&str> {
+        self.role.as_deref()
+    }
 
+    /// Set the memory budget of each operator, like SET work_mem. Operators that hold more rows
+    /// than fit on it spill them to disk.
+    pub fn set_work_mem(&mut self, work_mem: usize) {
+        self.memory.set_work_mem(work_mem);
+    }
+
+    /// Return the accountant of the memory used by operators, e.g to check their peak usage.
+    pub fn memory(&self) -> &MemoryAccountant {
+        &self.memory
+    }
+
+    /// Create a new buffer for an operator that holds many values, accounted by work_mem and
+    /// spilling to temporary files of the db data once over it.
+    pub fn spill_buffer<T: Serialize + DeserializeOwned>(&self) -> SpillBuffer<T> {
+        let db_data = self.db_data.clone();
+        SpillBuffer::new(&self.memory, Box::new(move || TempFile::create(&db_data)))
+    }
+
+    /// Make the engine serve only queries, rejecting any statement that would modify the
+    /// database. This is useful for standbys reading data files written by another process.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Record every statement that modifies the database on the pg_audit catalog, with its time,
+    /// role, command tag and number of affected rows. Queries and failed statements are not
+    /// recorded.
+    ///
+    /// Statements are recorded after they are executed, so a statement that could not be recorded
+    /// still succeeds and the failure is logged as a warning.
+    pub fn set_audit(&mut self, audit: bool) {
+        self.audit = audit;
+    }
+
+    /// Register a new table access method that relations can use to store their tuples.
+    pub fn register_table_am(&mut self, oid: Oid, am: Box<dyn TableAccessMethod>) -> Result<()> {
+        self.table_ams.register(oid, am)
+    }
+
+    /// Register a new set-returning function that queries can use as a row source on FROM clause.
+    pub fn register_function(&mut self, function: Box<dyn SetReturningFunction>) -> Result<()> {
+        self.functions.register(function)
+    }
+
+    /// Register a new virtual table that queries can read like a relation, e.g to expose state of
+    /// the embedding application.
+    pub fn register_virtual_table(&mut self, table: Box<dyn VirtualTable>) -> Result<()> {
+        self.virtual_tables.register(table)
+    }
+
+    /// Register the support functions of a new data type.
+    pub fn register_type(&mut self, typ: Box<dyn TypeIo>) -> Result<()> {
+        self.types.register(typ)
+    }
+
+    /// Register a new rewrite rule applied to every statement after parsing and before execution.
+    pub fn register_rewrite_rule(&mut self, rule: Box<dyn RewriteRule>) -> Result<()> {
+        self.rewriter.register(rule)
+    }
+
+    /// Set the table access method used by new relations. Existing relations keep using the access
+    /// method that they were created with.
+    pub fn set_default_table_am(&mut self, oid: Oid) -> Result<()> {
+        self.table_ams.get(oid)?;
+        self.default_table_am = oid;
         Ok(())
     }
 
-    fn exec_stmt(&mut self, db_name: &str, stmt: Statement) -> Result<()> {
-        match stmt {
-            Statement::CreateDatabase { db_name, .. } => self.create_database(db_name),
-            Statement::CreateTable { name, columns, .. } => {
-                self.create_table(db_name, name, columns)
-            }
-            Statement::Insert {
-                table_name,
-                columns,
-                source,
-                ..
-            } => self.insert_into(db_name, table_name, columns, source),
-            Statement::Query(query) => self.query(db_name, query),
-            _ => {
-                todo!()
-            }
+    /// Return the activity counters of the given relation of the current database.
+    pub fn relation_stats(&mut self, rel_name: &str) -> Result<RelationStats> {
+        let db_name = self.database()?.db_name().to_string();
+        let oid = self
+            .database()?
+            .get_oid_relation(&mut self.buffer_pool, rel_name)?;
+        Ok(self.stats.relation(&db_name, oid))
+    }
+
+    /// Return the names of the relations of the current database with the names of their columns,
+    /// e.g to complete them on the shell.
+    pub fn relations(&mut self) -> Result<Vec<(String, Vec<String>)>> {
+        let mut relations = Vec::new();
+        for rel in self.database()?.get_relations(&mut self.buffer_pool)? {
+            let columns = self.relation_columns(&rel.relname)?;
+            relations.push((rel.relname, columns));
         }
+        Ok(relations)
     }
 
-    fn query(&mut self, db_name: &str, query: Box<ast::Query>) -> Result<()> {
-        match query.body {
-            ast::SetExpr::Select(select) => {
-                for table in select.from {
-                    match table.relation {
-                        ast::TableFactor::Table { name, .. } => {
-                            let rel_name = name.0[0].to_string();
-                            let oid = self.catalog.get_oid_relation(
-                                &mut self.buffer_pool,
-                                db_name,
-                                &rel_name,
-                            )?;
-
-                            let rel_attrs = self.catalog.get_attributes_from_relation(
-                                &mut self.buffer_pool,
-                                db_name,
-                                &rel_name,
-                            )?;
+    /// Vacuum the given relation of the current database, returning to the operating system the
+    /// space of empty pages at the end of the relation.
+    pub fn vacuum(&mut self, rel_name: &str) -> Result<()> {
+        if self.read_only {
+            bail!(Error::ReadOnly(String::from("VACUUM")));
+        }
 
-                            let tuple_desc = TupleDesc { attrs: rel_attrs };
+        let (pg_class, rel) = self.open_relation(rel_name)?;
+        let am = self.table_ams.get(pg_class.relam)?;
+        am.vacuum(&mut self.buffer_pool, &rel)
+    }
 
-                            let rel = RelationData::open(oid, &self.db_data, db_name, &rel_name)?;
-                            let tuples = heap_scan(&mut self.buffer_pool, &rel)?;
-                            self.print_relation_tuples(&rel, tuples, &tuple_desc)?;
-                        }
-                        _ => todo!(),
-                    }
-                }
+    /// Apply the given migrations that were not applied yet to the current database, returning
+    /// the versions applied. Applied versions are recorded on the schema_migrations relation,
+    /// which is created by the first call.
+    ///
+    /// Migrations are applied in order and the first failed migration stops the process with
+    /// [Error::MigrationFailed]. There are no transactions yet, so the statements of the failed
+    /// migration executed before the failing one are not rolled back.
+    pub fn migrate(&mut self, migrations: &[Migration]) -> Result<Vec<i32>> {
+        for pair in migrations.windows(2) {
+            if pair[0].version >= pair[1].version {
+                bail!(Error::MigrationOutOfOrder(pair[1].version));
             }
-            _ => todo!(),
         }
-        Ok(())
-    }
 
-    fn print_relation_tuples(
-        &self,
-        rel: &Relation,
-        tuples: Vec<HeapTuple>,
-        tuple_desc: &TupleDesc,
-    ) -> Result<()> {
-        let mut columns = Vec::new();
-        let mut records = Vec::new();
+        if self
+            .database()?
+            .get_pg_class(&mut self.buffer_pool, SCHEMA_MIGRATIONS)
+            .is_err()
+        {
+            self.exec(&format!("CREATE TABLE {}(version int);", SCHEMA_MIGRATIONS))?;
+        }
 
-        match rel.borrow().rel_name.as_str() {
-            "pg_class" => {
-                columns.append(&mut vec![String::from("oid"), String::from("relname")]);
-                for tuple in tuples {
-                    let value = bincode::deserialize::<PgClass>(&tuple.data)?;
-                    records.push(vec![value.oid.to_string(), value.relname]);
-                }
+        let applied = self
+            .exec(&format!("SELECT version FROM {};", SCHEMA_MIGRATIONS))?
+            .into_iter()
+            .flat_map(|result| result.rows)
+            .map(|row| row[0].parse::<i32>())
+            .collect::<Result<HashSet<_>, _>>()?;
+
+        // Each script clears the notices, so the notices of all migrations are kept here.
+        let mut notices = Vec::new();
+        let mut versions = Vec::new();
+        for migration in migrations {
+            if applied.contains(&migration.version) {
+                continue;
             }
-            "pg_attribute" => {
-                columns.append(&mut vec![
-                    String::from("attrelid"),
-                    String::from("attname"),
-                    String::from("attnum"),
-                    String::from("attlen"),
-                ]);
-                for tuple in tuples {
-                    let value = bincode::deserialize::<PgAttribute>(&tuple.data)?;
-                    records.push(vec![
-                        value.attrelid.to_string(),
-                        value.attname,
-                        value.attnum.to_string(),
-                        value.attlen.to_string(),
-                    ]);
-                }
+
+            info!(
+                "applying migration {} ({})",
+                migration.version, migration.name
+            );
+            let results = self.exec_script(&migration.sql, OnError::Stop);
+            notices.append(&mut self.take_notices());
+            if let Some(Err(err)) = results?.pop() {
+                self.notices = notices;
+                return Err(anyhow::Error::new(err).context(Error::MigrationFailed(
+                    migration.version,
+                    migration.name.clone(),
+                )));
             }
-            _ => {
-                for attr in &tuple_desc.attrs {
-                    columns.push(attr.attname.clone());
-                }
 
-                for mut tuple in tuples {
-                    let mut tuple_values = Vec::new();
-                    for (i, attr) in tuple_desc.attrs.iter().enumerate() {
-                        assert_eq!(
-                            attr.attnum, i,
-                            "Expected equal tuple desc attr num to be equal loop index"
-                        );
+            self.exec(&format!(
+                "INSERT INTO {}(version) VALUES({});",
+                SCHEMA_MIGRATIONS, migration.version
+            ))?;
+            versions.push(migration.version);
+        }
 
-                        if tuple.data.len() < attr.attlen {
-                            // Means that the value does not exist on tuple.
-                            tuple_values.push(String::from("NULL"));
-                        } else {
-                            // Value exists on tuple, so deserialize it.
-                            let attr_value = &tuple.data[..attr.attlen];
-                            let value = bincode::deserialize::<i32>(&attr_value)?;
-                            tuple_values.push(value.to_string());
+        self.notices = notices;
+        Ok(versions)
+    }
 
-                            tuple.data = tuple.data[attr.attlen..].to_vec();
-                        }
-                    }
-                    records.push(tuple_values);
-                }
-            }
-        }
+    /// Return the runtime settings of the engine sorted by name, as shown by SHOW and the
+    /// pg_settings view.
+    pub fn settings(&self) -> Vec<Setting> {
+        let log_min_duration = match self.log_min_duration {
+            Some(duration) => duration.as_millis().to_string(),
+            None => String::from("-1"),
+        };
+        let default_table_am = self
+            .table_ams
+            .get(self.default_table_am)
+            .map(|am| am.name().to_string())
+            .unwrap_or_default();
+        let on_off = |value: bool| String::from(if value { "on" } else { "off" });
 
-        let mut table = Builder::default().set_columns(columns);
+        vec![
+            Setting {
+                name: "audit",
+                setting: on_off(self.audit),
+                unit: None,
+                boot_val: on_off(false),
+                short_desc: "Records the statements that modify the database on pg_audit.",
+            },
+            Setting {
+                name: "default_table_access_method",
+                setting: default_table_am,
+                unit: None,
+                boot_val: String::from("heap"),
+                short_desc: "Sets the default table access method for new tables.",
+            },
+            Setting {
+                name: "log_min_duration_statement",
+                setting: log_min_duration,
+                unit: Some("ms"),
+                boot_val: String::from("-1"),
+                short_desc:
+                    "Sets the minimum execution time above which statements will be logged.",
+            },
+            Setting {
+                name: "role",
+                setting: self.role.clone().unwrap_or_else(|| String::from("none")),
+                unit: None,
+                boot_val: String::from("none"),
+                short_desc: "Sets the current role.",
+            },
+            Setting {
+                name: "server_version",
+                setting: env!("CARGO_PKG_VERSION").to_string(),
+                unit: None,
+                boot_val: env!("CARGO_PKG_VERSION").to_string(),
+                short_desc: "Shows the server version.",
+            },
+            Setting {
+                name: "transaction_read_only",
+                setting: on_off(self.read_only),
+                unit: None,
+                boot_val: on_off(false),
+                short_desc: "Shows whether statements that modify the database are rejected.",
+            },
+            Setting {
+                name: "work_mem",
+                setting: (self.memory.work_mem() / 1024).to_string(),
+                unit: Some("kB"),
+                boot_val: (DEFAULT_WORK_MEM / 1024).to_string(),
+                short_desc: "Sets the maximum memory to be used for query workspaces.",
+            },
+        ]
+    }
 
-        for record in records {
-            table = table.add_record(record);
-        }
+    /// Cache the results of up to size queries, which are returned without scanning the relations
+    /// again while no statement of this engine changes their rows or policies. None disables the
+    /// cache, which is the default.
+    ///
+    /// Only queries reading heap relations are cached. Changes made by other engines over the same
+    /// db data are not seen while the results are cached, and cache hits are not counted as scans
+    /// on pg_stat_user_tables.
+    pub fn set_result_cache(&mut self, size: Option<usize>) {
+        self.result_cache = size.map(ResultCache::new);
+    }
 
-        let table = table.build().with(Style::psql());
+    /// Return the counters of the result cache, or None if it is disabled.
+    pub fn result_cache_stats(&self) -> Option<ResultCacheStats> {
+        self.result_cache.as_ref().map(ResultCache::stats)
+    }
 
-        println!("{}", table);
+    /// Log every statement that takes at least the given duration to execute, including its text,
+    /// duration and error if any. A zero duration logs all statements and None disables logging.
+    pub fn set_log_min_duration(&mut self, log_min_duration: Option<Duration>) {
+        self.log_min_duration = log_min_duration;
+    }
 
-        Ok(())
+    /// Execute the given SQL statements on the current database, returning the rows of each query.
+    ///
+    /// Errors of invalid statements have a [Diagnostic] context, with the position of the
+    /// offending token on command and a hint to fix it when possible.
+    pub fn exec(&mut self, command: &str) -> Result<Vec<QueryResult>> {
+        Ok(self
+            .exec_statements(command)?
+            .into_iter()
+            .flat_map(|result| result.results)
+            .collect())
     }
 
-    fn insert_into(
-        &mut self,
-        db_name: &str,
-        table_name: ObjectName,
-        columns: Vec<ast::Ident>,
-        source: Box<ast::Query>,
-    ) -> Result<()> {
-        let rel_name = table_name.0[0].to_string();
-        let oid = self
-            .catalog
-            .get_oid_relation(&mut self.buffer_pool, db_name, &rel_name)?;
+    /// Execute the given SQL statements on the current database like [Engine::exec], returning
+    /// the command tag and rows of each statement.
+    pub fn exec_statements(&mut self, command: &str) -> Result<Vec<StatementResult>> {
+        let db_name = self.database()?.db_name().to_string();
 
-        let rel = RelationData::open(oid, &self.db_data, db_name, &rel_name)?;
+        let _span = info_span!("exec", db = %db_name).entered();
+        self.notices.clear();
 
-        match source.body {
-            ast::SetExpr::Values(values) => {
-                let mut heap_data = Vec::new();
-                for (idx, _) in columns.iter().enumerate() {
-                    for row in &values.0 {
-                        assert_eq!(
-                            columns.len(),
-                            row.len(),
-                            "Incompatible columns and values to insert"
-                        );
-                        let value = &row[idx];
-                        match value {
-                            ast::Expr::Value(value) => match value {
-                                ast::Value::Number(value, _) => {
-                                    let value = value.parse::<i32>().unwrap();
-                                    heap_data.append(&mut bincode::serialize(&value).unwrap());
-                                }
-                                _ => todo!(),
-                            },
-                            _ => todo!(),
-                        }
+        let ast = debug_span!("parse").in_scope(|| parser::parse_sql(command))?;
+
+        let mut results = Vec::new();
+        for parsed in ast {
+            results.push(self.exec_parsed(&db_name, command, parsed)?);
+        }
+
+        Ok(results)
+    }
+
+    /// Execute a script of SQL statements, e.g read from a .sql file, returning the outcome of each
+    /// statement. Unlike [Engine::exec], failed statements are reported with their line on the
+    /// script, and the next statements are still executed if on_error is [OnError::Continue].
+    pub fn exec_script(&mut self, script: &str, on_error: OnError) -> Result<ScriptResult> {
+        let db_name = self.database()?.db_name().to_string();
+
+        let _span = info_span!("exec_script", db = %db_name).entered();
+        self.notices.clear();
+
+        let mut results = Vec::new();
+        for (offset, text) in parser::split_statements(script) {
+            let result = match parser::parse_statement_at(script, offset, text) {
+                Ok(Some(parsed)) => self.exec_parsed(&db_name, script, parsed),
+                Ok(None) => continue,
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok(result) => results.push(Ok(result)),
+                Err(err) => {
+                    results.push(Err(StatementError::new(script, offset, text, err)));
+                    if on_error == OnError::Stop {
+                        break;
                     }
                 }
-
-                heap_insert(&mut self.buffer_pool, &rel, &HeapTuple { data: heap_data })?;
             }
-            _ => todo!(),
         }
 
-        Ok(())
+        Ok(results)
     }
 
-    fn create_table(
+    /// Execute a statement parsed from sql, logging it and adding a [Diagnostic] to its error.
+    fn exec_parsed(
         &mut self,
         db_name: &str,
-        name: ObjectName,
-        columns: Vec<ColumnDef>,
-    ) -> Result<()> {
-        heap::heap_create(
+        sql: &str,
+        parsed: ParsedStatement,
+    ) -> Result<StatementResult> {
+        let ParsedStatement { offset, text, stmt } = parsed;
+        let statement = stmt.to_string();
+        let read_only = stmt.is_read_only();
+        let _span = info_span!("execute", statement = %statement).entered();
+        let start = Instant::now();
+        let result = debug_span!("rewrite")
+            .in_scope(|| self.rewriter.rewrite(db_name, stmt))
+            .and_then(|stmt| self.exec_stmt(db_name, stmt));
+        if let (Ok(result), true) = (&result, self.audit && !read_only) {
+            // The changes of the statement are already made, so it should not fail anymore.
+            if let Err(err) = self.audit_statement(&statement, &result.tag) {
+                warn!(
+                    "could not record statement on pg_audit: {} statement={:?}",
+                    err, statement
+                );
+            }
+        }
+        self.log_statement(db_name, &statement, start.elapsed(), &result);
+
+        result.map_err(|err| {
+            let diagnostic = self.diagnose(&err, sql, offset, text);
+            err.context(diagnostic)
+        })
+    }
+
+    /// Record a statement that modified the current database on its pg_audit catalog.
+    fn audit_statement(&mut self, statement: &str, tag: &CommandTag) -> Result<()> {
+        let entry = PgAudit {
+            audtime: SystemTime::now().duration_since(UNIX_EPOCH)?.as_micros() as u64,
+            audrole: self.role.clone(),
+            audcommand: tag.command.clone(),
+            audstatement: statement.to_string(),
+            audrows: tag.rows,
+        };
+        let db_oid = self.database()?.db_oid();
+        heap_insert(
             &mut self.buffer_pool,
-            &self.db_data,
-            db_name,
-            &name.0[0].to_string(),
-            columns,
-        )?;
-        Ok(())
+            &PgAudit::get_relation(&self.db_data, db_oid)?,
+            &HeapTuple::new(bincode::serialize(&entry)?),
+        )
     }
 
-    fn create_database(&self, name: ObjectName) -> Result<()> {
-        let table_path = Path::new(&self.db_data).join(name.0[0].to_string());
-        fs::create_dir(table_path)?;
-        Ok(())
+    /// Return the notices raised by the statements of the last [Engine::exec] call, e.g because a
+    /// statement was skipped by IF EXISTS or IF NOT EXISTS.
+    pub fn take_notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::initdb::init_database;
-    use tempfile::tempdir;
+    /// Raise a notice to the client. Notices are also logged.
+    fn notice(&mut self, message: String) {
+        info!("notice: {}", message);
+        self.notices.push(message);
+    }
 
-    #[test]
-    fn test_engine() -> Result<()> {
-        {
-            let db_data = tempdir()?;
-            let db_name = "test_engine";
+    /// Return the diagnostic of an error raised by the statement text at offset of sql, locating
+    /// the offending token and suggesting the most similar name for missing columns and relations.
+    fn diagnose(
+        &mut self,
+        err: &anyhow::Error,
+        sql: &str,
+        offset: usize,
+        text: &str,
+    ) -> Diagnostic {
+        let (token, hint) = match (err.downcast_ref(), err.downcast_ref()) {
+            (Some(Error::ColumnNotFound(column, similar)), _) => (
+                Some(column.clone()),
+                similar.as_ref().map(|similar| {
+                    format!("Perhaps you meant to reference the column \"{}\".", similar)
+                }),
+            ),
+            (Some(Error::InvalidInteger(value)), _) => (Some(value.clone()), None),
+            (Some(Error::FunctionNotFound(signature) | Error::ProcedureNotFound(signature)), _) => (
+                signature.split('(').next().map(str::to_string),
+                Some(String::from(
+                    "No function matches the given name and argument types. You might need to add explicit type casts.",
+                )),
+            ),
+            (Some(Error::AmbiguousFunction(signature)), _) => (
+                signature.split('(').next().map(str::to_string),
+                Some(String::from(
+                    "Could not choose a best candidate function. You might need to add explicit type casts.",
+                )),
+            ),
+            (_, Some(catalog::Error::RelationNotFound(rel_name))) => {
+                let relations = self
+                    .database()
+                    .and_then(|database| database.get_relations(&mut self.buffer_pool))
+                    .unwrap_or_default();
+                let similar =
+                    closest_match(rel_name, relations.iter().map(|rel| rel.relname.as_str()));
+                (
+                    Some(rel_name.clone()),
+                    similar.map(|similar| {
+                        format!(
+                            "Perhaps you meant to reference the relation \"{}\".",
+                            similar
+                        )
+                    }),
+                )
+            }
+            _ => (None, None),
+        };
 
-            init_database(&db_data.path().to_path_buf(), db_name)?;
+        let position = token
+            .and_then(|token| find_token(text, &token))
+            .map(|i| offset + i);
+        Diagnostic::new(err.to_string(), sql, position, hint)
+    }
 
-            let buffer = BufferPool::new(120);
-            let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string());
+    /// Log an executed statement if it took at least log_min_duration.
+    fn log_statement<T>(
+        &self,
+        db_name: &str,
+        statement: &str,
+        duration: Duration,
+        result: &Result<T>,
+    ) {
+        match self.log_min_duration {
+            Some(min_duration) if duration >= min_duration => {}
+            _ => return,
+        }
 
-            engine.exec("CREATE TABLE t(a int);", db_name)?;
-            engine.exec("INSERT INTO t(a) VALUES(87);", db_name)?;
-            engine.exec("SELECT * FROM t;", db_name)?;
+        let duration = duration.as_secs_f64() * 1000.0;
+        match result {
+            Ok(_) => info!(
+                "db={} duration={:.3}ms statement={:?}",
+                db_name, duration, statement
+            ),
+            Err(err) => warn!(
+                "db={} duration={:.3}ms statement={:?} error={:?}",
+                db_name,
+                duration,
+                statement,
+                err.to_string()
+            ),
         }
+    }
+
+    /// Execute a single statement, returning its command tag and the result sets that it produced.
+    fn exec_stmt(&mut self, db_name: &str, stmt: Statement) -> Result<StatementResult> {
+        if self.read_only && !stmt.is_read_only() {
+            let command = stmt.to_string();
+            let command = command.split_whitespace().next().unwrap_or_default();
+            bail!(Error::ReadOnly(command.to_string()));
+        }
+
+        let stmt = match stmt {
+            Statement::Sql(stmt) => *stmt,
+            Statement::DropDatabase { if_exists, name } => {
+                self.drop_database(name, if_exists)?;
+                return Ok(StatementResult::command(CommandTag::new("DROP DATABASE")));
+            }
+            Statement::CreatePolicy {
+                name,
+                table,
+                roles,
+                using,
+            } => {
+                self.create_policy(db_name, name, table, roles, using)?;
+                return Ok(StatementResult::command(CommandTag::new("CREATE POLICY")));
+            }
+            Statement::DropPolicy {
+                if_exists,
+                name,
+                table,
+            } => {
+                self.drop_policy(db_name, name, table, if_exists)?;
+                return Ok(StatementResult::command(CommandTag::new("DROP POLICY")));
+            }
+            Statement::Do { body } => {
+                let block = plpgsql::parse_block(&body)?;
+                plpgsql::execute(
+                    &block,
+                    Vec::new(),
+                    &mut BlockExecutor {
+                        engine: self,
+                        db_name,
+                    },
+                )?;
+                return Ok(StatementResult::command(CommandTag::new("DO")));
+            }
+            Statement::CreateProcedure {
+                or_replace,
+                name,
+                params,
+                body,
+            } => {
+                self.create_procedure(name, params, body, or_replace)?;
+                return Ok(StatementResult::command(CommandTag::new(
+                    "CREATE PROCEDURE",
+                )));
+            }
+            Statement::DropProcedure {
+                if_exists,
+                name,
+                arg_types,
+            } => {
+                self.drop_procedure(name, arg_types, if_exists)?;
+                return Ok(StatementResult::command(CommandTag::new("DROP PROCEDURE")));
+            }
+            Statement::Call { name, args } => {
+                self.call_procedure(db_name, name, args)?;
+                return Ok(StatementResult::command(CommandTag::new("CALL")));
+            }
+            Statement::SetRole { role } => {
+                let tag = if role.is_some() { "SET" } else { "RESET" };
+                self.role = role.map(|role| role.value);
+                return Ok(StatementResult::command(CommandTag::new(tag)));
+            }
+            Statement::AlterTableOptions { table, options } => {
+                self.alter_table_options(db_name, table, options)?;
+                return Ok(StatementResult::command(CommandTag::new("ALTER TABLE")));
+            }
+            Statement::SetWorkMem { value } => {
+                let tag = if value.is_some() { "SET" } else { "RESET" };
+                let work_mem = match value {
+                    Some(value) => workmem::parse_work_mem(&value)?,
+                    None => DEFAULT_WORK_MEM,
+                };
+                self.set_work_mem(work_mem);
+                return Ok(StatementResult::command(CommandTag::new(tag)));
+            }
+        };
+
+        // Like Postgres, CREATE TABLE AS and SELECT INTO are tagged as SELECT with the number of
+        // rows inserted on the new table.
+        let tag = match stmt {
+            ast::Statement::CreateDatabase {
+                db_name,
+                if_not_exists,
+                ..
+            } => {
+                self.create_database(db_name, if_not_exists)?;
+                CommandTag::new("CREATE DATABASE")
+            }
+            ast::Statement::CreateTable {
+                name,
+                columns,
+                with_options,
+                if_not_exists,
+                query: Some(query),
+                ..
+            } => {
+                let rows = self.create_table_as(
+                    db_name,
+                    name,
+                    columns,
+                    with_options,
+                    query,
+                    if_not_exists,
+                )?;
+                CommandTag::with_rows("SELECT", rows)
+            }
+            ast::Statement::CreateTable {
+                name,
+                columns,
+                with_options,
+                if_not_exists,
+                ..
+            } => {
+                self.create_table(db_name, name, columns, with_options, if_not_exists)?;
+                CommandTag::new("CREATE TABLE")
+            }
+            ast::Statement::Insert {
+                table_name,
+                columns,
+                source,
+                ..
+            } => {
+                let rows = self.insert_into(db_name, table_name, columns, source)?;
+                CommandTag::with_rows("INSERT", rows)
+            }
+            ast::Statement::Query(query) => match parser::select_into(&query) {
+                // SELECT ... INTO is the same of CREATE TABLE ... AS SELECT.
+                Some(name) => {
+                    let name = name.clone();
+                    let rows =
+                        self.create_table_as(db_name, name, Vec::new(), Vec::new(), query, false)?;
+                    CommandTag::with_rows("SELECT", rows)
+                }
+                None => {
+                    let results = self.cached_query(db_name, query)?;
+                    let rows = results.iter().map(|result| result.rows.len() as u64).sum();
+                    return Ok(StatementResult {
+                        tag: CommandTag::with_rows("SELECT", rows),
+                        results,
+                    });
+                }
+            },
+            ast::Statement::ShowVariable { variable } => {
+                let name = variable
+                    .iter()
+                    .map(|ident| ident.value.as_str())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                let result = if name.eq_ignore_ascii_case("all") {
+                    guc::show_all(&self.settings())
+                } else {
+                    guc::show(&self.settings(), &name)?
+                };
+                return Ok(StatementResult {
+                    tag: CommandTag::new("SHOW"),
+                    results: vec![result],
+                });
+            }
+            stmt => bail!(Error::Unsupported(stmt.to_string())),
+        };
+        Ok(StatementResult::command(tag))
+    }
+
+    /// Execute a query, returning its results from the result cache when they are cached.
+    fn cached_query(&mut self, db_name: &str, query: Box<ast::Query>) -> Result<Vec<QueryResult>> {
+        let relids = match self.result_cache {
+            Some(_) => self.cacheable_relations(db_name, &query)?,
+            None => None,
+        };
+        let relids = match relids {
+            Some(relids) => relids,
+            None => return self.query(db_name, query),
+        };
+
+        let key = CacheKey {
+            db_name: db_name.to_string(),
+            role: self.role.clone(),
+            query: query.to_string(),
+        };
+        if let Some(results) = self.result_cache.as_mut().and_then(|cache| cache.get(&key)) {
+            return Ok(results);
+        }
+
+        let results = self.query(db_name, query)?;
+        if let Some(cache) = &mut self.result_cache {
+            cache.insert(key, &relids, results.clone());
+        }
+        Ok(results)
+    }
+
+    /// Return the oids of the relations read by a query if its results can be cached, which are
+    /// the queries that only read heap relations. Virtual tables, functions, system catalogs and
+    /// relations of other access methods can return other rows without any statement changing
+    /// them, so queries reading them are never cached.
+    fn cacheable_relations(
+        &mut self,
+        db_name: &str,
+        query: &ast::Query,
+    ) -> Result<Option<Vec<Oid>>> {
+        let select = match &query.body {
+            ast::SetExpr::Select(select) => select,
+            _ => return Ok(None),
+        };
+
+        let relations = self.database()?.get_relations(&mut self.buffer_pool)?;
+        let mut relids = Vec::new();
+        for table in &select.from {
+            let name = match &table.relation {
+                ast::TableFactor::Table { name, args, .. } if args.is_empty() => name,
+                _ => return Ok(None),
+            };
+            let rel_name = relation_name(db_name, name)?;
+            if self
+                .virtual_tables
+                .get(schema_name(name), &rel_name)
+                .is_some()
+            {
+                return Ok(None);
+            }
+            match relations.iter().find(|rel| rel.relname == rel_name) {
+                Some(rel) if rel.relam == HEAP_TABLE_AM_OID => relids.push(rel.oid),
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(relids))
+    }
+
+    fn query(&mut self, db_name: &str, query: Box<ast::Query>) -> Result<Vec<QueryResult>> {
+        let mut results = Vec::new();
+        match query.body {
+            ast::SetExpr::Select(select) => {
+                for table in select.from {
+                    match table.relation {
+                        ast::TableFactor::Table {
+                            name, args, alias, ..
+                        } if !args.is_empty() => {
+                            let result = self.function_scan(&name, &args, alias)?;
+                            results.push(projection::project(&select.projection, result, None)?);
+                        }
+                        ast::TableFactor::Table { name, .. } => {
+                            let rel_name = relation_name(db_name, &name)?;
+                            if let Some(result) = self.virtual_table_scan(&name, &rel_name)? {
+                                results.push(projection::project(
+                                    &select.projection,
+                                    result,
+                                    None,
+                                )?);
+                                continue;
+                            }
+
+                            let (pg_class, rel) = self.open_relation(&rel_name)?;
+
+                            let rel_attrs = self
+                                .database()?
+                                .get_attributes_from_relation(&mut self.buffer_pool, &rel_name)?;
+
+                            let tuple_desc = TupleDesc { attrs: rel_attrs };
+
+                            let am = self.table_ams.get(pg_class.relam)?;
+                            let tuples = am.scan(&mut self.buffer_pool, &rel)?;
+                            self.stats.relation_mut(db_name, pg_class.oid).seq_scan += 1;
+
+                            let ctids: Vec<ItemPointer> =
+                                tuples.iter().map(|tuple| tuple.ctid).collect();
+                            let result = self.relation_tuples_result(&rel, tuples, &tuple_desc)?;
+                            let (result, ctids) =
+                                self.apply_policies(pg_class.oid, result, ctids)?;
+                            results.push(projection::project(
+                                &select.projection,
+                                result,
+                                Some(&ctids),
+                            )?);
+                        }
+                        relation => bail!(Error::Unsupported(relation.to_string())),
+                    }
+                }
+            }
+            body => bail!(Error::Unsupported(body.to_string())),
+        }
+        Ok(results)
+    }
+
+    /// Remove the rows of a relation that the row-level security policies don't allow the session
+    /// role to see, returning the visible rows and their ctids.
+    fn apply_policies(
+        &mut self,
+        relid: Oid,
+        result: QueryResult,
+        ctids: Vec<ItemPointer>,
+    ) -> Result<(QueryResult, Vec<ItemPointer>)> {
+        let quals = match self.row_security_quals(relid)? {
+            Some(quals) => quals,
+            None => return Ok((result, ctids)),
+        };
+
+        let names = result.column_names();
+        let mut rows = Vec::new();
+        let mut visible_ctids = Vec::new();
+        for (row, ctid) in result.rows.into_iter().zip(ctids) {
+            if satisfies_policies(&quals, &names, &row)? {
+                rows.push(row);
+                visible_ctids.push(ctid);
+            }
+        }
+
+        let columns = result.columns;
+        Ok((QueryResult { columns, rows }, visible_ctids))
+    }
+
+    /// Return the USING expressions of the policies of a relation that apply to the session role,
+    /// or None if the session is not subject to row-level security on the relation.
+    ///
+    /// Like Postgres with row-level security enabled, a relation with policies but none applying
+    /// to the role has no visible rows. Relations without policies are not restricted.
+    fn row_security_quals(&mut self, relid: Oid) -> Result<Option<Vec<ast::Expr>>> {
+        let role = match &self.role {
+            Some(role) => role.clone(),
+            None => return Ok(None),
+        };
+
+        let policies = self
+            .database()?
+            .get_policies(&mut self.buffer_pool, relid)?;
+        if policies.is_empty() {
+            return Ok(None);
+        }
+
+        let quals = policies
+            .iter()
+            .filter(|policy| policy.applies_to(&role))
+            .map(|policy| parser::parse_expr(&policy.polqual))
+            .collect::<Result<_>>()?;
+        Ok(Some(quals))
+    }
+
+    /// Execute a function used as a row source on FROM clause and return its rows. The overload
+    /// called is resolved from pg_proc, coercing the arguments to its parameter types. Columns are
+    /// renamed by the table alias, if any.
+    fn function_scan(
+        &mut self,
+        name: &ObjectName,
+        args: &[ast::FunctionArg],
+        alias: Option<ast::TableAlias>,
+    ) -> Result<QueryResult> {
+        let func_name = name.to_string();
+        let args = func::function_args(&func_name, args)?;
+
+        let mut candidates = self
+            .database()?
+            .get_functions(&mut self.buffer_pool, &func_name)?;
+        candidates.retain(|function| function.prokind == PROKIND_FUNCTION);
+        if candidates.is_empty() && self.functions.contains(&func_name) {
+            // Functions registered by embedders are not stored on pg_proc, so they are called
+            // with the arguments as written.
+            let args: Vec<Arg> = args.into_iter().map(|(arg, _)| arg).collect();
+            let result = self.functions.get(&func_name)?.call(&args)?;
+            return Ok(rename_columns(result, alias));
+        }
+
+        let arg_types: Vec<Oid> = args.iter().map(|(_, typ)| *typ).collect();
+        let (function, param_types) = func::resolve_function(&func_name, &candidates, &arg_types)?;
+        let args = args
+            .into_iter()
+            .zip(param_types)
+            .map(|((arg, from), to)| func::coerce_arg(arg, from, to))
+            .collect::<Result<Vec<_>>>()?;
+
+        let result = match function.prosrc.as_str() {
+            // Page inspection functions read the buffer pool, so they are executed by the engine
+            // instead of being registered as set-returning functions.
+            "page_header" | "heap_page_items" => {
+                let (rel_name, page_num) = match args.as_slice() {
+                    [Arg::Value(ast::Value::SingleQuotedString(rel_name)), Arg::Value(ast::Value::Number(page_num, _))] => {
+                        (rel_name, page_num.parse::<PageNumber>()?)
+                    }
+                    _ => bail!(Error::InvalidArguments(func_name)),
+                };
+                let (_, rel) = self.open_relation(rel_name)?;
+
+                if function.prosrc == "page_header" {
+                    pageinspect::page_header(&mut self.buffer_pool, &rel, page_num)?
+                } else {
+                    pageinspect::heap_page_items(&mut self.buffer_pool, &rel, page_num)?
+                }
+            }
+            prosrc => self.functions.get(prosrc)?.call(&args)?,
+        };
+
+        Ok(rename_columns(result, alias))
+    }
+
+    /// Scan the virtual table referenced by the given name, returning None if there is no virtual
+    /// table with the name.
+    fn virtual_table_scan(
+        &mut self,
+        name: &ObjectName,
+        rel_name: &str,
+    ) -> Result<Option<QueryResult>> {
+        let table = match self.virtual_tables.get(schema_name(name), rel_name) {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+
+        let database = self.database()?;
+        let settings = self.settings();
+        let mut ctx = vtab::ScanContext {
+            buffer_pool: &mut self.buffer_pool,
+            database: &database,
+            stats: &self.stats,
+            settings: &settings,
+        };
+        vtab::scan(table, &mut ctx).map(Some)
+    }
+
+    /// Open the given relation of the current database, returning its pg_class entry. Shared
+    /// relations are opened from the global database.
+    fn open_relation(&mut self, rel_name: &str) -> Result<(PgClass, Relation)> {
+        let database = self.database()?;
+        let pg_class = database.get_pg_class(&mut self.buffer_pool, rel_name)?;
+        let rel = database.open_relation(&pg_class)?;
+
+        Ok((pg_class, rel))
+    }
+
+    /// Decode the given tuples of a relation into rows using the relation tuple descriptor.
+    fn relation_tuples_result(
+        &self,
+        rel: &Relation,
+        tuples: Vec<HeapTuple>,
+        tuple_desc: &TupleDesc,
+    ) -> Result<QueryResult> {
+        let relid = rel.borrow().oid;
+        // Catalog columns are never NULL. Oids are shown as int8, since there is no oid type yet.
+        let catalog_columns = |columns: &[(&str, Oid)]| -> Vec<Column> {
+            columns
+                .iter()
+                .enumerate()
+                .map(|(attnum, (name, typ))| {
+                    Column::new(name, *typ)
+                        .with_source(relid, attnum)
+                        .not_null()
+                })
+                .collect()
+        };
+
+        let mut columns = Vec::new();
+        let mut records = Vec::new();
+
+        match rel.borrow().rel_name.as_str() {
+            "pg_class" => {
+                columns = catalog_columns(&[
+                    ("oid", INT8_OID),
+                    ("relname", TEXT_OID),
+                    ("relam", INT8_OID),
+                    ("relisshared", BOOL_OID),
+                ]);
+                // Relations without options have NULL reloptions, like on Postgres.
+                columns.push(Column::new("reloptions", TEXT_OID).with_source(relid, 4));
+                for tuple in tuples {
+                    let value = bincode::deserialize::<PgClass>(&tuple.data)?;
+                    let reloptions = if value.reloptions.is_empty() {
+                        String::from("NULL")
+                    } else {
+                        reloptions::format_reloptions(&value.reloptions)
+                    };
+                    records.push(vec![
+                        value.oid.to_string(),
+                        value.relname,
+                        value.relam.to_string(),
+                        value.relisshared.to_string(),
+                        reloptions,
+                    ]);
+                }
+            }
+            "pg_database" => {
+                columns = catalog_columns(&[("oid", INT8_OID), ("datname", TEXT_OID)]);
+                for tuple in tuples {
+                    let value = bincode::deserialize::<PgDatabase>(&tuple.data)?;
+                    records.push(vec![value.oid.to_string(), value.datname]);
+                }
+            }
+            "pg_type" => {
+                columns = catalog_columns(&[
+                    ("oid", INT8_OID),
+                    ("typname", TEXT_OID),
+                    ("typlen", INT4_OID),
+                    ("typelem", INT8_OID),
+                ]);
+                for tuple in tuples {
+                    let value = bincode::deserialize::<PgType>(&tuple.data)?;
+                    records.push(vec![
+                        value.oid.to_string(),
+                        value.typname,
+                        value.typlen.to_string(),
+                        value.typelem.to_string(),
+                    ]);
+                }
+            }
+            "pg_index" => {
+                columns = catalog_columns(&[
+                    ("indexrelid", INT8_OID),
+                    ("indrelid", INT8_OID),
+                    ("indkey", TEXT_OID),
+                ]);
+                for tuple in tuples {
+                    let value = bincode::deserialize::<PgIndex>(&tuple.data)?;
+                    records.push(vec![
+                        value.indexrelid.to_string(),
+                        value.indrelid.to_string(),
+                        format!("{:?}", value.indkey),
+                    ]);
+                }
+            }
+            "pg_proc" => {
+                columns = catalog_columns(&[
+                    ("oid", INT8_OID),
+                    ("proname", TEXT_OID),
+                    ("prokind", TEXT_OID),
+                    ("proargtypes", TEXT_OID),
+                    ("proargnames", TEXT_OID),
+                    ("prorettype", INT8_OID),
+                    ("proretset", BOOL_OID),
+                    ("provariadic", INT8_OID),
+                    ("prosrc", TEXT_OID),
+                ]);
+                for tuple in tuples {
+                    let value = bincode::deserialize::<PgProc>(&tuple.data)?;
+                    records.push(vec![
+                        value.oid.to_string(),
+                        value.proname,
+                        value.prokind.to_string(),
+                        format!("{:?}", value.proargtypes),
+                        format!("{:?}", value.proargnames),
+                        value.prorettype.to_string(),
+                        value.proretset.to_string(),
+                        value.provariadic.to_string(),
+                        value.prosrc,
+                    ]);
+                }
+            }
+            "pg_policy" => {
+                columns = catalog_columns(&[
+                    ("oid", INT8_OID),
+                    ("polname", TEXT_OID),
+                    ("polrelid", INT8_OID),
+                    ("polroles", TEXT_OID),
+                    ("polqual", TEXT_OID),
+                ]);
+                for tuple in tuples {
+                    let value = bincode::deserialize::<PgPolicy>(&tuple.data)?;
+                    records.push(vec![
+                        value.oid.to_string(),
+                        value.polname,
+                        value.polrelid.to_string(),
+                        format!("{:?}", value.polroles),
+                        value.polqual,
+                    ]);
+                }
+            }
+            "pg_audit" => {
+                // Statements of the owner and commands that don't handle rows have NULL role and
+                // rows.
+                columns = vec![
+                    Column::new("audtime", INT8_OID)
+                        .with_source(relid, 0)
+                        .not_null(),
+                    Column::new("audrole", TEXT_OID).with_source(relid, 1),
+                    Column::new("audcommand", TEXT_OID)
+                        .with_source(relid, 2)
+                        .not_null(),
+                    Column::new("audstatement", TEXT_OID)
+                        .with_source(relid, 3)
+                        .not_null(),
+                    Column::new("audrows", INT8_OID).with_source(relid, 4),
+                ];
+                let null = || String::from("NULL");
+                for tuple in tuples {
+                    let value = bincode::deserialize::<PgAudit>(&tuple.data)?;
+                    records.push(vec![
+                        value.audtime.to_string(),
+                        value.audrole.unwrap_or_else(null),
+                        value.audcommand,
+                        value.audstatement,
+                        value
+                            .audrows
+                            .map(|rows| rows.to_string())
+                            .unwrap_or_else(null),
+                    ]);
+                }
+            }
+            "pg_attribute" => {
+                columns = catalog_columns(&[
+                    ("attrelid", INT8_OID),
+                    ("attname", TEXT_OID),
+                    ("attnum", INT4_OID),
+                    ("attlen", INT4_OID),
+                ]);
+                for tuple in tuples {
+                    let value = bincode::deserialize::<PgAttribute>(&tuple.data)?;
+                    records.push(vec![
+                        value.attrelid.to_string(),
+                        value.attname,
+                        value.attnum.to_string(),
+                        value.attlen.to_string(),
+                    ]);
+                }
+            }
+            _ => {
+                for attr in &tuple_desc.attrs {
+                    columns
+                        .push(Column::new(&attr.attname, INT4_OID).with_source(relid, attr.attnum));
+                }
+
+                // Columns are int4 until pg_attribute records their types.
+                let int4 = self.types.get(INT4_OID)?;
+
+                for mut tuple in tuples {
+                    let mut tuple_values = Vec::new();
+                    for (i, attr) in tuple_desc.attrs.iter().enumerate() {
+                        assert_eq!(
+                            attr.attnum, i,
+                            "Expected equal tuple desc attr num to be equal loop index"
+                        );
+
+                        if tuple.data.len() < attr.attlen {
+                            // Means that the value does not exist on tuple.
+                            tuple_values.push(String::from("NULL"));
+                        } else {
+                            // Value exists on tuple, so format it.
+                            let attr_value = &tuple.data[..attr.attlen];
+                            tuple_values.push(int4.output(attr_value)?);
+
+                            tuple.data = tuple.data[attr.attlen..].to_vec();
+                        }
+                    }
+                    records.push(tuple_values);
+                }
+            }
+        }
+
+        Ok(QueryResult {
+            columns,
+            rows: records,
+        })
+    }
+
+    fn insert_into(
+        &mut self,
+        db_name: &str,
+        table_name: ObjectName,
+        columns: Vec<ast::Ident>,
+        source: Box<ast::Query>,
+    ) -> Result<u64> {
+        let rel_name = relation_name(db_name, &table_name)?;
+        let (pg_class, rel) = self.open_relation(&rel_name)?;
+
+        let rows = if let ast::SetExpr::Select(_) = source.body {
+            // INSERT ... SELECT, so insert all rows returned by the query.
+            result_values(self.query(db_name, source)?)?
+        } else {
+            match source.body {
+                ast::SetExpr::Values(values) => {
+                    let mut rows = Vec::new();
+                    for row in &values.0 {
+                        let mut values = Vec::new();
+                        for value in row {
+                            match value {
+                                ast::Expr::Value(value) => match value {
+                                    ast::Value::Number(number, _) => {
+                                        values.push(number.parse::<i32>().map_err(|_| {
+                                            Error::InvalidInteger(value.to_string())
+                                        })?);
+                                    }
+                                    _ => bail!(Error::InvalidInteger(value.to_string())),
+                                },
+                                // Other expressions can't reference columns, e.g values computed
+                                // by DO blocks.
+                                expr => match expr::eval(expr, &expr::Row::default())? {
+                                    expr::Datum::Int(value) => values.push(value),
+                                    _ => bail!(Error::InvalidInteger(expr.to_string())),
+                                },
+                            }
+                        }
+                        rows.push(values);
+                    }
+                    rows
+                }
+                body => bail!(Error::Unsupported(body.to_string())),
+            }
+        };
+
+        for row in &rows {
+            if columns.len() != row.len() {
+                bail!(Error::InsertColumnMismatch(columns.len(), row.len()));
+            }
+        }
+
+        self.insert_rows(db_name, &pg_class, &rel, &rows)
+    }
+
+    /// Insert the given rows into a relation using its table access method, returning the number
+    /// of inserted rows.
+    fn insert_rows(
+        &mut self,
+        db_name: &str,
+        pg_class: &PgClass,
+        rel: &Relation,
+        rows: &[Vec<i32>],
+    ) -> Result<u64> {
+        if let Some(quals) = self.row_security_quals(pg_class.oid)? {
+            let columns = self.relation_columns(&pg_class.relname)?;
+            for row in rows {
+                // Values of missing columns at the end of the row are NULL.
+                let mut values: Vec<String> = row.iter().map(ToString::to_string).collect();
+                values.resize(columns.len(), String::from("NULL"));
+                if !satisfies_policies(&quals, &columns, &values)? {
+                    bail!(Error::PolicyViolation(pg_class.relname.clone()));
+                }
+            }
+        }
+
+        let am = self.table_ams.get(pg_class.relam)?;
+        let mut inserted = 0;
+        for row in rows {
+            let mut heap_data = Vec::new();
+            for value in row {
+                heap_data.append(&mut bincode::serialize(value)?);
+            }
+            am.tuple_insert(&mut self.buffer_pool, rel, &HeapTuple::new(heap_data))?;
+            inserted += 1;
+        }
+        self.stats.relation_mut(db_name, pg_class.oid).n_tup_ins += inserted;
+        self.invalidate_results(db_name, pg_class.oid);
+
+        Ok(inserted)
+    }
+
+    fn create_table(
+        &mut self,
+        db_name: &str,
+        name: ObjectName,
+        columns: Vec<ColumnDef>,
+        with_options: Vec<ast::SqlOption>,
+        if_not_exists: bool,
+    ) -> Result<()> {
+        let rel_name = relation_name(db_name, &name)?;
+        if !self.check_new_relation(&rel_name, if_not_exists)? {
+            return Ok(());
+        }
+
+        // Options are validated before creating anything, so invalid ones don't leave a table.
+        let mut reloptions = Vec::new();
+        for option in with_options {
+            reloptions::set_reloption(
+                &mut reloptions,
+                &option.name.value,
+                &reloption_value(&option.value),
+            )?;
+        }
+
+        let database = self.database()?;
+        heap::heap_create(
+            &mut self.buffer_pool,
+            &self.db_data,
+            database.db_oid(),
+            &rel_name,
+            columns,
+            self.default_table_am,
+            self.table_ams.get(self.default_table_am)?,
+        )?;
+        if !reloptions.is_empty() {
+            database.set_reloptions(&mut self.buffer_pool, &rel_name, reloptions)?;
+        }
+        Ok(())
+    }
+
+    /// Set or reset the storage options of a relation of db_name, like fillfactor. Options without
+    /// value are reset to their default. New options only apply to the pages written after them.
+    fn alter_table_options(
+        &mut self,
+        db_name: &str,
+        table: ObjectName,
+        options: Vec<(ast::Ident, Option<ast::Value>)>,
+    ) -> Result<()> {
+        let rel_name = relation_name(db_name, &table)?;
+        let database = self.database()?;
+        let mut reloptions = database
+            .get_pg_class(&mut self.buffer_pool, &rel_name)?
+            .reloptions;
+        for (name, value) in options {
+            match value {
+                Some(value) => reloptions::set_reloption(
+                    &mut reloptions,
+                    &name.value,
+                    &reloption_value(&value),
+                )?,
+                None => reloptions::reset_reloption(&mut reloptions, &name.value)?,
+            }
+        }
+        database.set_reloptions(&mut self.buffer_pool, &rel_name, reloptions)?;
+
+        // Reopen the relation, so the cached relation uses the new options.
+        self.open_relation(&rel_name)?;
+        Ok(())
+    }
+
+    /// Create a new table with the rows returned by the given query, returning the number of rows
+    /// inserted. Unless columns are given, the table columns are named after the query output
+    /// columns. All columns are int4, since it is the only type supported.
+    fn create_table_as(
+        &mut self,
+        db_name: &str,
+        name: ObjectName,
+        columns: Vec<ColumnDef>,
+        with_options: Vec<ast::SqlOption>,
+        query: Box<ast::Query>,
+        if_not_exists: bool,
+    ) -> Result<u64> {
+        let rel_name = relation_name(db_name, &name)?;
+        if !self.check_new_relation(&rel_name, if_not_exists)? {
+            return Ok(0);
+        }
+
+        let results = self.query(db_name, query)?;
+        let result_columns = results
+            .first()
+            .map(|result| result.column_names())
+            .unwrap_or_default();
+
+        let columns = if columns.is_empty() {
+            result_columns
+                .iter()
+                .map(|column| ColumnDef {
+                    name: ast::Ident::new(column),
+                    data_type: ast::DataType::Int(None),
+                    collation: None,
+                    options: Vec::new(),
+                })
+                .collect()
+        } else if columns.len() != result_columns.len() {
+            bail!(Error::ColumnCountMismatch(
+                columns.len(),
+                result_columns.len()
+            ));
+        } else {
+            columns
+        };
+
+        let rows = result_values(results)?;
+        self.create_table(db_name, name, columns, with_options, false)?;
+
+        let (pg_class, rel) = self.open_relation(&rel_name)?;
+        self.insert_rows(db_name, &pg_class, &rel, &rows)
+    }
+
+    /// Check that a new relation with the given name can be created on db_name.
+    ///
+    /// Return false, raising a notice, if the relation already exists and if_not_exists is set,
+    /// or error if it is not set.
+    fn check_new_relation(&mut self, rel_name: &str, if_not_exists: bool) -> Result<bool> {
+        if self
+            .database()?
+            .get_pg_class(&mut self.buffer_pool, rel_name)
+            .is_err()
+        {
+            return Ok(true);
+        }
+
+        if if_not_exists {
+            self.notice(format!(
+                "relation \"{}\" already exists, skipping",
+                rel_name
+            ));
+            return Ok(false);
+        }
+        bail!(Error::DuplicateRelation(rel_name.to_string()));
+    }
+
+    /// Return the column names of the given relation.
+    fn relation_columns(&mut self, rel_name: &str) -> Result<Vec<String>> {
+        Ok(self
+            .database()?
+            .get_attributes_from_relation(&mut self.buffer_pool, rel_name)?
+            .into_iter()
+            .map(|attr| attr.attname)
+            .collect())
+    }
+
+    /// Create a row-level security policy on a relation of db_name, restricting its rows to the
+    /// ones that satisfy the USING expression for sessions using any of the given roles.
+    fn create_policy(
+        &mut self,
+        db_name: &str,
+        name: ast::Ident,
+        table: ObjectName,
+        roles: Vec<ast::Ident>,
+        using: ast::Expr,
+    ) -> Result<()> {
+        let rel_name = relation_name(db_name, &table)?;
+        let relid = self
+            .database()?
+            .get_oid_relation(&mut self.buffer_pool, &rel_name)?;
+        let policies = self
+            .database()?
+            .get_policies(&mut self.buffer_pool, relid)?;
+        if policies.iter().any(|policy| policy.polname == name.value) {
+            bail!(Error::DuplicatePolicy(name.value, rel_name));
+        }
+
+        // Evaluate the expression on a row of NULLs, so references to missing columns are
+        // rejected now instead of by every query on the relation.
+        let columns = self.relation_columns(&rel_name)?;
+        let values = vec![String::from("NULL"); columns.len()];
+        satisfies_policies(std::slice::from_ref(&using), &columns, &values)?;
+
+        self.invalidate_results(db_name, relid);
+        let db_oid = self.database()?.db_oid();
+        let policy = PgPolicy {
+            oid: new_object_id(),
+            polname: name.value,
+            polrelid: relid,
+            polroles: roles.into_iter().map(|role| role.value).collect(),
+            polqual: using.to_string(),
+        };
+        heap_insert(
+            &mut self.buffer_pool,
+            &PgPolicy::get_relation(&self.db_data, db_oid)?,
+            &HeapTuple::new(bincode::serialize(&policy)?),
+        )
+    }
+
+    /// Drop a row-level security policy of a relation of db_name.
+    ///
+    /// Return error if the policy does not exist, unless if_exists is set.
+    fn drop_policy(
+        &mut self,
+        db_name: &str,
+        name: ast::Ident,
+        table: ObjectName,
+        if_exists: bool,
+    ) -> Result<()> {
+        let rel_name = relation_name(db_name, &table)?;
+        let relid = self
+            .database()?
+            .get_oid_relation(&mut self.buffer_pool, &rel_name)?;
+        let db_oid = self.database()?.db_oid();
+        let pg_policy = PgPolicy::get_relation(&self.db_data, db_oid)?;
+
+        let mut entry = None;
+        heap_iter_items(
+            &mut self.buffer_pool,
+            &pg_policy,
+            |ctid, tuple| -> Result<()> {
+                let policy = bincode::deserialize::<PgPolicy>(tuple)?;
+                if policy.polrelid == relid && policy.polname == name.value {
+                    entry = Some(ctid);
+                }
+                Ok(())
+            },
+        )?;
+
+        match entry {
+            Some(ctid) => {
+                self.invalidate_results(db_name, relid);
+                heap_delete(&mut self.buffer_pool, &pg_policy, ctid)
+            }
+            None if if_exists => {
+                self.notice(format!(
+                    "policy \"{}\" for table \"{}\" does not exist, skipping",
+                    name.value, rel_name
+                ));
+                Ok(())
+            }
+            None => bail!(Error::PolicyNotFound(name.value, rel_name)),
+        }
+    }
+
+    /// Create a procedure on db_name with a PL/pgSQL body, which is parsed now so syntax errors
+    /// are reported by CREATE instead of by every CALL. An existing procedure with the same name
+    /// and parameter types is replaced if or_replace is set.
+    fn create_procedure(
+        &mut self,
+        name: ObjectName,
+        params: Vec<(ast::Ident, ast::DataType)>,
+        body: String,
+        or_replace: bool,
+    ) -> Result<()> {
+        plpgsql::parse_block(&body)?;
+
+        let proname = name.to_string();
+        let mut proargtypes = Vec::with_capacity(params.len());
+        let mut proargnames = Vec::with_capacity(params.len());
+        for (param, data_type) in params {
+            let typ = func::type_oid(&data_type)?;
+            if !plpgsql::is_supported_type(typ) {
+                bail!(Error::UnsupportedParameterType(format_type(typ)));
+            }
+            proargtypes.push(typ);
+            proargnames.push(param.value);
+        }
+
+        let db_oid = self.database()?.db_oid();
+        let pg_proc = PgProc::get_relation(&self.db_data, db_oid)?;
+
+        let mut existing = None;
+        heap_iter_items(
+            &mut self.buffer_pool,
+            &pg_proc,
+            |ctid, tuple| -> Result<()> {
+                let function = bincode::deserialize::<PgProc>(tuple)?;
+                if function.proname == proname && function.proargtypes == proargtypes {
+                    existing = Some((ctid, function.prokind));
+                }
+                Ok(())
+            },
+        )?;
+
+        match existing {
+            Some((ctid, PROKIND_PROCEDURE)) if or_replace => {
+                heap_delete(&mut self.buffer_pool, &pg_proc, ctid)?;
+            }
+            Some(_) => bail!(Error::DuplicateFunction(func::format_signature(
+                &proname,
+                &proargtypes
+            ))),
+            None => {}
+        }
+
+        let procedure = PgProc {
+            oid: new_object_id(),
+            proname,
+            prokind: PROKIND_PROCEDURE,
+            proargtypes,
+            proargnames,
+            prorettype: VOID_OID,
+            proretset: false,
+            provariadic: 0,
+            prosrc: body,
+        };
+        heap_insert(
+            &mut self.buffer_pool,
+            &pg_proc,
+            &HeapTuple::new(bincode::serialize(&procedure)?),
+        )
+    }
+
+    /// Drop a procedure of db_name. The parameter types are only needed to choose between
+    /// overloads of the procedure.
+    ///
+    /// Return error if no procedure matches, unless if_exists is set.
+    fn drop_procedure(
+        &mut self,
+        name: ObjectName,
+        arg_types: Option<Vec<ast::DataType>>,
+        if_exists: bool,
+    ) -> Result<()> {
+        let proname = name.to_string();
+        let arg_types = arg_types
+            .map(|types| types.iter().map(func::type_oid).collect::<Result<Vec<_>>>())
+            .transpose()?;
+        let signature = match &arg_types {
+            Some(types) => func::format_signature(&proname, types),
+            None => proname.clone(),
+        };
+
+        let db_oid = self.database()?.db_oid();
+        let pg_proc = PgProc::get_relation(&self.db_data, db_oid)?;
+
+        let mut entries = Vec::new();
+        heap_iter_items(
+            &mut self.buffer_pool,
+            &pg_proc,
+            |ctid, tuple| -> Result<()> {
+                let function = bincode::deserialize::<PgProc>(tuple)?;
+                let matches_args = arg_types.iter().all(|types| *types == function.proargtypes);
+                if function.prokind == PROKIND_PROCEDURE
+                    && function.proname == proname
+                    && matches_args
+                {
+                    entries.push(ctid);
+                }
+                Ok(())
+            },
+        )?;
+
+        match entries.as_slice() {
+            [ctid] => heap_delete(&mut self.buffer_pool, &pg_proc, *ctid),
+            [] if if_exists => {
+                self.notice(format!("procedure {} does not exist, skipping", signature));
+                Ok(())
+            }
+            [] => bail!(Error::ProcedureNotFound(signature)),
+            _ => bail!(Error::AmbiguousProcedure(signature)),
+        }
+    }
+
+    /// Call a procedure of db_name, executing its body with the parameters assigned to the given
+    /// arguments. Arguments should be constant expressions.
+    fn call_procedure(
+        &mut self,
+        db_name: &str,
+        name: ObjectName,
+        args: Vec<ast::Expr>,
+    ) -> Result<()> {
+        let proname = name.to_string();
+        let values = args
+            .iter()
+            .map(|arg| expr::eval(arg, &expr::Row::default()))
+            .collect::<Result<Vec<_>>>()?;
+        let arg_types: Vec<Oid> = values
+            .iter()
+            .map(|value| match value {
+                expr::Datum::Int(_) => INT4_OID,
+                expr::Datum::Bool(_) => BOOL_OID,
+                expr::Datum::Null => UNKNOWN_OID,
+            })
+            .collect();
+
+        let mut candidates = self
+            .database()?
+            .get_functions(&mut self.buffer_pool, &proname)?;
+        candidates.retain(|function| function.prokind == PROKIND_PROCEDURE);
+        let (procedure, _) = match func::resolve_function(&proname, &candidates, &arg_types) {
+            Ok(resolved) => resolved,
+            Err(err) => match err.downcast::<Error>()? {
+                Error::FunctionNotFound(signature) => bail!(Error::ProcedureNotFound(signature)),
+                Error::AmbiguousFunction(signature) => {
+                    bail!(Error::AmbiguousProcedure(signature))
+                }
+                err => bail!(err),
+            },
+        };
+
+        let params = procedure
+            .proargnames
+            .iter()
+            .zip(&procedure.proargtypes)
+            .zip(values)
+            .map(|((name, typ), value)| plpgsql::Param {
+                name: name.clone(),
+                typ: *typ,
+                value,
+            })
+            .collect();
+        let block = plpgsql::parse_block(&procedure.prosrc)?;
+
+        if self.call_depth >= MAX_CALL_DEPTH {
+            bail!(Error::StackDepthExceeded(proname));
+        }
+        self.call_depth += 1;
+        let result = plpgsql::execute(
+            &block,
+            params,
+            &mut BlockExecutor {
+                engine: self,
+                db_name,
+            },
+        );
+        self.call_depth -= 1;
+
+        result
+    }
+
+    fn create_database(&mut self, name: ObjectName, if_not_exists: bool) -> Result<()> {
+        let db_name = name.0[0].to_string();
+        if self
+            .catalog
+            .get_database_oid(&mut self.buffer_pool, &db_name)
+            .is_ok()
+        {
+            if if_not_exists {
+                self.notice(format!("database \"{}\" already exists, skipping", db_name));
+                return Ok(());
+            }
+            bail!(Error::DuplicateDatabase(db_name));
+        }
+        bootstrap_database(&mut self.buffer_pool, &self.db_data, &db_name)
+    }
+
+    /// Drop the given database, removing its pg_database entry, its buffers and all its files.
+    ///
+    /// Return error if the database is the current database or any other session is connected
+    /// to it. A missing database is an error too, unless if_exists is set.
+    fn drop_database(&mut self, name: ObjectName, if_exists: bool) -> Result<()> {
+        let db_name = name.0[0].to_string();
+        if self.current_database() == Some(db_name.as_str()) {
+            bail!(Error::DropCurrentDatabase);
+        }
+        let pg_database = PgDatabase::get_relation(&self.db_data)?;
+        let mut entry = None;
+        heap_iter_items(
+            &mut self.buffer_pool,
+            &pg_database,
+            |ctid, tuple| -> Result<()> {
+                let database = bincode::deserialize::<PgDatabase>(tuple)?;
+                if database.datname == db_name {
+                    entry = Some((ctid, database.oid));
+                }
+                Ok(())
+            },
+        )?;
+        let (ctid, db_oid) = match entry {
+            Some(entry) => entry,
+            None if if_exists => {
+                self.notice(format!("database \"{}\" does not exist, skipping", db_name));
+                return Ok(());
+            }
+            None => bail!(catalog::Error::DatabaseNotFound(db_name)),
+        };
+        if sessions::count(&self.db_data, db_oid)? > 0 {
+            bail!(Error::DatabaseInUse(db_name));
+        }
+
+        heap_delete(&mut self.buffer_pool, &pg_database, ctid)?;
+        self.buffer_pool.drop_database(&self.db_data, db_oid)?;
+        rel::evict_database(&self.db_data, db_oid);
+        fs::remove_dir_all(database_path(&self.db_data, db_oid))?;
+        self.stats.remove_database(&db_name);
+        if let Some(cache) = &mut self.result_cache {
+            cache.remove_database(&db_name);
+        }
+
+        Ok(())
+    }
+
+    /// Invalidate the cached results of the queries that read the given relation of db_name.
+    fn invalidate_results(&mut self, db_name: &str, relid: Oid) {
+        if let Some(cache) = &mut self.result_cache {
+            cache.invalidate(db_name, relid);
+        }
+    }
+}
+
+/// Executor of the statements of a DO block on a database. Notices raised by the block are kept
+/// while its statements are executed.
+struct BlockExecutor<'a> {
+    engine: &'a mut Engine,
+    db_name: &'a str,
+}
+
+impl plpgsql::Executor for BlockExecutor<'_> {
+    fn exec_sql(&mut self, sql: &str) -> Result<Vec<QueryResult>> {
+        let mut results = Vec::new();
+        for parsed in parser::parse_sql(sql)? {
+            results.extend(self.engine.exec_parsed(self.db_name, sql, parsed)?.results);
+        }
+        Ok(results)
+    }
+
+    fn notice(&mut self, message: String) {
+        self.engine.notice(message);
+    }
+
+    /// There are no transactions yet, so COMMIT only writes the changes done so far to disk. Read
+    /// only engines never change the data directory, so there is nothing to write.
+    fn commit(&mut self) -> Result<()> {
+        if self.engine.read_only {
+            return Ok(());
+        }
+        self.engine.buffer_pool.flush_all_buffers()
+    }
+}
+
+/// Return true if the row with the given columns and values satisfies any of the given policy
+/// expressions. Policies are permissive, so a row only needs to satisfy one of them.
+fn satisfies_policies(quals: &[ast::Expr], columns: &[String], values: &[String]) -> Result<bool> {
+    let row = expr::Row { columns, values };
+    for qual in quals {
+        if expr::eval_predicate(qual, &row, "POLICY USING")? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Return the values of all rows of the given query results, which should be integers.
+fn result_values(results: Vec<QueryResult>) -> Result<Vec<Vec<i32>>> {
+    let mut rows = Vec::new();
+    for result in results {
+        for row in result.rows {
+            let mut values = Vec::new();
+            for value in row {
+                values.push(value.parse::<i32>()?);
+            }
+            rows.push(values);
+        }
+    }
+    Ok(rows)
+}
+
+/// Rename the columns of a function result by its table alias, if any.
+fn rename_columns(mut result: QueryResult, alias: Option<ast::TableAlias>) -> QueryResult {
+    if let Some(alias) = alias {
+        if alias.columns.is_empty() {
+            // Like Postgres, the single column of a function is named after the table alias.
+            if result.columns.len() == 1 {
+                result.columns[0].name = alias.name.value;
+            }
+        } else {
+            for (column, alias) in result.columns.iter_mut().zip(alias.columns) {
+                column.name = alias.value;
+            }
+        }
+    }
+    result
+}
+
+/// Return the text of a relation option value, without the quotes of string values.
+fn reloption_value(value: &ast::Value) -> String {
+    match value {
+        ast::Value::SingleQuotedString(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Return the schema of a qualified object name, or None if the name is not qualified.
+fn schema_name(name: &ObjectName) -> Option<&str> {
+    match name.0.len() {
+        1 => None,
+        len => Some(name.0[len - 2].value.as_str()),
+    }
+}
+
+/// Return the relation name of a possibly qualified object name. Qualified names referencing a
+/// database other than db_name are rejected.
+fn relation_name(db_name: &str, name: &ObjectName) -> Result<String> {
+    if name.0.len() == 3 && name.0[0].to_string() != db_name {
+        bail!(Error::CrossDatabaseReference(name.to_string()));
+    }
+    Ok(name.0[name.0.len() - 1].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::GLOBAL_DB_OID;
+    use crate::initdb::init_database;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_engine() -> Result<()> {
+        {
+            let db_data = tempdir()?;
+            let db_name = "test_engine";
+
+            init_database(&db_data.path().to_path_buf(), db_name)?;
+
+            let buffer = BufferPool::new(120);
+            let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+            engine.use_database(db_name)?;
+
+            engine.exec("CREATE TABLE t(a int);")?;
+            engine.exec("INSERT INTO t(a) VALUES(87);")?;
+            engine.exec("SELECT * FROM t;")?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_custom_table_am() -> Result<()> {
+        use crate::storage::rel::Relation;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        /// Access method that keep all tuples in memory.
+        struct MemoryAccessMethod {
+            tuples: Rc<RefCell<Vec<Vec<u8>>>>,
+        }
+
+        impl TableAccessMethod for MemoryAccessMethod {
+            fn name(&self) -> &str {
+                "memory"
+            }
+
+            fn relation_create(&self, _: &mut BufferPool, _: &Relation) -> Result<()> {
+                Ok(())
+            }
+
+            fn tuple_insert(
+                &self,
+                _: &mut BufferPool,
+                _: &Relation,
+                tuple: &HeapTuple,
+            ) -> Result<()> {
+                self.tuples.borrow_mut().push(tuple.data.clone());
+                Ok(())
+            }
+
+            fn scan(&self, _: &mut BufferPool, _: &Relation) -> Result<Vec<HeapTuple>> {
+                Ok(self
+                    .tuples
+                    .borrow()
+                    .iter()
+                    .map(|data| HeapTuple::new(data.clone()))
+                    .collect())
+            }
+        }
+
+        let db_data = tempdir()?;
+        let db_name = "test_engine_custom_table_am";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let tuples = Rc::new(RefCell::new(Vec::new()));
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+        engine.register_table_am(
+            10,
+            Box::new(MemoryAccessMethod {
+                tuples: tuples.clone(),
+            }),
+        )?;
+        engine.set_default_table_am(10)?;
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(87);")?;
+        engine.exec("SELECT * FROM t;")?;
+
+        assert_eq!(tuples.borrow().len(), 1);
+        assert!(engine.set_default_table_am(11).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_read_only() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_read_only";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        {
+            let buffer = BufferPool::new(120);
+            let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+            engine.use_database(db_name)?;
+            engine.exec("CREATE TABLE t(a int);")?;
+            engine.exec("INSERT INTO t(a) VALUES(87);")?;
+        }
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+        engine.set_read_only(true);
+
+        engine.exec("SELECT * FROM t;")?;
+
+        let err = engine.exec("INSERT INTO t(a) VALUES(88);").unwrap_err();
+        assert!(matches!(err.downcast::<Error>()?, Error::ReadOnly(_)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_open_read_only() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_open_read_only";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+        let db_data = db_data.path().to_string_lossy().to_string();
+
+        let relid = {
+            let buffer = BufferPool::new(120);
+            let mut engine = Engine::new(buffer, &db_data)?;
+            engine.use_database(db_name)?;
+            engine.exec("CREATE TABLE t(a int);")?;
+            engine.exec("INSERT INTO t(a) VALUES(87);")?;
+            engine
+                .database()?
+                .get_oid_relation(&mut engine.buffer_pool, "t")?
+        };
+
+        let mut engine = Engine::open_read_only(&db_data)?;
+        engine.use_database(db_name)?;
+
+        let results = engine.exec("SELECT * FROM t;")?;
+        assert_eq!(results[0].rows, vec![vec!["87"]]);
+
+        let err = engine.exec("CREATE TABLE t2(a int);").unwrap_err();
+        assert!(matches!(err.downcast::<Error>()?, Error::ReadOnly(_)));
+        engine.exec("DO $$ BEGIN COMMIT; END $$;")?;
+
+        // Missing relation files are not created, and temporary files are not removed.
+        let path = rel::relation_path(&db_data, engine.database()?.db_oid(), relid);
+        drop(engine);
+        fs::remove_file(&path)?;
+        let leftover = temp::temp_dir(&db_data).join("pgsql_tmp0.0");
+        fs::create_dir_all(temp::temp_dir(&db_data))?;
+        fs::write(&leftover, b"")?;
+
+        let mut engine = Engine::open_read_only(&db_data)?;
+        engine.use_database(db_name)?;
+        assert!(engine.exec("SELECT * FROM t;").is_err());
+        assert!(!path.exists());
+        assert!(leftover.exists());
+
+        // Relation files are writable again once the read-only engine is closed.
+        drop(engine);
+        fs::remove_file(&leftover)?;
+        let mut engine = Engine::new(BufferPool::new(120), &db_data)?;
+        engine.use_database(db_name)?;
+        engine.exec("CREATE TABLE t2(a int);")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_use_database() -> Result<()> {
+        let db_data = tempdir()?;
+
+        init_database(&db_data.path().to_path_buf(), "db1")?;
+        init_database(&db_data.path().to_path_buf(), "db2")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+
+        let err = engine.exec("CREATE TABLE t(a int);").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::NoDatabaseSelected
+        ));
+
+        let err = engine.use_database("db3").unwrap_err();
+        assert!(matches!(
+            err.downcast::<catalog::Error>()?,
+            catalog::Error::DatabaseNotFound(_)
+        ));
+
+        engine.use_database("db1")?;
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO db1.public.t(a) VALUES(87);")?;
+
+        let err = engine.exec("SELECT * FROM db2.public.t;").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::CrossDatabaseReference(_)
+        ));
+
+        engine.use_database("db2")?;
+        assert_eq!(engine.current_database(), Some("db2"));
+        assert!(engine.exec("SELECT * FROM t;").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_create_database() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db1")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db1")?;
+
+        engine.exec("CREATE DATABASE db2;")?;
+        let err = engine.exec("CREATE DATABASE db2;").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::DuplicateDatabase(_)
+        ));
+
+        engine.use_database("db2")?;
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("SELECT * FROM pg_type;")?;
+        engine.exec("SELECT * FROM pg_database;")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_database_catalog() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db1")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db1")?;
+        engine.exec("CREATE DATABASE db2;")?;
+        engine.exec("CREATE TABLE t(a int); INSERT INTO t(a) VALUES(1);")?;
+
+        // Relations with the same name on different databases are distinct relations.
+        engine.use_database("db2")?;
+        let db2 = engine.database()?;
+        assert_eq!(db2.db_name(), "db2");
+        assert!(engine.exec("SELECT * FROM t;").is_err());
+        engine.exec("CREATE TABLE t(a int, b int); INSERT INTO t(a, b) VALUES(2, 3);")?;
+        assert_eq!(
+            engine.exec("SELECT * FROM t;")?[0].rows,
+            vec![vec!["2", "3"]]
+        );
+
+        engine.use_database("db1")?;
+        let db1 = engine.database()?;
+        assert_ne!(db1.db_oid(), db2.db_oid());
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows, vec![vec!["1"]]);
+        assert_ne!(
+            db1.get_oid_relation(&mut engine.buffer_pool, "t")?,
+            db2.get_oid_relation(&mut engine.buffer_pool, "t")?
+        );
+        assert_eq!(engine.relations()?[0].1, vec!["a"]);
+
+        // Shared catalogs are stored once for all databases, other catalogs once per database.
+        let pg_database = db1.get_pg_class(&mut engine.buffer_pool, "pg_database")?;
+        assert_eq!(db1.relation_db_oid(&pg_database), GLOBAL_DB_OID);
+        assert_eq!(db2.relation_db_oid(&pg_database), GLOBAL_DB_OID);
+        let pg_class = db1.get_pg_class(&mut engine.buffer_pool, "pg_class")?;
+        assert_eq!(db1.relation_db_oid(&pg_class), db1.db_oid());
+        assert_eq!(db2.relation_db_oid(&pg_class), db2.db_oid());
+        let databases = engine.exec("SELECT * FROM pg_database;")?[0].rows.clone();
+        engine.use_database("db2")?;
+        assert_eq!(
+            engine.exec("SELECT * FROM pg_database;")?[0].rows,
+            databases
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_drop_database() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_data = db_data.path().to_string_lossy().to_string();
+        init_database(&Path::new(&db_data).to_path_buf(), "db1")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data)?;
+        engine.use_database("db1")?;
+
+        // Write db2 to disk, so other engines can connect to it, but leave dirty pages of its
+        // relations on the buffer pool, which should be discarded by the drop.
+        engine.exec("CREATE DATABASE db2;")?;
+        engine.buffer_pool.flush_all_buffers()?;
+        engine.use_database("db2")?;
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(1);")?;
+        let db_oid = engine
+            .catalog
+            .get_database_oid(&mut engine.buffer_pool, "db2")?;
+
+        let err = engine.exec("DROP DATABASE db2;").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::DropCurrentDatabase
+        ));
+
+        engine.use_database("db1")?;
+        {
+            let mut other = Engine::new(BufferPool::new(10), &db_data)?;
+            other.use_database("db2")?;
+            let err = engine.exec("DROP DATABASE db2;").unwrap_err();
+            assert!(matches!(err.downcast::<Error>()?, Error::DatabaseInUse(_)));
+        }
+
+        // Sessions of other threads are visible too.
+        let (connected, connected_rx) = std::sync::mpsc::channel();
+        let (done, done_rx) = std::sync::mpsc::channel::<()>();
+        let other = std::thread::spawn({
+            let db_data = db_data.clone();
+            move || -> Result<()> {
+                let mut other = Engine::new(BufferPool::new(10), &db_data)?;
+                other.use_database("db2")?;
+                connected.send(())?;
+                done_rx.recv()?;
+                Ok(())
+            }
+        });
+        connected_rx.recv()?;
+        let err = engine.exec("DROP DATABASE db2;").unwrap_err();
+        assert!(matches!(err.downcast::<Error>()?, Error::DatabaseInUse(_)));
+        done.send(())?;
+        other.join().unwrap()?;
+
+        engine.exec("DROP DATABASE db2;")?;
+        assert!(!database_path(&db_data, db_oid).exists());
+        assert!(engine.use_database("db2").is_err());
+        assert!(engine.exec("DROP DATABASE db2;").is_err());
+
+        // The name can be used again by a new database.
+        engine.exec("CREATE DATABASE db2;")?;
+        engine.use_database("db2")?;
+        engine.exec("CREATE TABLE t(a int);")?;
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_if_exists() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db1")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db1")?;
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        let err = engine.exec("CREATE TABLE t(a int);").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::DuplicateRelation(_)
+        ));
+        engine.exec("CREATE TABLE IF NOT EXISTS t(a int);")?;
+        assert_eq!(
+            engine.take_notices(),
+            vec!["relation \"t\" already exists, skipping"]
+        );
+        assert_eq!(
+            engine
+                .database()?
+                .get_relations(&mut engine.buffer_pool)?
+                .len(),
+            1
+        );
+
+        engine.exec("CREATE DATABASE IF NOT EXISTS db2;")?;
+        assert!(engine.take_notices().is_empty());
+        engine.exec("CREATE DATABASE IF NOT EXISTS db2;")?;
+        assert_eq!(
+            engine.take_notices(),
+            vec!["database \"db2\" already exists, skipping"]
+        );
+
+        engine.exec("DROP DATABASE IF EXISTS db2; DROP DATABASE IF EXISTS db2;")?;
+        assert_eq!(
+            engine.take_notices(),
+            vec!["database \"db2\" does not exist, skipping"]
+        );
+        assert!(engine.exec("DROP DATABASE db2;").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_create_table_as() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+
+        engine.exec("CREATE TABLE t(a int, b int);")?;
+        engine.exec("INSERT INTO t(a, b) VALUES(1, 2), (3, 4);")?;
+
+        engine.exec("CREATE TABLE t2 AS SELECT b, a FROM t;")?;
+        let result = &engine.exec("SELECT * FROM t2;")?[0];
+        assert_eq!(result.column_names(), vec!["b", "a"]);
+        assert_eq!(result.rows, vec![vec!["2", "1"], vec!["4", "3"]]);
+        assert_eq!(engine.relation_stats("t2")?.n_tup_ins, 2);
+
+        engine.exec("SELECT * INTO series FROM generate_series(1, 3);")?;
+        let result = &engine.exec("SELECT * FROM series;")?[0];
+        assert_eq!(result.column_names(), vec!["generate_series"]);
+        assert_eq!(result.rows.len(), 3);
+
+        engine.exec("CREATE TABLE IF NOT EXISTS t2 AS SELECT * FROM t;")?;
+        assert_eq!(engine.exec("SELECT * FROM t2;")?[0].rows.len(), 2);
+        assert!(engine.exec("CREATE TABLE t2 AS SELECT * FROM t;").is_err());
+
+        let err = engine
+            .exec("CREATE TABLE t3(a int) AS SELECT * FROM t;")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::ColumnCountMismatch(1, 2)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_command_tags() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+
+        let results = engine.exec_statements(
+            "CREATE TABLE t(a int); INSERT INTO t(a) VALUES(1), (2), (3); \
+             INSERT INTO t(a) SELECT * FROM t; SELECT * FROM t; SELECT * INTO t2 FROM t; \
+             CREATE DATABASE db2; DROP DATABASE db2;",
+        )?;
+        let tags: Vec<String> = results.iter().map(|r| r.tag.to_string()).collect();
+        assert_eq!(
+            tags,
+            vec![
+                "CREATE TABLE",
+                "INSERT 0 3",
+                "INSERT 0 3",
+                "SELECT 6",
+                "SELECT 6",
+                "CREATE DATABASE",
+                "DROP DATABASE"
+            ]
+        );
+        assert_eq!(results[3].results[0].rows.len(), 6);
+        assert!(results[1].results.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_unsupported() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+        engine.exec("CREATE TABLE t(a int);")?;
+
+        for sql in vec![
+            "DROP TABLE t;",
+            "UPDATE t SET a = 1;",
+            "SELECT * FROM (SELECT * FROM t) AS s;",
+            "SELECT * FROM t UNION SELECT * FROM t;",
+            "INSERT INTO t(a) SELECT * FROM t UNION SELECT * FROM t;",
+        ] {
+            let err = engine.exec(sql).unwrap_err();
+            assert!(
+                matches!(err.downcast::<Error>()?, Error::Unsupported(_)),
+                "{}",
+                sql
+            );
+        }
+
+        let err = engine.exec("INSERT INTO t VALUES(1);").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::InsertColumnMismatch(0, 1)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_exec_script() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+
+        let script = "-- Create t\n\
+                      CREATE TABLE t(a int);\n\
+                      INSERT INTO t(a) VALUES(1);\n\
+                      INSERT INTO missing(a)\n  VALUES(2);\n\
+                      SELECT * FROM;\n\
+                      INSERT INTO t(a) VALUES(3);\n";
+
+        let results = engine.exec_script(script, OnError::Stop)?;
+        assert_eq!(results.len(), 3);
+        let err = results[2].as_ref().unwrap_err();
+        assert_eq!(err.line, 4);
+        assert_eq!(err.statement, "INSERT INTO missing(a)\n  VALUES(2)");
+        assert_eq!(err.to_string(), "line 4: relation missing does not exist");
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 1);
+
+        let results = engine.exec_script(script, OnError::Continue)?;
+        let lines: Vec<usize> = results
+            .iter()
+            .filter_map(|result| result.as_ref().err().map(|err| err.line))
+            .collect();
+        assert_eq!(results.len(), 5);
+        assert_eq!(lines, vec![2, 4, 6]);
+        assert!(results[3].as_ref().unwrap_err().error.is::<Diagnostic>());
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_migrate() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+
+        let mut migrations = vec![
+            Migration::new(1, "create t", "CREATE TABLE t(a int);"),
+            Migration::new(2, "seed t", "INSERT INTO t(a) VALUES(1), (2);"),
+        ];
+        assert_eq!(engine.migrate(&migrations)?, vec![1, 2]);
+        assert_eq!(engine.migrate(&migrations)?, Vec::<i32>::new());
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 2);
+
+        migrations.push(Migration::new(
+            3,
+            "broken",
+            "CREATE TABLE t2(a int);\nINSERT INTO missing(a) VALUES(1);",
+        ));
+        migrations.push(Migration::new(4, "create t3", "CREATE TABLE t3(a int);"));
+        let err = engine.migrate(&migrations).unwrap_err();
+        assert_eq!(err.to_string(), "migration 3 (broken) failed");
+        assert_eq!(
+            err.downcast_ref::<StatementError>().map(|err| err.line),
+            Some(2)
+        );
+        assert!(engine.exec("SELECT * FROM t3;").is_err());
+
+        migrations[2].sql = String::from("CREATE TABLE IF NOT EXISTS t2(a int);");
+        assert_eq!(engine.migrate(&migrations)?, vec![3, 4]);
+        assert_eq!(
+            engine.take_notices(),
+            vec!["relation \"t2\" already exists, skipping"]
+        );
+        let result = &engine.exec("SELECT * FROM schema_migrations;")?[0];
+        assert_eq!(result.rows.len(), 4);
+
+        let err = engine
+            .migrate(&[Migration::new(2, "a", ""), Migration::new(1, "b", "")])
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::MigrationOutOfOrder(1)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_rewrite_rule() -> Result<()> {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        /// Redirect the relations of each tenant to relations suffixed by the tenant name.
+        struct TenantRule {
+            tenant: Rc<RefCell<String>>,
+        }
+
+        impl RewriteRule for TenantRule {
+            fn name(&self) -> &str {
+                "tenant"
+            }
+
+            fn rewrite(&self, _: &str, stmt: &mut ast::Statement) -> Result<()> {
+                let tenant = self.tenant.borrow();
+                if tenant.is_empty() {
+                    bail!("no tenant selected");
+                }
+                rewrite::visit_relations(stmt, &mut |name| {
+                    let last = name.0.len() - 1;
+                    name.0[last] = ast::Ident::new(format!("{}_{}", name.0[last], tenant));
+                });
+                Ok(())
+            }
+        }
+
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+        engine.exec("CREATE TABLE items_a(x int); CREATE TABLE items_b(x int);")?;
+
+        let tenant = Rc::new(RefCell::new(String::new()));
+        engine.register_rewrite_rule(Box::new(TenantRule {
+            tenant: tenant.clone(),
+        }))?;
+        let err = engine
+            .register_rewrite_rule(Box::new(TenantRule {
+                tenant: tenant.clone(),
+            }))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::DuplicateRewriteRule(_)
+        ));
+
+        assert!(engine.exec("SELECT * FROM items;").is_err());
+
+        *tenant.borrow_mut() = String::from("a");
+        engine.exec("INSERT INTO items(x) VALUES(1), (2);")?;
+        assert_eq!(engine.exec("SELECT * FROM items;")?[0].rows.len(), 2);
+
+        *tenant.borrow_mut() = String::from("b");
+        assert_eq!(engine.exec("SELECT * FROM items;")?[0].rows.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_row_security() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+
+        engine.exec("CREATE TABLE items(tenant int, value int);")?;
+        engine.exec("INSERT INTO items(tenant, value) VALUES(1, 10), (1, 11), (2, 20);")?;
+        engine.exec("CREATE TABLE other(a int);")?;
+        engine.exec("INSERT INTO other(a) VALUES(1);")?;
+
+        engine.exec("CREATE POLICY tenant1 ON items TO alice USING (tenant = 1);")?;
+        engine.exec("CREATE POLICY tenant2 ON items TO bob USING (tenant = 2 AND value > 0);")?;
+        engine.exec("CREATE POLICY admin ON items TO carol, bob USING (value >= 20);")?;
+
+        let err = engine
+            .exec("CREATE POLICY tenant1 ON items USING (true);")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::DuplicatePolicy(_, _)
+        ));
+        let err = engine
+            .exec("CREATE POLICY p ON items USING (tenantt = 1);")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::ColumnNotFound(_, Some(_))
+        ));
+
+        // Sessions without a role are not subject to policies.
+        assert_eq!(engine.exec("SELECT * FROM items;")?[0].rows.len(), 3);
+
+        engine.exec("SET ROLE alice;")?;
+        assert_eq!(engine.current_role(), Some("alice"));
+        let result = &engine.exec("SELECT ctid, value FROM items;")?[0];
+        assert_eq!(result.rows, vec![vec!["(2,1)", "10"], vec!["(2,2)", "11"]]);
+        assert_eq!(engine.exec("SELECT * FROM other;")?[0].rows.len(), 1);
+
+        engine.exec("INSERT INTO items(tenant, value) VALUES(1, 12);")?;
+        let err = engine
+            .exec("INSERT INTO items(tenant, value) VALUES(1, 13), (2, 21);")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::PolicyViolation(_)
+        ));
+
+        engine.set_role(Some("dave"));
+        assert_eq!(engine.exec("SELECT * FROM items;")?[0].rows.len(), 0);
+
+        engine.set_role(Some("bob"));
+        assert_eq!(engine.exec("SELECT * FROM items;")?[0].rows.len(), 1);
+
+        engine.exec("RESET ROLE;")?;
+        assert_eq!(engine.exec("SELECT * FROM items;")?[0].rows.len(), 4);
+        assert_eq!(engine.exec("SELECT * FROM pg_policy;")?[0].rows.len(), 3);
+
+        engine.exec("DROP POLICY tenant1 ON items;")?;
+        engine.exec("DROP POLICY IF EXISTS tenant1 ON items;")?;
+        assert_eq!(
+            engine.take_notices(),
+            vec!["policy \"tenant1\" for table \"items\" does not exist, skipping"]
+        );
+        let err = engine.exec("DROP POLICY tenant1 ON items;").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::PolicyNotFound(_, _)
+        ));
+
+        engine.set_role(Some("alice"));
+        assert_eq!(engine.exec("SELECT * FROM items;")?[0].rows.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_do_block() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+        engine.exec("CREATE TABLE t(a int); CREATE TABLE squares(a int, square int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(1), (2), (3);")?;
+
+        let results = engine.exec_statements(
+            "DO $$
+             DECLARE
+                 total integer := 0;
+             BEGIN
+                 FOR r IN SELECT a FROM t LOOP
+                     IF r.a % 2 = 1 THEN
+                         INSERT INTO squares(a, square) VALUES(r.a, r.a * r.a);
+                         total := total + 1;
+                     END IF;
+                 END LOOP;
+                 RAISE NOTICE 'inserted % rows', total;
+             END
+             $$;",
+        )?;
+        assert_eq!(results[0].tag.to_string(), "DO");
+        assert_eq!(engine.take_notices(), vec!["inserted 2 rows"]);
+
+        let result = &engine.exec("SELECT * FROM squares;")?[0];
+        assert_eq!(result.rows, vec![vec!["1", "1"], vec!["3", "9"]]);
+
+        let err = engine
+            .exec("DO $$ BEGIN IF (SELECT 1) THEN NULL; END IF; END $$")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::UnsupportedExpression(_)
+        ));
+        let err = engine
+            .exec("DO $$ BEGIN RAISE EXCEPTION 'stop'; INSERT INTO t(a) VALUES(4); END $$")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "stop");
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_procedures() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+        engine.exec("CREATE TABLE t(a int);")?;
+
+        let results = engine.exec_statements(
+            "CREATE PROCEDURE fill(n integer, twice boolean) AS $$
+             BEGIN
+                 FOR r IN SELECT * FROM generate_series(1, n) LOOP
+                     INSERT INTO t(a) VALUES(r.generate_series);
+                     IF twice THEN
+                         INSERT INTO t(a) VALUES(r.generate_series);
+                     END IF;
+                 END LOOP;
+                 COMMIT;
+             END
+             $$;",
+        )?;
+        assert_eq!(results[0].tag.to_string(), "CREATE PROCEDURE");
+
+        let results = engine.exec_statements("CALL fill(2, false);")?;
+        assert_eq!(results[0].tag.to_string(), "CALL");
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 2);
+        engine.exec("CALL fill(1 + 1, true);")?;
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 6);
+
+        let err = engine.exec("CALL fill(1);").unwrap_err();
+        assert_eq!(err.to_string(), "procedure fill(integer) does not exist");
+        let err = engine
+            .exec("CREATE PROCEDURE fill(n int, twice bool) AS $$ BEGIN NULL; END $$")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::DuplicateFunction(_)
+        ));
+        let err = engine
+            .exec("CREATE PROCEDURE p(s text) AS $$ BEGIN NULL; END $$")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::UnsupportedParameterType(_)
+        ));
+
+        engine.exec(
+            "CREATE OR REPLACE PROCEDURE fill(n int, twice bool) AS $$
+             BEGIN RAISE NOTICE 'n = %', n; ROLLBACK; END $$",
+        )?;
+        let err = engine.exec("CALL fill(3, NULL);").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::RollbackNotSupported
+        ));
+        assert_eq!(engine.take_notices(), vec!["n = 3"]);
+
+        engine.exec("CREATE PROCEDURE forever() AS $$ BEGIN CALL forever(); END $$")?;
+        let err = engine.exec("CALL forever();").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::StackDepthExceeded(_)
+        ));
+
+        let results = engine.exec_statements("DROP PROCEDURE fill(integer, boolean);")?;
+        assert_eq!(results[0].tag.to_string(), "DROP PROCEDURE");
+        engine.exec("DROP PROCEDURE IF EXISTS fill;")?;
+        assert_eq!(
+            engine.take_notices(),
+            vec!["procedure fill does not exist, skipping"]
+        );
+        assert!(engine.exec("CALL fill(1, true);").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_log_min_duration() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_log_min_duration";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+        engine.set_log_min_duration(Some(Duration::ZERO));
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(87);")?;
+
+        // Errors are still returned after being logged.
+        assert!(engine.exec("SELECT * FROM missing;").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_pg_stat_user_tables() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_pg_stat_user_tables";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(87);")?;
+        engine.exec("INSERT INTO t(a) VALUES(88);")?;
+        engine.exec("SELECT * FROM t;")?;
+        engine.exec("SELECT * FROM pg_stat_user_tables;")?;
+
+        let stats = engine.relation_stats("t")?;
+        assert_eq!(stats.seq_scan, 1);
+        assert_eq!(stats.n_tup_ins, 2);
+
+        let result = &engine.exec("SELECT * FROM pg_stat_user_tables;")?[0];
+        assert_eq!(result.rows.len(), 1);
+        assert_eq!(result.rows[0][1], "t");
+        assert_ne!(result.rows[0][7], "0", "expected buffer hits on t");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_generate_series() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_generate_series";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+
+        let results = engine.exec("SELECT * FROM generate_series(1, 10, 3);")?;
+        assert_eq!(
+            results[0].rows,
+            vec![vec!["1"], vec!["4"], vec!["7"], vec!["10"]]
+        );
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        // Insert enough rows to fill more than a single heap page.
+        engine.exec("INSERT INTO t(a) SELECT * FROM generate_series(1, 5000);")?;
+        engine.exec("INSERT INTO t(a) VALUES(5001), (5002);")?;
+
+        let results = engine.exec("SELECT * FROM t;")?;
+        assert_eq!(results[0].rows.len(), 5002);
+        assert_eq!(results[0].rows[5001], vec!["5002"]);
+        assert_eq!(engine.relation_stats("t")?.n_tup_ins, 5002);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_function_scan() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_function_scan";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+
+        let results = engine.exec("SELECT * FROM generate_series(3, 1, -1) AS g;")?;
+        assert_eq!(results[0].column_names(), vec!["g"]);
+        assert_eq!(results[0].rows, vec![vec!["3"], vec!["2"], vec!["1"]]);
+
+        let results = engine.exec("SELECT * FROM unnest(ARRAY[1, 2], ARRAY['a']) AS u(n, s);")?;
+        assert_eq!(results[0].column_names(), vec!["n", "s"]);
+        assert_eq!(results[0].rows, vec![vec!["1", "a"], vec!["2", "NULL"]]);
+
+        assert!(engine.exec("SELECT * FROM unnest(1);").is_err());
+        assert!(engine.exec("SELECT * FROM missing_function(1);").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_function_overloads() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_function_overloads";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+
+        let results = engine.exec("SELECT * FROM pg_proc;")?;
+        assert_eq!(results[0].rows.len(), PgProc::builtin_functions().len());
+
+        // The int8 overload is chosen when an argument does not fit on int4.
+        let results = engine.exec("SELECT * FROM generate_series(4294967296, 4294967297);")?;
+        assert_eq!(
+            results[0].rows,
+            vec![vec!["4294967296"], vec!["4294967297"]]
+        );
+
+        let results = engine.exec("SELECT * FROM generate_series('1'::int, 2);")?;
+        assert_eq!(results[0].rows, vec![vec!["1"], vec!["2"]]);
+        let results = engine.exec("SELECT * FROM generate_series(CAST('3' AS bigint), 2, -1);")?;
+        assert_eq!(results[0].rows, vec![vec!["3"], vec!["2"]]);
+
+        let results = engine.exec("SELECT * FROM unnest(ARRAY[1, '2', NULL]);")?;
+        assert_eq!(results[0].rows, vec![vec!["1"], vec!["2"], vec!["NULL"]]);
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(1);")?;
+        let results = engine.exec("SELECT * FROM page_header('t', '2');")?;
+        assert_eq!(results[0].rows.len(), 1);
+
+        let err = engine
+            .exec("SELECT * FROM generate_series('1', '2');")
+            .unwrap_err();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(diagnostic.position, Some(15));
+        assert!(diagnostic.hint.is_some());
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::AmbiguousFunction(_)
+        ));
+
+        let err = engine
+            .exec("SELECT * FROM generate_series(1, true);")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "function generate_series(integer, boolean) does not exist"
+        );
+
+        let err = engine
+            .exec("SELECT * FROM generate_series(4294967296::int, 2);")
+            .unwrap_err();
+        assert!(matches!(err.downcast::<Error>()?, Error::IntegerOutOfRange));
+        let err = engine
+            .exec("SELECT * FROM unnest(ARRAY[1, true]);")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::ArrayTypeMismatch(_, _)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_select_ctid() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_select_ctid";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+
+        engine.exec("CREATE TABLE t(a int, b int);")?;
+        engine.exec("INSERT INTO t(a, b) VALUES(1, 2), (3, 4);")?;
+
+        let results = engine.exec("SELECT ctid, b AS c, * FROM t;")?;
+        assert_eq!(results[0].column_names(), vec!["ctid", "c", "a", "b"]);
+        let relid = engine.open_relation("t")?.0.oid;
+        assert_eq!(
+            results[0].columns[0],
+            Column::new("ctid", TEXT_OID).not_null()
+        );
+        assert_eq!(
+            results[0].columns[1],
+            Column::new("c", INT4_OID).with_source(relid, 1)
+        );
+        assert_eq!(
+            results[0].rows,
+            vec![vec!["(2,1)", "2", "1", "2"], vec!["(2,2)", "4", "3", "4"]]
+        );
+
+        assert!(engine.exec("SELECT missing FROM t;").is_err());
+        assert!(engine
+            .exec("SELECT ctid FROM generate_series(1, 2);")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_diagnostic() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+        engine.exec("CREATE TABLE t(name int, age int);")?;
+
+        let sql = "SELECT * FROM t; SELECT nme FROM t;";
+        let err = engine.exec(sql).unwrap_err();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(diagnostic.position, Some(25));
+        assert_eq!(
+            diagnostic.render(sql),
+            "ERROR:  column \"nme\" does not exist\n\
+             LINE 1: SELECT * FROM t; SELECT nme FROM t;\n\
+             \x20                               ^\n\
+             HINT:  Perhaps you meant to reference the column \"name\"."
+        );
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::ColumnNotFound(_, Some(_))
+        ));
+
+        let err = engine.exec("SELECT * FROM tt;").unwrap_err();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(diagnostic.position, Some(15));
+        assert_eq!(
+            diagnostic.hint.as_deref(),
+            Some("Perhaps you meant to reference the relation \"t\".")
+        );
+
+        let err = engine
+            .exec("INSERT INTO t(name, age) VALUES(1, 'abc');")
+            .unwrap_err();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(
+            diagnostic.message,
+            "invalid input syntax for type integer: 'abc'"
+        );
+        assert_eq!(diagnostic.position, Some(36));
+        assert_eq!(diagnostic.hint, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_vacuum() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_vacuum";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(1), (2);")?;
+
+        // Add some empty pages at the end of relation, like the ones left by an insert that
+        // allocated a new page but failed before adding its tuple.
+        let (_, rel) = engine.open_relation("t")?;
+        for _ in 0..3 {
+            rel.borrow_mut().pager.allocate_page()?;
+        }
+        assert_eq!(rel.borrow().pager.size()?, 5);
+
+        engine.vacuum("t")?;
+        assert_eq!(rel.borrow().pager.size()?, 2);
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_reloptions() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+
+        engine
+            .exec("CREATE TABLE full(a int); CREATE TABLE half(a int) WITH (fillfactor = 50);")?;
+        for rel_name in ["full", "half"] {
+            engine.exec(&format!(
+                "INSERT INTO {}(a) SELECT * FROM generate_series(1, 2000);",
+                rel_name
+            ))?;
+        }
+        let pages = |engine: &mut Engine, rel_name| -> Result<PageNumber> {
+            Ok(engine.open_relation(rel_name)?.1.borrow().pager.size()?)
+        };
+        let full_pages = pages(&mut engine, "full")?;
+        assert!(pages(&mut engine, "half")? >= 2 * (full_pages - 1));
+
+        let reloptions = |engine: &mut Engine| -> Result<Vec<Vec<String>>> {
+            let result = &engine.exec("SELECT relname, reloptions FROM pg_class;")?[0];
+            Ok(result.rows.clone())
+        };
+        assert_eq!(
+            reloptions(&mut engine)?,
+            vec![vec!["full", "NULL"], vec!["half", "{fillfactor=50}"]]
+        );
+
+        // Changed options only apply to new pages.
+        let results = engine.exec_statements("ALTER TABLE full SET (fillfactor = '70');")?;
+        assert_eq!(results[0].tag.to_string(), "ALTER TABLE");
+        assert_eq!(
+            engine.open_relation("full")?.1.borrow().options.fillfactor,
+            70
+        );
+        assert_eq!(pages(&mut engine, "full")?, full_pages);
+        engine.exec("ALTER TABLE half RESET (fillfactor);")?;
+        assert_eq!(
+            reloptions(&mut engine)?,
+            vec![vec!["full", "{fillfactor=70}"], vec!["half", "NULL"]]
+        );
+        assert_eq!(engine.exec("SELECT * FROM half;")?[0].rows.len(), 2000);
+
+        for sql in [
+            "CREATE TABLE t(a int) WITH (fillfactor = 5);",
+            "CREATE TABLE t(a int) WITH (autovacuum_enabled = false);",
+            "ALTER TABLE full SET (fillfactor = 101);",
+        ] {
+            let err = engine.exec(sql).unwrap_err();
+            assert!(err.downcast::<reloptions::Error>().is_ok());
+        }
+        assert!(engine.exec("SELECT * FROM t;").is_err());
+
+        let err = engine
+            .exec("ALTER TABLE pg_class SET (fillfactor = 50);")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<catalog::Error>()?,
+            catalog::Error::SystemCatalog(_)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_scan_progress() -> Result<()> {
+        use crate::access::progress::{self, Progress, ProgressCommand};
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let db_data = tempdir()?;
+        let db_name = "test_engine_scan_progress";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(1), (2);")?;
+        let relid = engine.open_relation("t")?.0.oid;
+
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let handler_reports = reports.clone();
+        // Catalog scans report their progress too, only the reports of t are checked.
+        progress::set_handler(Some(Box::new(move |progress: &Progress| {
+            if progress.rel_name != "t" {
+                return Ok(());
+            }
+            handler_reports.borrow_mut().push(progress::active());
+            if progress.command == ProgressCommand::Scan {
+                bail!("canceling statement due to user request");
+            }
+            Ok(())
+        })));
+
+        // The interrupted scan must not leak the pin of the page being scanned.
+        assert!(engine.exec("SELECT * FROM t;").is_err());
+        assert!(engine.buffer_pool.pinned_buffers().is_empty());
+        assert_eq!(
+            reports.borrow().as_slice(),
+            &[vec![Progress {
+                command: ProgressCommand::Scan,
+                relid,
+                rel_name: String::from("t"),
+                pages_done: 1,
+                pages_total: 1,
+            }]]
+        );
+
+        engine.vacuum("t")?;
+        assert_eq!(reports.borrow().len(), 2);
+        assert_eq!(reports.borrow()[1][0].command, ProgressCommand::Vacuum);
+
+        progress::set_handler(None);
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 2);
+        let result = &engine.exec("SELECT * FROM pg_stat_progress;")?[0];
+        assert_eq!(result.columns.len(), 5);
+        assert!(result.rows.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_work_mem() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_work_mem";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+        assert_eq!(engine.memory().work_mem(), DEFAULT_WORK_MEM);
+
+        let result = engine.exec_statements("SET work_mem = '64MB';")?;
+        assert_eq!(result[0].tag.to_string(), "SET");
+        assert_eq!(engine.memory().work_mem(), 64 * 1024 * 1024);
+
+        let err = engine.exec("SET work_mem TO '1kB';").unwrap_err();
+        assert!(matches!(err.downcast::<Error>()?, Error::InvalidWorkMem(_)));
+        assert_eq!(engine.memory().work_mem(), 64 * 1024 * 1024);
+
+        engine.exec("RESET work_mem;")?;
+        assert_eq!(engine.memory().work_mem(), DEFAULT_WORK_MEM);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_settings() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+        engine.exec("SET work_mem = '64MB'; SET ROLE alice;")?;
+        engine.set_log_min_duration(Some(Duration::from_millis(250)));
+
+        let results = engine.exec_statements("SHOW work_mem; SHOW Role; SHOW ALL;")?;
+        assert_eq!(results[0].tag.to_string(), "SHOW");
+        assert_eq!(results[0].results[0].column_names(), vec!["work_mem"]);
+        assert_eq!(results[0].results[0].rows, vec![vec!["64MB"]]);
+        assert_eq!(results[1].results[0].rows, vec![vec!["alice"]]);
+        let all = &results[2].results[0];
+        assert_eq!(all.column_names(), vec!["name", "setting", "description"]);
+        assert_eq!(all.rows.len(), engine.settings().len());
+        assert!(all.rows.contains(&vec![
+            String::from("log_min_duration_statement"),
+            String::from("250ms"),
+            String::from("Sets the minimum execution time above which statements will be logged.")
+        ]));
+
+        let result = &engine.exec("SELECT name, setting, unit, boot_val FROM pg_settings;")?[0];
+        assert!(result.rows.contains(&vec![
+            String::from("work_mem"),
+            String::from("65536"),
+            String::from("kB"),
+            String::from("4096")
+        ]));
+        assert!(result.rows.contains(&vec![
+            String::from("default_table_access_method"),
+            String::from("heap"),
+            String::from("NULL"),
+            String::from("heap")
+        ]));
+
+        let err = engine.exec("SHOW fsync;").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::UnrecognizedParameter(_)
+        ));
+
+        // Settings can be read by read-only engines.
+        engine.set_read_only(true);
+        assert_eq!(
+            engine.exec("SHOW transaction_read_only;")?[0].rows,
+            vec![vec!["on"]]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_virtual_tables() -> Result<()> {
+        struct Tenants;
+
+        impl VirtualTable for Tenants {
+            fn name(&self) -> &str {
+                "tenants"
+            }
+
+            fn schema(&self) -> &str {
+                "app"
+            }
+
+            fn columns(&self) -> Vec<Column> {
+                vec![Column::new("name", TEXT_OID).not_null()]
+            }
+
+            fn scan(&self, _ctx: &mut vtab::ScanContext) -> Result<vtab::Rows> {
+                let rows = vec![vec![String::from("acme")], vec![String::from("globex")]];
+                Ok(Box::new(rows.into_iter()))
+            }
+        }
+
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database("db")?;
+        engine.exec("CREATE TABLE t(a int, b int); CREATE TABLE tables(c int);")?;
+
+        let result =
+            &engine.exec("SELECT table_name, table_type FROM information_schema.tables;")?[0];
+        assert_eq!(
+            result.rows,
+            vec![vec!["t", "BASE TABLE"], vec!["tables", "BASE TABLE"]]
+        );
+        let result = &engine.exec(
+            "SELECT table_name, column_name, ordinal_position, data_type FROM information_schema.columns;",
+        )?[0];
+        assert_eq!(
+            result.rows,
+            vec![
+                vec!["t", "a", "1", "integer"],
+                vec!["t", "b", "2", "integer"],
+                vec!["tables", "c", "1", "integer"]
+            ]
+        );
+        assert_eq!(result.columns[2].type_oid, INT4_OID);
+
+        // Only pg_catalog is searched for unqualified names, so user relations are not shadowed
+        // by tables of other schemas.
+        assert!(engine.exec("SELECT * FROM tables;")?[0].rows.is_empty());
+        engine.exec("SELECT * FROM pg_catalog.pg_settings;")?;
+
+        engine.register_virtual_table(Box::new(Tenants))?;
+        assert_eq!(
+            engine.exec("SELECT * FROM app.tenants;")?[0].rows,
+            vec![vec!["acme"], vec!["globex"]]
+        );
+        assert!(engine.exec("SELECT * FROM tenants;").is_err());
+        let err = engine
+            .register_virtual_table(Box::new(Tenants))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::DuplicateRelation(_)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_result_cache() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_result_cache";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+        assert_eq!(engine.result_cache_stats(), None);
+        engine.set_result_cache(Some(8));
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(1), (2);")?;
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 2);
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 2);
+        assert_eq!(engine.relation_stats("t")?.seq_scan, 1);
+
+        // Inserts invalidate the results of the relation.
+        engine.exec("INSERT INTO t(a) VALUES(3);")?;
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 3);
+        assert_eq!(engine.relation_stats("t")?.seq_scan, 2);
+
+        // Roles have their own results, which are invalidated by new policies.
+        engine.exec("SET ROLE alice;")?;
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 3);
+        engine.exec("CREATE POLICY p ON t TO alice USING (a = 1);")?;
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 1);
+        engine.exec("RESET ROLE;")?;
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 3);
+
+        // Virtual tables and functions are never cached.
+        engine.exec("SELECT * FROM pg_stat_user_tables;")?;
+        engine.exec("SELECT * FROM generate_series(1, 3);")?;
+
+        assert_eq!(
+            engine.result_cache_stats(),
+            Some(ResultCacheStats {
+                hits: 1,
+                misses: 5,
+                entries: 2,
+            })
+        );
+
+        engine.set_result_cache(None);
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 3);
+        assert_eq!(engine.relation_stats("t")?.seq_scan, 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_audit() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_audit";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+        let db_data = db_data.path().to_string_lossy().to_string();
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data)?;
+        engine.use_database(db_name)?;
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        assert!(engine.exec("SELECT * FROM pg_audit;")?[0].rows.is_empty());
+
+        engine.set_audit(true);
+        engine.exec("INSERT INTO t(a) VALUES(1), (2);")?;
+        engine.exec("SELECT * FROM t; SHOW audit;")?;
+        engine.exec("SET ROLE alice;")?;
+        assert!(engine.exec("INSERT INTO missing(a) VALUES(3);").is_err());
+        engine.exec("CREATE TABLE u(a int);")?;
+
+        let result =
+            &engine.exec("SELECT audrole, audcommand, audstatement, audrows FROM pg_audit;")?[0];
+        assert_eq!(
+            result.rows,
+            vec![
+                vec!["NULL", "INSERT", "INSERT INTO t (a) VALUES (1), (2)", "2"],
+                vec!["alice", "CREATE TABLE", "CREATE TABLE u (a INT)", "NULL"],
+            ]
+        );
+
+        // Statements that could not be recorded still succeed, since their changes were made.
+        let db_oid = engine.database()?.db_oid();
+        let path = rel::relation_path(&db_data, db_oid, catalog::pg_audit::RELATION_OID);
+        rel::evict_database(&db_data, db_oid);
+        fs::remove_file(&path)?;
+        fs::create_dir(&path)?;
+        let results = engine.exec_statements("INSERT INTO t(a) VALUES(3);")?;
+        assert_eq!(results[0].tag.to_string(), "INSERT 0 1");
+        assert_eq!(engine.exec("SELECT * FROM t;")?[0].rows.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_temp_files() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_data = db_data.path().to_string_lossy().to_string();
+        init_database(&Path::new(&db_data).to_path_buf(), "test_engine_temp_files")?;
+
+        // Files left by a crashed process are removed on startup. Only Linux can tell that the
+        // process is not running, so other platforms keep them.
+        let leftover = temp::temp_dir(&db_data).join("pgsql_tmp0.0");
+        fs::create_dir_all(temp::temp_dir(&db_data))?;
+        fs::write(&leftover, b"")?;
+
+        let mut engine = Engine::new(BufferPool::new(120), &db_data)?;
+        assert_eq!(leftover.exists(), !cfg!(target_os = "linux"));
+        if leftover.exists() {
+            fs::remove_file(&leftover)?;
+        }
+
+        engine.set_work_mem(workmem::MIN_WORK_MEM);
+        let mut buffer = engine.spill_buffer::<Vec<i32>>();
+        for i in 0..10_000 {
+            buffer.push(vec![i; 4])?;
+        }
+        assert!(buffer.is_spilled());
+        assert_eq!(fs::read_dir(temp::temp_dir(&db_data))?.count(), 1);
+
+        let mut sum = 0;
+        buffer.drain(|row| {
+            sum += row[0] as i64;
+            Ok(())
+        })?;
+        assert_eq!(sum, 49_995_000);
+        assert_eq!(fs::read_dir(temp::temp_dir(&db_data))?.count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_engine_page_inspect() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_name = "test_engine_page_inspect";
+
+        init_database(&db_data.path().to_path_buf(), db_name)?;
+
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, &db_data.path().to_string_lossy().to_string())?;
+        engine.use_database(db_name)?;
+
+        engine.exec("CREATE TABLE t(a int);")?;
+        engine.exec("INSERT INTO t(a) VALUES(87);")?;
+        engine.exec("SELECT * FROM page_header('t', 2);")?;
+        engine.exec("SELECT * FROM heap_page_items('t', 2);")?;
+
+        // The first page is the relation metapage, so tuples start at the second page.
+        let (_, rel) = engine.open_relation("t")?;
+        let result = pageinspect::heap_page_items(&mut engine.buffer_pool, &rel, 2)?;
+        assert_eq!(result.rows, vec![vec!["1", "8188", "4", "57000000"]]);
+
+        let err = engine.exec("SELECT * FROM page_header(1);").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::FunctionNotFound(_)
+        ));
+
+        let err = engine.exec("SELECT * FROM missing('t', 1);").unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>()?,
+            Error::FunctionNotFound(_)
+        ));
 
         Ok(())
     }
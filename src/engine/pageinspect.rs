@@ -0,0 +1,101 @@
+use anyhow::Result;
+
+use crate::storage::bufpage::{ItemId, PageHeader, ITEM_ID_SIZE, PAGE_HEADER_SIZE};
+use crate::storage::pager::{PageNumber, PAGE_SIZE};
+use crate::storage::rel::Relation;
+use crate::storage::BufferPool;
+
+use crate::catalog::pg_type::{INT4_OID, TEXT_OID};
+
+use super::result::{Column, QueryResult};
+
+/// Return a single row describing the header of the given relation page.
+///
+/// The page is read through the buffer pool, so changes not flushed to disk yet are visible.
+pub fn page_header(
+    buffer_pool: &mut BufferPool,
+    rel: &Relation,
+    page_num: PageNumber,
+) -> Result<QueryResult> {
+    let page = get_raw_page(buffer_pool, rel, page_num)?;
+    let header = bincode::deserialize::<PageHeader>(&page[..PAGE_HEADER_SIZE])?;
+
+    let columns = vec![
+        Column::new("start_free_space", INT4_OID).not_null(),
+        Column::new("end_free_space", INT4_OID).not_null(),
+        Column::new("pagesize", INT4_OID).not_null(),
+        Column::new("free_space", INT4_OID).not_null(),
+    ];
+    let rows = vec![vec![
+        header.start_free_space.to_string(),
+        header.end_free_space.to_string(),
+        PAGE_SIZE.to_string(),
+        header
+            .end_free_space
+            .saturating_sub(header.start_free_space)
+            .to_string(),
+    ]];
+
+    Ok(QueryResult { columns, rows })
+}
+
+/// Return one row for each line pointer of the given relation page, with the raw tuple data
+/// encoded as hex.
+pub fn heap_page_items(
+    buffer_pool: &mut BufferPool,
+    rel: &Relation,
+    page_num: PageNumber,
+) -> Result<QueryResult> {
+    let page = get_raw_page(buffer_pool, rel, page_num)?;
+    let header = bincode::deserialize::<PageHeader>(&page[..PAGE_HEADER_SIZE])?;
+
+    let columns = vec![
+        Column::new("lp", INT4_OID).not_null(),
+        Column::new("lp_off", INT4_OID).not_null(),
+        Column::new("lp_len", INT4_OID).not_null(),
+        Column::new("t_data", TEXT_OID),
+    ];
+    let mut rows = Vec::new();
+
+    let start = (header.start_free_space as usize).clamp(PAGE_HEADER_SIZE, PAGE_SIZE);
+    for (i, data) in page[PAGE_HEADER_SIZE..start]
+        .chunks_exact(ITEM_ID_SIZE)
+        .enumerate()
+    {
+        let item_id = bincode::deserialize::<ItemId>(data)?;
+        let offset = item_id.offset as usize;
+        let length = item_id.length as usize;
+
+        // Line pointers pointing outside of the page are still listed, but without data, since
+        // inspecting broken pages is the main use of these functions.
+        let t_data = if offset + length <= PAGE_SIZE {
+            page[offset..offset + length]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect()
+        } else {
+            String::from("NULL")
+        };
+
+        rows.push(vec![
+            (i + 1).to_string(),
+            offset.to_string(),
+            length.to_string(),
+            t_data,
+        ]);
+    }
+
+    Ok(QueryResult { columns, rows })
+}
+
+/// Return a copy of the given relation page.
+fn get_raw_page(
+    buffer_pool: &mut BufferPool,
+    rel: &Relation,
+    page_num: PageNumber,
+) -> Result<[u8; PAGE_SIZE]> {
+    let buffer = buffer_pool.fetch_buffer(rel, page_num)?;
+    let page = buffer_pool.get_page(&buffer).borrow().bytes();
+    buffer_pool.unpin_buffer(buffer, false)?;
+    Ok(page)
+}
@@ -0,0 +1,866 @@
+use std::fmt;
+
+use anyhow::Result;
+use sqlparser::ast::{self, Ident, ObjectName};
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::{Token, Tokenizer};
+
+use super::diagnostic::{find_token, Diagnostic};
+use super::DIALECT;
+
+/// A statement to execute. Most statements are parsed by sqlparser, the others are statements
+/// that sqlparser does not support yet.
+#[derive(Debug)]
+pub enum Statement {
+    /// A statement parsed by sqlparser.
+    Sql(Box<ast::Statement>),
+
+    /// DROP DATABASE [ IF EXISTS ] name
+    DropDatabase { if_exists: bool, name: ObjectName },
+
+    /// CREATE POLICY name ON table [ FOR ALL ] [ TO role [, ...] ] USING ( expr )
+    CreatePolicy {
+        name: Ident,
+        table: ObjectName,
+        /// Roles that the policy applies to. Empty means PUBLIC.
+        roles: Vec<Ident>,
+        using: ast::Expr,
+    },
+
+    /// DROP POLICY [ IF EXISTS ] name ON table
+    DropPolicy {
+        if_exists: bool,
+        name: Ident,
+        table: ObjectName,
+    },
+
+    /// SET ROLE { role | NONE } and RESET ROLE. None is the session user.
+    SetRole { role: Option<Ident> },
+
+    /// SET work_mem { TO | = } value and RESET work_mem. None is the default value.
+    SetWorkMem { value: Option<String> },
+
+    /// DO [ LANGUAGE plpgsql ] $$ body $$, an anonymous block of procedural code.
+    Do { body: String },
+
+    /// CREATE [ OR REPLACE ] PROCEDURE name ( [ param type [, ...] ] ) [ LANGUAGE plpgsql ]
+    /// AS $$ body $$
+    CreateProcedure {
+        or_replace: bool,
+        name: ObjectName,
+        params: Vec<(Ident, ast::DataType)>,
+        body: String,
+    },
+
+    /// DROP PROCEDURE [ IF EXISTS ] name [ ( [ type [, ...] ] ) ]
+    DropProcedure {
+        if_exists: bool,
+        name: ObjectName,
+        /// Types of the parameters of the procedure to drop, needed if it has overloads.
+        arg_types: Option<Vec<ast::DataType>>,
+    },
+
+    /// CALL name ( [ expr [, ...] ] )
+    Call {
+        name: ObjectName,
+        args: Vec<ast::Expr>,
+    },
+
+    /// ALTER TABLE name SET ( option = value [, ...] ) and ALTER TABLE name RESET ( option [, ...] ).
+    /// Options without value are reset to their default.
+    AlterTableOptions {
+        table: ObjectName,
+        options: Vec<(Ident, Option<ast::Value>)>,
+    },
+}
+
+impl Statement {
+    /// Return true if the statement doesn't modify the database, like queries or changes of the
+    /// session state. SELECT INTO creates a new table, so it is not read-only.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Statement::Sql(stmt) => match stmt.as_ref() {
+                ast::Statement::Query(query) => select_into(query).is_none(),
+                ast::Statement::ShowVariable { .. } => true,
+                _ => false,
+            },
+            // Statements executed by DO blocks and procedures are checked one by one.
+            Statement::SetRole { .. }
+            | Statement::SetWorkMem { .. }
+            | Statement::Do { .. }
+            | Statement::Call { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Sql(stmt) => write!(f, "{}", stmt),
+            Statement::DropDatabase { if_exists, name } => write!(
+                f,
+                "DROP DATABASE {}{}",
+                if *if_exists { "IF EXISTS " } else { "" },
+                name
+            ),
+            Statement::CreatePolicy {
+                name,
+                table,
+                roles,
+                using,
+            } => {
+                write!(f, "CREATE POLICY {} ON {}", name, table)?;
+                if !roles.is_empty() {
+                    let roles: Vec<String> = roles.iter().map(ToString::to_string).collect();
+                    write!(f, " TO {}", roles.join(", "))?;
+                }
+                write!(f, " USING ({})", using)
+            }
+            Statement::DropPolicy {
+                if_exists,
+                name,
+                table,
+            } => write!(
+                f,
+                "DROP POLICY {}{} ON {}",
+                if *if_exists { "IF EXISTS " } else { "" },
+                name,
+                table
+            ),
+            Statement::SetRole { role: Some(role) } => write!(f, "SET ROLE {}", role),
+            Statement::SetRole { role: None } => write!(f, "RESET ROLE"),
+            Statement::SetWorkMem { value: Some(value) } => {
+                write!(f, "SET work_mem = '{}'", value)
+            }
+            Statement::SetWorkMem { value: None } => write!(f, "RESET work_mem"),
+            Statement::Do { body } => write!(f, "DO {}", DollarQuoted(body)),
+            Statement::CreateProcedure {
+                or_replace,
+                name,
+                params,
+                body,
+            } => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|(name, typ)| format!("{} {}", name, typ))
+                    .collect();
+                write!(
+                    f,
+                    "CREATE {}PROCEDURE {}({}) AS {}",
+                    if *or_replace { "OR REPLACE " } else { "" },
+                    name,
+                    params.join(", "),
+                    DollarQuoted(body)
+                )
+            }
+            Statement::DropProcedure {
+                if_exists,
+                name,
+                arg_types,
+            } => {
+                write!(
+                    f,
+                    "DROP PROCEDURE {}{}",
+                    if *if_exists { "IF EXISTS " } else { "" },
+                    name
+                )?;
+                if let Some(arg_types) = arg_types {
+                    let arg_types: Vec<String> =
+                        arg_types.iter().map(ToString::to_string).collect();
+                    write!(f, "({})", arg_types.join(", "))?;
+                }
+                Ok(())
+            }
+            Statement::Call { name, args } => {
+                let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+                write!(f, "CALL {}({})", name, args.join(", "))
+            }
+            Statement::AlterTableOptions { table, options } => {
+                let set: Vec<String> = options
+                    .iter()
+                    .filter_map(|(name, value)| Some(format!("{} = {}", name, value.as_ref()?)))
+                    .collect();
+                let reset: Vec<String> = options
+                    .iter()
+                    .filter(|(_, value)| value.is_none())
+                    .map(|(name, _)| name.to_string())
+                    .collect();
+                write!(f, "ALTER TABLE {}", table)?;
+                if !set.is_empty() {
+                    write!(f, " SET ({})", set.join(", "))?;
+                }
+                if !reset.is_empty() {
+                    write!(f, " RESET ({})", reset.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Display a text as a dollar-quoted string, choosing a delimiter that is not part of the text.
+struct DollarQuoted<'a>(&'a str);
+
+impl fmt::Display for DollarQuoted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let delimiter = if self.0.contains("$$") {
+            "$body$"
+        } else {
+            "$$"
+        };
+        write!(f, "{}{}{}", delimiter, self.0, delimiter)
+    }
+}
+
+/// A statement parsed from a SQL text that can have multiple statements.
+#[derive(Debug)]
+pub struct ParsedStatement<'a> {
+    /// Byte offset of the statement on the SQL text.
+    pub offset: usize,
+
+    /// Text of the statement, without the semicolon that ends it.
+    pub text: &'a str,
+
+    pub stmt: Statement,
+}
+
+/// Parse the given SQL statements separated by semicolons.
+///
+/// Return error with a [Diagnostic] locating the offending token if any statement is invalid.
+pub fn parse_sql(sql: &str) -> Result<Vec<ParsedStatement<'_>>> {
+    let mut stmts = Vec::new();
+    for (offset, text) in split_statements(sql) {
+        // Ignore empty statements, e.g between successive statement delimiters.
+        stmts.extend(parse_statement_at(sql, offset, text)?);
+    }
+
+    Ok(stmts)
+}
+
+/// Parse a single statement returned by [split_statements], returning None if it is empty.
+pub fn parse_statement_at<'a>(
+    sql: &str,
+    offset: usize,
+    text: &'a str,
+) -> Result<Option<ParsedStatement<'a>>> {
+    let stmt = parse_statement(text).map_err(|err| syntax_error(err, sql, offset, text))?;
+    Ok(stmt.map(|stmt| ParsedStatement { offset, text, stmt }))
+}
+
+/// Parse a single statement, returning None if it has only whitespaces and comments.
+fn parse_statement(text: &str) -> Result<Option<Statement>, ParserError> {
+    if let Some(stmt) = parse_do(text)? {
+        return Ok(Some(stmt));
+    }
+    if let Some(stmt) = parse_create_procedure(text)? {
+        return Ok(Some(stmt));
+    }
+
+    let tokens = Tokenizer::new(&DIALECT, text).tokenize()?;
+    if tokens
+        .iter()
+        .all(|token| matches!(token, Token::Whitespace(_)))
+    {
+        return Ok(None);
+    }
+
+    let mut parser = Parser::new(tokens, &DIALECT);
+    let stmt = match parse_extension(&mut parser)? {
+        Some(stmt) => stmt,
+        None => Statement::Sql(Box::new(parser.parse_statement()?)),
+    };
+
+    if parser.peek_token() != Token::EOF {
+        return parser.expected("end of statement", parser.peek_token());
+    }
+    Ok(Some(stmt))
+}
+
+/// Parse the statements that sqlparser does not support, returning None if the next statement is
+/// not one of them.
+fn parse_extension(parser: &mut Parser) -> Result<Option<Statement>, ParserError> {
+    if parser.parse_keywords(&[Keyword::DROP, Keyword::DATABASE]) {
+        return Ok(Some(Statement::DropDatabase {
+            if_exists: parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]),
+            name: parser.parse_object_name()?,
+        }));
+    }
+
+    if parser.parse_keyword(Keyword::CREATE) {
+        if parse_word(parser, "POLICY") {
+            return parse_create_policy(parser).map(Some);
+        }
+        parser.prev_token();
+    }
+
+    if parser.parse_keyword(Keyword::DROP) {
+        if parse_word(parser, "PROCEDURE") {
+            let if_exists = parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
+            let name = parser.parse_object_name()?;
+            let mut arg_types = None;
+            if parser.consume_token(&Token::LParen) {
+                let mut types = Vec::new();
+                if !parser.consume_token(&Token::RParen) {
+                    types = parser.parse_comma_separated(Parser::parse_data_type)?;
+                    parser.expect_token(&Token::RParen)?;
+                }
+                arg_types = Some(types);
+            }
+            return Ok(Some(Statement::DropProcedure {
+                if_exists,
+                name,
+                arg_types,
+            }));
+        }
+        if parse_word(parser, "POLICY") {
+            let if_exists = parser.parse_keywords(&[Keyword::IF, Keyword::EXISTS]);
+            let name = parser.parse_identifier()?;
+            parser.expect_keyword(Keyword::ON)?;
+            return Ok(Some(Statement::DropPolicy {
+                if_exists,
+                name,
+                table: parser.parse_object_name()?,
+            }));
+        }
+        parser.prev_token();
+    }
+
+    if parser.parse_keyword(Keyword::SET) {
+        if parse_word(parser, "ROLE") {
+            let role = parser.parse_identifier()?;
+            let role = if role.quote_style.is_none() && role.value.eq_ignore_ascii_case("NONE") {
+                None
+            } else {
+                Some(role)
+            };
+            return Ok(Some(Statement::SetRole { role }));
+        }
+        if parse_word(parser, "work_mem") {
+            if !parser.consume_token(&Token::Eq) {
+                parser.expect_keyword(Keyword::TO)?;
+            }
+            let value = match parser.next_token() {
+                Token::SingleQuotedString(value) => value,
+                Token::Number(value, _) => value,
+                _ => {
+                    parser.prev_token();
+                    return parser.expected("a memory size", parser.peek_token());
+                }
+            };
+            return Ok(Some(Statement::SetWorkMem { value: Some(value) }));
+        }
+        parser.prev_token();
+    }
+
+    if parser.parse_keywords(&[Keyword::ALTER, Keyword::TABLE]) {
+        if let Some(stmt) = parse_alter_table_options(parser)? {
+            return Ok(Some(stmt));
+        }
+        parser.prev_token();
+        parser.prev_token();
+    }
+
+    if parse_word(parser, "CALL") {
+        let name = parser.parse_object_name()?;
+        parser.expect_token(&Token::LParen)?;
+        let mut args = Vec::new();
+        if !parser.consume_token(&Token::RParen) {
+            args = parser.parse_comma_separated(Parser::parse_expr)?;
+            parser.expect_token(&Token::RParen)?;
+        }
+        return Ok(Some(Statement::Call { name, args }));
+    }
+
+    if parse_word(parser, "RESET") {
+        if parse_word(parser, "ROLE") {
+            return Ok(Some(Statement::SetRole { role: None }));
+        }
+        if parse_word(parser, "work_mem") {
+            return Ok(Some(Statement::SetWorkMem { value: None }));
+        }
+        parser.prev_token();
+    }
+
+    Ok(None)
+}
+
+/// Parse a DO statement, returning None if text is not one. It is parsed before tokenizing the
+/// statement, since sqlparser can't tokenize the dollar-quoted string of its body.
+fn parse_do(text: &str) -> Result<Option<Statement>, ParserError> {
+    match strip_word(skip_comments(text), "DO") {
+        Some(rest) => Ok(Some(Statement::Do {
+            body: parse_body(rest)?,
+        })),
+        None => Ok(None),
+    }
+}
+
+/// Parse a CREATE PROCEDURE statement, returning None if text is not one. Like DO, only the text
+/// before its dollar-quoted body is tokenized.
+fn parse_create_procedure(text: &str) -> Result<Option<Statement>, ParserError> {
+    let rest = match strip_word(skip_comments(text), "CREATE") {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+    let (or_replace, rest) = match strip_word(rest, "OR").and_then(|r| strip_word(r, "REPLACE")) {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let rest = match strip_word(rest, "PROCEDURE") {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+
+    let body_start = rest
+        .match_indices('$')
+        .map(|(i, _)| i)
+        .find(|i| dollar_quote_delimiter(&rest[*i..]).is_some())
+        .unwrap_or(rest.len());
+
+    let tokens = Tokenizer::new(&DIALECT, &rest[..body_start]).tokenize()?;
+    let mut parser = Parser::new(tokens, &DIALECT);
+    let name = parser.parse_object_name()?;
+    parser.expect_token(&Token::LParen)?;
+    let mut params = Vec::new();
+    if !parser.consume_token(&Token::RParen) {
+        loop {
+            params.push((parser.parse_identifier()?, parser.parse_data_type()?));
+            if !parser.consume_token(&Token::Comma) {
+                break;
+            }
+        }
+        parser.expect_token(&Token::RParen)?;
+    }
+    if parse_word(&mut parser, "LANGUAGE") && !parse_word(&mut parser, "plpgsql") {
+        return parser.expected("plpgsql", parser.peek_token());
+    }
+    parser.expect_keyword(Keyword::AS)?;
+    if parser.peek_token() != Token::EOF {
+        return parser.expected("dollar-quoted string", parser.peek_token());
+    }
+
+    Ok(Some(Statement::CreateProcedure {
+        or_replace,
+        name,
+        params,
+        body: parse_body(&rest[body_start..])?,
+    }))
+}
+
+/// Parse the dollar-quoted body of procedural code that ends a statement, which can be preceded or
+/// followed by LANGUAGE plpgsql.
+fn parse_body(mut rest: &str) -> Result<String, ParserError> {
+    let mut body = None;
+    let mut language = None;
+    loop {
+        if let Some(after) = strip_word(rest, "LANGUAGE") {
+            let end = after
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            if language.is_some() || !after[..end].eq_ignore_ascii_case("plpgsql") {
+                return Err(unexpected("language plpgsql", after));
+            }
+            language = Some(&after[..end]);
+            rest = skip_comments(&after[end..]);
+        } else if let Some(delimiter) = dollar_quote_delimiter(rest).filter(|_| body.is_none()) {
+            let after = &rest[delimiter.len()..];
+            let end = match after.find(delimiter) {
+                Some(end) => end,
+                None => return Err(unexpected("end of dollar-quoted string", "")),
+            };
+            body = Some(after[..end].to_string());
+            rest = skip_comments(&after[end + delimiter.len()..]);
+        } else {
+            break;
+        }
+    }
+
+    match body {
+        Some(body) if rest.is_empty() => Ok(body),
+        Some(_) => Err(unexpected("end of statement", rest)),
+        None => Err(unexpected("dollar-quoted string", rest)),
+    }
+}
+
+/// Return the text after the given word at the start of text, without the whitespaces and
+/// comments that follow it, or None if text does not start with the word.
+fn strip_word<'a>(text: &'a str, word: &str) -> Option<&'a str> {
+    let rest = text.get(word.len()..)?;
+    if !text[..word.len()].eq_ignore_ascii_case(word)
+        || rest.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    Some(skip_comments(rest))
+}
+
+/// Return the error of an unexpected token at the start of text, formatted like sqlparser errors.
+fn unexpected(expected: &str, text: &str) -> ParserError {
+    let found = text.split_whitespace().next().unwrap_or("EOF");
+    ParserError::ParserError(format!("Expected {}, found: {}", expected, found))
+}
+
+/// Parse a CREATE POLICY statement after the POLICY keyword. Only permissive policies for all
+/// commands are supported, so neither AS nor FOR other than ALL are accepted.
+fn parse_create_policy(parser: &mut Parser) -> Result<Statement, ParserError> {
+    let name = parser.parse_identifier()?;
+    parser.expect_keyword(Keyword::ON)?;
+    let table = parser.parse_object_name()?;
+
+    if parser.parse_keyword(Keyword::FOR) {
+        parser.expect_keyword(Keyword::ALL)?;
+    }
+
+    let mut roles = Vec::new();
+    if parser.parse_keyword(Keyword::TO) {
+        loop {
+            let role = parser.parse_identifier()?;
+            if role.quote_style.is_some() || !role.value.eq_ignore_ascii_case("PUBLIC") {
+                roles.push(role);
+            }
+            if !parser.consume_token(&Token::Comma) {
+                break;
+            }
+        }
+    }
+
+    parser.expect_keyword(Keyword::USING)?;
+    parser.expect_token(&Token::LParen)?;
+    let using = parser.parse_expr()?;
+    parser.expect_token(&Token::RParen)?;
+
+    Ok(Statement::CreatePolicy {
+        name,
+        table,
+        roles,
+        using,
+    })
+}
+
+/// Parse the SET and RESET of storage options after ALTER TABLE, returning None, without consuming
+/// any token, if the statement alters the table otherwise. Other alterations are left to sqlparser.
+fn parse_alter_table_options(parser: &mut Parser) -> Result<Option<Statement>, ParserError> {
+    let table = parser.parse_object_name()?;
+    let set = parser.parse_keyword(Keyword::SET);
+    let reset = !set && parse_word(parser, "RESET");
+    if !(set || reset) || !parser.consume_token(&Token::LParen) {
+        // The name is made of identifiers separated by periods, plus the SET or RESET consumed.
+        for _ in 0..2 * table.0.len() - 1 + usize::from(set || reset) {
+            parser.prev_token();
+        }
+        return Ok(None);
+    }
+
+    let mut options = Vec::new();
+    loop {
+        let name = parser.parse_identifier()?;
+        let value = if set {
+            parser.expect_token(&Token::Eq)?;
+            Some(parser.parse_value()?)
+        } else {
+            None
+        };
+        options.push((name, value));
+        if !parser.consume_token(&Token::Comma) {
+            break;
+        }
+    }
+    parser.expect_token(&Token::RParen)?;
+
+    Ok(Some(Statement::AlterTableOptions { table, options }))
+}
+
+/// Consume the next token if it is the given word, ignoring case. It is used for keywords that
+/// sqlparser does not know yet.
+fn parse_word(parser: &mut Parser, word: &str) -> bool {
+    match parser.peek_token() {
+        Token::Word(w) if w.quote_style.is_none() && w.value.eq_ignore_ascii_case(word) => {
+            parser.next_token();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Parse a list of expressions separated by commas.
+pub fn parse_expr_list(sql: &str) -> Result<Vec<ast::Expr>> {
+    let tokens = Tokenizer::new(&DIALECT, sql).tokenize()?;
+    let mut parser = Parser::new(tokens, &DIALECT);
+    let exprs = parser.parse_comma_separated(Parser::parse_expr)?;
+    if parser.peek_token() != Token::EOF {
+        return Ok(parser.expected("end of expression", parser.peek_token())?);
+    }
+    Ok(exprs)
+}
+
+/// Parse a single expression, e.g the USING expression of a policy stored on pg_policy.
+pub fn parse_expr(sql: &str) -> Result<ast::Expr> {
+    let tokens = Tokenizer::new(&DIALECT, sql).tokenize()?;
+    let mut parser = Parser::new(tokens, &DIALECT);
+    let expr = parser.parse_expr()?;
+    if parser.peek_token() != Token::EOF {
+        return Ok(parser.expected("end of expression", parser.peek_token())?);
+    }
+    Ok(expr)
+}
+
+/// Return the offset and text of each statement of sql, split on semicolons that are not part of
+/// quoted strings, dollar-quoted strings, quoted identifiers or comments.
+pub fn split_statements(sql: &str) -> Vec<(usize, &str)> {
+    let mut stmts = Vec::new();
+    let mut start = 0;
+    let mut chars = sql.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                // A doubled quote escapes the quote, so it is handled like two quoted strings.
+                for (_, next) in chars.by_ref() {
+                    if next == c {
+                        break;
+                    }
+                }
+            }
+            '-' if matches!(chars.peek(), Some((_, '-'))) => {
+                for (_, next) in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if matches!(chars.peek(), Some((_, '*'))) => {
+                chars.next();
+                while let Some((_, next)) = chars.next() {
+                    if next == '*' && matches!(chars.peek(), Some((_, '/'))) {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            '$' => {
+                if let Some(delimiter) = dollar_quote_delimiter(&sql[i..]) {
+                    let body = i + delimiter.len();
+                    let end = sql[body..]
+                        .find(delimiter)
+                        .map_or(sql.len(), |end| body + end + delimiter.len());
+                    while matches!(chars.peek(), Some((j, _)) if *j < end) {
+                        chars.next();
+                    }
+                }
+            }
+            ';' => {
+                stmts.push((start, &sql[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    stmts.push((start, &sql[start..]));
+
+    stmts
+}
+
+/// Return the opening delimiter of a dollar-quoted string, e.g $$ or $body$, if text starts with
+/// one. Like Postgres, tags can't start with a digit, so $1 is not a delimiter.
+pub fn dollar_quote_delimiter(text: &str) -> Option<&str> {
+    let rest = text.strip_prefix('$')?;
+    let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+    if rest.starts_with(|c: char| c.is_ascii_digit()) || !rest[end..].starts_with('$') {
+        return None;
+    }
+    Some(&text[..end + 2])
+}
+
+/// Return text without its leading whitespaces and comments.
+pub fn skip_comments(mut text: &str) -> &str {
+    loop {
+        text = text.trim_start();
+        if text.starts_with("--") {
+            text = text.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if text.starts_with("/*") {
+            text = text.split_once("*/").map_or("", |(_, rest)| rest);
+        } else {
+            return text;
+        }
+    }
+}
+
+/// Return the error of an invalid statement at the given offset of sql, with a diagnostic
+/// pointing to the unexpected token.
+fn syntax_error(err: ParserError, sql: &str, offset: usize, text: &str) -> anyhow::Error {
+    let message = err.to_string();
+    let position = match message.split_once("found: ") {
+        Some((_, "EOF")) => Some(text.trim_end().len()),
+        Some((_, token)) => find_token(text, token),
+        None => None,
+    };
+
+    let diagnostic = Diagnostic::new(message, sql, position.map(|i| offset + i), None);
+    anyhow::Error::new(err).context(diagnostic)
+}
+
+/// Return the name of the new table of a SELECT ... INTO query, if any.
+pub fn select_into(query: &ast::Query) -> Option<&ObjectName> {
+    match &query.body {
+        ast::SetExpr::Select(select) => select.into.as_ref().map(|into| &into.name),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sql() -> Result<()> {
+        let sql = "DROP DATABASE db; SELECT * FROM t;; DROP DATABASE IF EXISTS db -- ;\n";
+        let stmts = parse_sql(sql)?;
+        assert_eq!(stmts.len(), 3);
+        assert_eq!(stmts[0].stmt.to_string(), "DROP DATABASE db");
+        assert_eq!(stmts[1].stmt.to_string(), "SELECT * FROM t");
+        assert_eq!(stmts[1].offset, 17);
+        assert_eq!(stmts[1].text, " SELECT * FROM t");
+        assert_eq!(stmts[2].stmt.to_string(), "DROP DATABASE IF EXISTS db");
+
+        let stmts = parse_sql("SELECT * INTO t2 FROM t; SELECT * FROM t")?;
+        assert!(!stmts[0].stmt.is_read_only());
+        assert!(stmts[1].stmt.is_read_only());
+
+        let stmts = parse_sql("SELECT * FROM \"a;b\" /* ; */; SELECT 1 -- ;")?;
+        assert_eq!(stmts.len(), 2);
+        assert_eq!(stmts[0].stmt.to_string(), "SELECT * FROM \"a;b\"");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_policy() -> Result<()> {
+        let stmts = parse_sql(
+            "CREATE POLICY p ON t TO alice, PUBLIC USING (a = 1 AND b > 2); \
+             CREATE POLICY p ON t FOR ALL USING (true); DROP POLICY IF EXISTS p ON t; \
+             SET ROLE alice; SET ROLE NONE; RESET ROLE; CREATE TABLE t(a int); DROP TABLE t",
+        )?;
+        let stmts: Vec<String> = stmts.iter().map(|parsed| parsed.stmt.to_string()).collect();
+        assert_eq!(
+            stmts,
+            vec![
+                "CREATE POLICY p ON t TO alice USING (a = 1 AND b > 2)",
+                "CREATE POLICY p ON t USING (true)",
+                "DROP POLICY IF EXISTS p ON t",
+                "SET ROLE alice",
+                "RESET ROLE",
+                "RESET ROLE",
+                "CREATE TABLE t (a INT)",
+                "DROP TABLE t",
+            ]
+        );
+
+        assert!(parse_sql("CREATE POLICY p ON t FOR SELECT USING (true)").is_err());
+        assert!(parse_sql("CREATE POLICY p ON t").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_alter_table_options() -> Result<()> {
+        let stmts = parse_sql(
+            "ALTER TABLE t SET (fillfactor = 70); alter table db.public.t reset (fillfactor); \
+             ALTER TABLE t RENAME TO t2",
+        )?;
+        let stmts: Vec<String> = stmts.iter().map(|parsed| parsed.stmt.to_string()).collect();
+        assert_eq!(
+            stmts,
+            vec![
+                "ALTER TABLE t SET (fillfactor = 70)",
+                "ALTER TABLE db.public.t RESET (fillfactor)",
+                "ALTER TABLE t RENAME TO t2",
+            ]
+        );
+
+        assert!(parse_sql("ALTER TABLE t SET (fillfactor)").is_err());
+        assert!(parse_sql("ALTER TABLE t RESET (fillfactor = 70)").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_set_work_mem() -> Result<()> {
+        let stmts = parse_sql("SET work_mem = '64MB'; set WORK_MEM to 1024; RESET work_mem")?;
+        let stmts: Vec<String> = stmts.iter().map(|parsed| parsed.stmt.to_string()).collect();
+        assert_eq!(
+            stmts,
+            vec![
+                "SET work_mem = '64MB'",
+                "SET work_mem = '1024'",
+                "RESET work_mem"
+            ]
+        );
+
+        assert!(parse_sql("SET work_mem 64").is_err());
+        assert!(parse_sql("SET work_mem = big").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_do() -> Result<()> {
+        let sql = "DO $$ BEGIN INSERT INTO t(a) VALUES(1); END $$; \
+                   do language plpgsql $body$ BEGIN RAISE NOTICE '$$;'; END $body$; SELECT $1";
+        let split: Vec<&str> = split_statements(sql)
+            .iter()
+            .map(|(_, text)| *text)
+            .collect();
+        assert_eq!(split.len(), 3);
+
+        let stmts = parse_sql(&sql[..sql.rfind(';').unwrap()])?;
+        let stmts: Vec<String> = stmts.iter().map(|parsed| parsed.stmt.to_string()).collect();
+        assert_eq!(
+            stmts,
+            vec![
+                "DO $$ BEGIN INSERT INTO t(a) VALUES(1); END $$",
+                "DO $body$ BEGIN RAISE NOTICE '$$;'; END $body$",
+            ]
+        );
+
+        assert!(parse_sql("DO $$ BEGIN END").is_err());
+        assert!(parse_sql("DO LANGUAGE sql $$ BEGIN END $$").is_err());
+        assert!(parse_sql("DO 'BEGIN END'").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_procedure() -> Result<()> {
+        let stmts = parse_sql(
+            "CREATE OR REPLACE PROCEDURE p(a int, b boolean) LANGUAGE plpgsql AS $$ BEGIN END $$; \
+             create procedure db.q() as $q$ BEGIN RAISE NOTICE '$$'; END $q$ language plpgsql; \
+             CALL p(1 + 1, true); CALL q(); DROP PROCEDURE IF EXISTS p(int, boolean); \
+             DROP PROCEDURE q",
+        )?;
+        let stmts: Vec<String> = stmts.iter().map(|parsed| parsed.stmt.to_string()).collect();
+        assert_eq!(
+            stmts,
+            vec![
+                "CREATE OR REPLACE PROCEDURE p(a INT, b BOOLEAN) AS $$ BEGIN END $$",
+                "CREATE PROCEDURE db.q() AS $body$ BEGIN RAISE NOTICE '$$'; END $body$",
+                "CALL p(1 + 1, true)",
+                "CALL q()",
+                "DROP PROCEDURE IF EXISTS p(INT, BOOLEAN)",
+                "DROP PROCEDURE q",
+            ]
+        );
+
+        assert!(parse_sql("CREATE PROCEDURE p AS $$ BEGIN END $$").is_err());
+        assert!(parse_sql("CREATE PROCEDURE p() LANGUAGE sql AS $$ SELECT 1 $$").is_err());
+        assert!(parse_sql("CALL p").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_sql_error() {
+        let position = |sql| {
+            let err = parse_sql(sql).err().expect("expected parse error");
+            err.downcast_ref::<Diagnostic>().unwrap().position
+        };
+
+        assert_eq!(position("SELECT 1; DROP DATABASE db SELECT 1"), Some(28));
+        assert_eq!(position("SELECT * FROM"), Some(14));
+    }
+}
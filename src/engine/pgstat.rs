@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::access::progress;
+use crate::catalog::pg_type::{INT8_OID, TEXT_OID};
+use crate::Oid;
+
+use super::result::Column;
+use super::vtab::{Rows, ScanContext, VirtualTable};
+
+/// Name of the virtual table exposing the activity counters of user tables.
+pub const PG_STAT_USER_TABLES: &str = "pg_stat_user_tables";
+
+/// Name of the virtual table exposing the progress of the scans and vacuums running on this
+/// thread.
+pub const PG_STAT_PROGRESS: &str = "pg_stat_progress";
+
+/// Activity counters of a single relation since the engine was created.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct RelationStats {
+    /// Number of sequential scans initiated on the relation.
+    pub seq_scan: u64,
+
+    /// Number of index scans initiated on the relation.
+    pub idx_scan: u64,
+
+    /// Number of tuples inserted.
+    pub n_tup_ins: u64,
+
+    /// Number of tuples deleted.
+    pub n_tup_del: u64,
+}
+
+/// Collect the activity counters of all relations accessed by an engine.
+///
+/// Counters are kept only in memory, so they start at zero each time the engine is created.
+#[derive(Default)]
+pub struct StatsCollector {
+    /// Counters indexed by database name and relation oid, since relation oids are only unique
+    /// within a database.
+    relations: HashMap<(String, Oid), RelationStats>,
+}
+
+impl StatsCollector {
+    /// Return the counters of the given relation.
+    pub fn relation(&self, db_name: &str, oid: Oid) -> RelationStats {
+        self.relations
+            .get(&(db_name.to_string(), oid))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Remove the counters of all relations of the given database, e.g because it was dropped.
+    pub fn remove_database(&mut self, db_name: &str) {
+        self.relations.retain(|(name, _), _| name != db_name);
+    }
+
+    /// Return a mutable reference to the counters of the given relation.
+    pub fn relation_mut(&mut self, db_name: &str, oid: Oid) -> &mut RelationStats {
+        self.relations
+            .entry((db_name.to_string(), oid))
+            .or_default()
+    }
+}
+
+/// The pg_stat_user_tables view, with one row for each relation of the current database.
+pub struct PgStatUserTables;
+
+impl VirtualTable for PgStatUserTables {
+    fn name(&self) -> &str {
+        PG_STAT_USER_TABLES
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![
+            Column::new("relid", INT8_OID).not_null(),
+            Column::new("relname", TEXT_OID).not_null(),
+            Column::new("seq_scan", INT8_OID).not_null(),
+            Column::new("idx_scan", INT8_OID).not_null(),
+            Column::new("n_tup_ins", INT8_OID).not_null(),
+            Column::new("n_tup_del", INT8_OID).not_null(),
+            Column::new("heap_blks_read", INT8_OID).not_null(),
+            Column::new("heap_blks_hit", INT8_OID).not_null(),
+        ]
+    }
+
+    fn scan(&self, ctx: &mut ScanContext) -> Result<Rows> {
+        let db_name = ctx.database.db_name();
+        let db_oid = ctx.database.db_oid();
+
+        let mut rows = Vec::new();
+        for rel in ctx.database.get_relations(ctx.buffer_pool)? {
+            let stats = ctx.stats.relation(db_name, rel.oid);
+            let block_stats = ctx.buffer_pool.block_stats(db_oid, rel.oid);
+            rows.push(vec![
+                rel.oid.to_string(),
+                rel.relname,
+                stats.seq_scan.to_string(),
+                stats.idx_scan.to_string(),
+                stats.n_tup_ins.to_string(),
+                stats.n_tup_del.to_string(),
+                block_stats.blks_read.to_string(),
+                block_stats.blks_hit.to_string(),
+            ]);
+        }
+        Ok(Box::new(rows.into_iter()))
+    }
+}
+
+/// The pg_stat_progress view, with one row for each scan or vacuum running on this thread.
+/// Operations are only visible while they run, e.g from a progress handler.
+pub struct PgStatProgress;
+
+impl VirtualTable for PgStatProgress {
+    fn name(&self) -> &str {
+        PG_STAT_PROGRESS
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![
+            Column::new("command", TEXT_OID).not_null(),
+            Column::new("relid", INT8_OID).not_null(),
+            Column::new("relname", TEXT_OID).not_null(),
+            Column::new("pages_done", INT8_OID).not_null(),
+            Column::new("pages_total", INT8_OID).not_null(),
+        ]
+    }
+
+    fn scan(&self, _ctx: &mut ScanContext) -> Result<Rows> {
+        Ok(Box::new(progress::active().into_iter().map(|progress| {
+            vec![
+                progress.command.to_string(),
+                progress.relid.to_string(),
+                progress.rel_name,
+                progress.pages_done.to_string(),
+                progress.pages_total.to_string(),
+            ]
+        })))
+    }
+}
@@ -0,0 +1,728 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use sqlparser::ast;
+
+use crate::catalog::pg_type::{BOOL_OID, INT4_OID};
+use crate::Oid;
+
+use super::expr::{self, Datum, Row};
+use super::parser::{dollar_quote_delimiter, parse_expr, parse_expr_list, skip_comments};
+use super::result::QueryResult;
+use super::Error;
+
+/// Interface used by the interpreter of DO blocks to execute SQL statements and raise notices.
+pub trait Executor {
+    /// Execute SQL statements, returning the rows of each query.
+    fn exec_sql(&mut self, sql: &str) -> Result<Vec<QueryResult>>;
+
+    /// Send a notice to the client.
+    fn notice(&mut self, message: String);
+
+    /// Make the changes of the statements executed so far durable.
+    fn commit(&mut self) -> Result<()>;
+}
+
+/// Data type of a variable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    Integer,
+    Boolean,
+    /// A row returned by a query, assigned by FOR loops.
+    Record,
+}
+
+/// Return true if variables of the given type are supported, e.g for the parameters of a
+/// procedure.
+pub fn is_supported_type(typ: Oid) -> bool {
+    matches!(typ, INT4_OID | BOOL_OID)
+}
+
+/// A parameter of a procedure, assigned as a variable before its block is executed.
+pub struct Param {
+    pub name: String,
+
+    /// Type of the parameter, which should be supported by [is_supported_type].
+    pub typ: Oid,
+
+    pub value: Datum,
+}
+
+#[derive(Debug, PartialEq)]
+struct Declaration {
+    name: String,
+    typ: Type,
+    default: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RaiseLevel {
+    Notice,
+    Exception,
+}
+
+/// A statement of a block. Expressions and queries are kept as text, since variables are
+/// replaced by their values each time they are executed.
+#[derive(Debug, PartialEq)]
+enum Stmt {
+    /// name := expr
+    Assign { name: String, expr: String },
+
+    /// IF cond THEN ... [ ELSIF cond THEN ... ] [ ELSE ... ] END IF
+    If {
+        branches: Vec<(String, Vec<Stmt>)>,
+        else_branch: Vec<Stmt>,
+    },
+
+    /// FOR name IN query LOOP ... END LOOP
+    ForQuery {
+        var: String,
+        query: String,
+        body: Vec<Stmt>,
+    },
+
+    /// RAISE { NOTICE | EXCEPTION } 'format' [, expr ...], where each % of format is replaced by
+    /// the next expression.
+    Raise {
+        level: RaiseLevel,
+        format: String,
+        args: Vec<String>,
+    },
+
+    /// NULL, which does nothing.
+    Null,
+
+    /// COMMIT. There are no transactions yet, so statements are applied immediately and COMMIT
+    /// only makes them durable.
+    Commit,
+
+    /// ROLLBACK, which fails since statements can't be rolled back yet.
+    Rollback,
+
+    /// Any other SQL statement. PERFORM query is executed as a SELECT query.
+    Sql(String),
+}
+
+/// A block of procedural code executed by DO. It is a small subset of PL/pgSQL:
+///
+/// ```sql
+/// [ DECLARE
+///     name { integer | boolean | record } [ := expr ]; ... ]
+/// BEGIN
+///     statements
+/// END
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Block {
+    declarations: Vec<Declaration>,
+    body: Vec<Stmt>,
+}
+
+/// Parse the body of a DO statement.
+///
+/// Return error if the body is not a valid block.
+pub fn parse_block(body: &str) -> Result<Block> {
+    let mut cursor = Cursor { text: body, pos: 0 };
+
+    let mut declarations = Vec::new();
+    if cursor.parse("DECLARE") {
+        while !cursor.peek_is("BEGIN") {
+            declarations.push(parse_declaration(&mut cursor)?);
+        }
+    }
+
+    cursor.expect("BEGIN")?;
+    let body = parse_stmts(&mut cursor)?;
+    cursor.expect("END")?;
+    cursor.parse(";");
+    if !cursor.at_end() {
+        bail!(cursor.error("expected end of block"));
+    }
+
+    Ok(Block { declarations, body })
+}
+
+fn parse_declaration(cursor: &mut Cursor) -> Result<Declaration> {
+    let name = cursor.identifier()?;
+    let typ = match cursor.identifier()?.as_str() {
+        "integer" | "int" | "int4" => Type::Integer,
+        "boolean" | "bool" => Type::Boolean,
+        "record" => Type::Record,
+        typname => bail!(Error::TypeNotFound(typname.to_string())),
+    };
+
+    let default = if cursor.parse(":=") || cursor.parse("DEFAULT") {
+        if typ == Type::Record {
+            bail!(cursor.error("record variables can't have a default value"));
+        }
+        Some(cursor.expr(";")?)
+    } else {
+        cursor.expect(";")?;
+        None
+    };
+
+    Ok(Declaration { name, typ, default })
+}
+
+/// Parse statements until the END, ELSE or ELSIF that ends them.
+fn parse_stmts(cursor: &mut Cursor) -> Result<Vec<Stmt>> {
+    let mut stmts = Vec::new();
+    while !cursor.at_end() && !["END", "ELSE", "ELSIF"].iter().any(|w| cursor.peek_is(w)) {
+        stmts.push(parse_stmt(cursor)?);
+    }
+    Ok(stmts)
+}
+
+fn parse_stmt(cursor: &mut Cursor) -> Result<Stmt> {
+    if cursor.parse("IF") {
+        let mut branches = vec![(cursor.expr("THEN")?, parse_stmts(cursor)?)];
+        while cursor.parse("ELSIF") {
+            branches.push((cursor.expr("THEN")?, parse_stmts(cursor)?));
+        }
+        let else_branch = if cursor.parse("ELSE") {
+            parse_stmts(cursor)?
+        } else {
+            Vec::new()
+        };
+        cursor.expect("END")?;
+        cursor.expect("IF")?;
+        cursor.expect(";")?;
+        return Ok(Stmt::If {
+            branches,
+            else_branch,
+        });
+    }
+
+    if cursor.parse("FOR") {
+        let var = cursor.identifier()?;
+        cursor.expect("IN")?;
+        let query = cursor.until("LOOP")?.to_string();
+        let body = parse_stmts(cursor)?;
+        cursor.expect("END")?;
+        cursor.expect("LOOP")?;
+        cursor.expect(";")?;
+        return Ok(Stmt::ForQuery { var, query, body });
+    }
+
+    if cursor.parse("RAISE") {
+        let level = match cursor.identifier()?.as_str() {
+            "notice" | "info" | "warning" => RaiseLevel::Notice,
+            "exception" => RaiseLevel::Exception,
+            level => bail!(cursor.error(&format!("unrecognized RAISE level {}", level))),
+        };
+        let mut args = parse_expr_list(cursor.until(";")?)?.into_iter();
+        let format = match args.next() {
+            Some(ast::Expr::Value(ast::Value::SingleQuotedString(format))) => format,
+            _ => bail!(cursor.error("RAISE requires a format string literal")),
+        };
+        let args: Vec<String> = args.map(|arg| arg.to_string()).collect();
+
+        let params = format.matches('%').count() - 2 * format.matches("%%").count();
+        if params != args.len() {
+            let problem = if params > args.len() { "few" } else { "many" };
+            bail!(cursor.error(&format!("too {} parameters specified for RAISE", problem)));
+        }
+        return Ok(Stmt::Raise {
+            level,
+            format,
+            args,
+        });
+    }
+
+    if cursor.parse("NULL") {
+        cursor.expect(";")?;
+        return Ok(Stmt::Null);
+    }
+
+    if cursor.parse("COMMIT") {
+        cursor.expect(";")?;
+        return Ok(Stmt::Commit);
+    }
+
+    if cursor.parse("ROLLBACK") {
+        cursor.expect(";")?;
+        return Ok(Stmt::Rollback);
+    }
+
+    if cursor.parse("PERFORM") {
+        return Ok(Stmt::Sql(format!("SELECT {}", cursor.until(";")?)));
+    }
+
+    let start = cursor.pos;
+    if let Ok(name) = cursor.identifier() {
+        if cursor.parse(":=") {
+            let expr = cursor.expr(";")?;
+            return Ok(Stmt::Assign { name, expr });
+        }
+    }
+    cursor.pos = start;
+
+    Ok(Stmt::Sql(cursor.until(";")?.to_string()))
+}
+
+/// Cursor over the text of a block. Whitespaces and comments are skipped, and quoted strings are
+/// single tokens, so keywords are only found outside of them.
+struct Cursor<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    /// Return the start and end of the next token, which is a word, a quoted string, the :=
+    /// operator or any other single character.
+    fn next_token(&self) -> Option<(usize, usize)> {
+        let rest = skip_comments(&self.text[self.pos..]);
+        let start = self.text.len() - rest.len();
+
+        let c = rest.chars().next()?;
+        let len = if c.is_alphabetic() || c == '_' {
+            rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+                .unwrap_or(rest.len())
+        } else if c == '\'' || c == '"' {
+            // A doubled quote escapes the quote, so it is handled like two quoted strings.
+            rest[1..].find(c).map_or(rest.len(), |end| end + 2)
+        } else if let Some(delimiter) = dollar_quote_delimiter(rest) {
+            let body = &rest[delimiter.len()..];
+            body.find(delimiter)
+                .map_or(rest.len(), |end| end + 2 * delimiter.len())
+        } else if rest.starts_with(":=") {
+            2
+        } else {
+            c.len_utf8()
+        };
+
+        Some((start, start + len))
+    }
+
+    fn at_end(&self) -> bool {
+        self.next_token().is_none()
+    }
+
+    /// Return true if the next token is the given word or symbol, ignoring case.
+    fn peek_is(&self, token: &str) -> bool {
+        match self.next_token() {
+            Some((start, end)) => self.text[start..end].eq_ignore_ascii_case(token),
+            None => false,
+        }
+    }
+
+    /// Consume the next token if it is the given word or symbol, ignoring case.
+    fn parse(&mut self, token: &str) -> bool {
+        match self.next_token() {
+            Some((start, end)) if self.text[start..end].eq_ignore_ascii_case(token) => {
+                self.pos = end;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if !self.parse(token) {
+            bail!(self.error(&format!("expected {}", token)));
+        }
+        Ok(())
+    }
+
+    /// Consume the next token if it is an identifier, returning it in lower case like Postgres
+    /// does for unquoted identifiers.
+    fn identifier(&mut self) -> Result<String> {
+        match self.next_token() {
+            Some((start, end)) if self.text[start..].starts_with(|c: char| c.is_alphabetic()) => {
+                self.pos = end;
+                Ok(self.text[start..end].to_lowercase())
+            }
+            _ => bail!(self.error("expected identifier")),
+        }
+    }
+
+    /// Consume the text until the given token, returning the text before it.
+    fn until(&mut self, token: &str) -> Result<&'a str> {
+        let start = self.pos;
+        while let Some((token_start, token_end)) = self.next_token() {
+            self.pos = token_end;
+            if self.text[token_start..token_end].eq_ignore_ascii_case(token) {
+                let text = self.text[start..token_start].trim();
+                if text.is_empty() {
+                    bail!(self.error(&format!("unexpected {}", token)));
+                }
+                return Ok(text);
+            }
+        }
+        bail!(self.error(&format!("expected {}", token)))
+    }
+
+    /// Consume an expression that ends on the given token, checking its syntax.
+    fn expr(&mut self, end: &str) -> Result<String> {
+        let text = self.until(end)?;
+        parse_expr(text)?;
+        Ok(text.to_string())
+    }
+
+    /// Return a syntax error located at the next token.
+    fn error(&self, message: &str) -> Error {
+        let pos = self
+            .next_token()
+            .map_or(self.text.len(), |(start, _)| start);
+        let line = self.text[..pos].matches('\n').count() + 1;
+        Error::DoBlockSyntax(line, message.to_string())
+    }
+}
+
+/// Value of a variable.
+#[derive(Debug)]
+enum Variable {
+    Scalar(Type, Datum),
+
+    /// The columns and values of a row, or None if no row was assigned yet.
+    Record(Option<(Vec<String>, Vec<String>)>),
+}
+
+/// Execute a block with the given parameters, running its SQL statements with the given executor.
+pub fn execute(block: &Block, params: Vec<Param>, executor: &mut dyn Executor) -> Result<()> {
+    let mut interpreter = Interpreter {
+        executor,
+        variables: HashMap::new(),
+    };
+
+    for param in params {
+        let typ = match param.typ {
+            BOOL_OID => Type::Boolean,
+            _ => Type::Integer,
+        };
+        interpreter.assign(&param.name, Variable::Scalar(typ, param.value))?;
+    }
+
+    for declaration in &block.declarations {
+        let value = match &declaration.default {
+            _ if declaration.typ == Type::Record => Variable::Record(None),
+            Some(expr) => Variable::Scalar(declaration.typ, interpreter.eval(expr)?),
+            None => Variable::Scalar(declaration.typ, Datum::Null),
+        };
+        interpreter.assign(&declaration.name, value)?;
+    }
+
+    interpreter.exec_stmts(&block.body)
+}
+
+struct Interpreter<'a> {
+    executor: &'a mut dyn Executor,
+    variables: HashMap<String, Variable>,
+}
+
+impl Interpreter<'_> {
+    fn exec_stmts(&mut self, stmts: &[Stmt]) -> Result<()> {
+        for stmt in stmts {
+            self.exec_stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn exec_stmt(&mut self, stmt: &Stmt) -> Result<()> {
+        match stmt {
+            Stmt::Assign { name, expr } => {
+                let typ = match self.variables.get(name) {
+                    Some(Variable::Scalar(typ, _)) => *typ,
+                    Some(Variable::Record(_)) => bail!(Error::VariableTypeMismatch(
+                        name.clone(),
+                        String::from("record"),
+                        String::from("integer or boolean")
+                    )),
+                    None => bail!(Error::VariableNotFound(name.clone())),
+                };
+                let value = self.eval(expr)?;
+                self.assign(name, Variable::Scalar(typ, value))?;
+            }
+            Stmt::If {
+                branches,
+                else_branch,
+            } => {
+                for (cond, body) in branches {
+                    let cond = parse_expr(&self.substitute(cond)?)?;
+                    if expr::eval_predicate(&cond, &Row::default(), "IF")? {
+                        return self.exec_stmts(body);
+                    }
+                }
+                self.exec_stmts(else_branch)?;
+            }
+            Stmt::ForQuery { var, query, body } => {
+                // Like Postgres, the loop variable should be a record, but it doesn't need to be
+                // declared.
+                if let Some(Variable::Scalar(typ, _)) = self.variables.get(var) {
+                    bail!(Error::VariableTypeMismatch(
+                        var.clone(),
+                        type_name(*typ),
+                        String::from("record")
+                    ));
+                }
+                let sql = self.substitute(query)?;
+                let result = match self.executor.exec_sql(&sql)?.pop() {
+                    Some(result) => result,
+                    None => bail!(Error::NotAQuery(sql)),
+                };
+                let columns = result.column_names();
+                for row in result.rows {
+                    let record = Some((columns.clone(), row));
+                    self.variables.insert(var.clone(), Variable::Record(record));
+                    self.exec_stmts(body)?;
+                }
+            }
+            Stmt::Raise {
+                level,
+                format,
+                args,
+            } => {
+                let mut args = args.iter();
+                let mut message = String::new();
+                let mut chars = format.chars().peekable();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '%' if chars.peek() == Some(&'%') => {
+                            chars.next();
+                            message.push('%');
+                        }
+                        // The number of arguments was checked by the parser.
+                        '%' => match args.next() {
+                            Some(arg) => message.push_str(&self.format(arg)?),
+                            None => message.push('%'),
+                        },
+                        c => message.push(c),
+                    }
+                }
+                match level {
+                    RaiseLevel::Notice => self.executor.notice(message),
+                    RaiseLevel::Exception => bail!(Error::RaiseException(message)),
+                }
+            }
+            Stmt::Null => {}
+            Stmt::Commit => self.executor.commit()?,
+            Stmt::Rollback => bail!(Error::RollbackNotSupported),
+            Stmt::Sql(sql) => {
+                let sql = self.substitute(sql)?;
+                self.executor.exec_sql(&sql)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Assign a value to a variable, checking that it has the type of the variable.
+    fn assign(&mut self, name: &str, value: Variable) -> Result<()> {
+        if let Variable::Scalar(typ, datum) = &value {
+            let datum_type = match datum {
+                Datum::Int(_) => Type::Integer,
+                Datum::Bool(_) => Type::Boolean,
+                Datum::Null => *typ,
+            };
+            if datum_type != *typ {
+                bail!(Error::VariableTypeMismatch(
+                    name.to_string(),
+                    type_name(*typ),
+                    type_name(datum_type)
+                ));
+            }
+        }
+        self.variables.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Evaluate an expression of integers and booleans.
+    fn eval(&self, expr: &str) -> Result<Datum> {
+        let expr = parse_expr(&self.substitute(expr)?)?;
+        expr::eval(&expr, &Row::default())
+    }
+
+    /// Return the text of an argument of RAISE. Like Postgres, NULL is formatted as <NULL>.
+    fn format(&self, arg: &str) -> Result<String> {
+        let expr = parse_expr(&self.substitute(arg)?)?;
+        if let ast::Expr::Value(ast::Value::SingleQuotedString(value)) = expr {
+            return Ok(value);
+        }
+        Ok(match expr::eval(&expr, &Row::default())? {
+            Datum::Int(value) => value.to_string(),
+            Datum::Bool(true) => String::from("t"),
+            Datum::Bool(false) => String::from("f"),
+            Datum::Null => String::from("<NULL>"),
+        })
+    }
+
+    /// Replace the variables referenced by a SQL text with literals of their values. Fields of
+    /// records are referenced as name.field. Variables take precedence over columns with the same
+    /// name, and names qualified by a table are not replaced.
+    fn substitute(&self, sql: &str) -> Result<String> {
+        let mut cursor = Cursor { text: sql, pos: 0 };
+        let mut result = String::new();
+        let mut copied = 0;
+        let mut qualified = false;
+
+        while let Some((start, end)) = cursor.next_token() {
+            cursor.pos = end;
+            let token = &sql[start..end];
+            let variable = match self.variables.get(&token.to_lowercase()) {
+                Some(variable) if !qualified => variable,
+                _ => {
+                    qualified = token == ".";
+                    continue;
+                }
+            };
+
+            let value = match variable {
+                Variable::Scalar(_, datum) => datum_literal(datum),
+                Variable::Record(record) => {
+                    if !cursor.parse(".") {
+                        continue;
+                    }
+                    let field = cursor.identifier()?;
+                    let (columns, values) = match record {
+                        Some(record) => record,
+                        None => bail!(Error::RecordNotAssigned(token.to_string())),
+                    };
+                    match columns.iter().position(|column| *column == field) {
+                        Some(i) => text_literal(&values[i]),
+                        None => bail!(Error::RecordFieldNotFound(token.to_string(), field)),
+                    }
+                }
+            };
+
+            result.push_str(&sql[copied..start]);
+            result.push_str(&value);
+            copied = cursor.pos;
+        }
+        result.push_str(&sql[copied..]);
+
+        Ok(result)
+    }
+}
+
+fn type_name(typ: Type) -> String {
+    match typ {
+        Type::Integer => String::from("integer"),
+        Type::Boolean => String::from("boolean"),
+        Type::Record => String::from("record"),
+    }
+}
+
+/// Return the SQL literal of a value. Negative numbers are parenthesized, so they can't form a
+/// comment with a preceding minus.
+fn datum_literal(datum: &Datum) -> String {
+    match datum {
+        Datum::Int(value) if *value < 0 => format!("({})", value),
+        Datum::Int(value) => value.to_string(),
+        Datum::Bool(value) => value.to_string(),
+        Datum::Null => String::from("NULL"),
+    }
+}
+
+/// Return the SQL literal of a value of a row, which is formatted as text.
+fn text_literal(value: &str) -> String {
+    match value {
+        "NULL" | "true" | "false" => value.to_string(),
+        value if value.parse::<i64>().is_ok() && value.starts_with('-') => format!("({})", value),
+        value if value.parse::<i64>().is_ok() => value.to_string(),
+        value => format!("'{}'", value.replace('\'', "''")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::pg_type::TEXT_OID;
+    use crate::engine::result::Column;
+
+    /// Executor that records the statements executed, returning the rows of a fixed query for
+    /// every statement.
+    #[derive(Default)]
+    struct Recorder {
+        statements: Vec<String>,
+        notices: Vec<String>,
+    }
+
+    impl Executor for Recorder {
+        fn exec_sql(&mut self, sql: &str) -> Result<Vec<QueryResult>> {
+            self.statements.push(sql.to_string());
+            Ok(vec![QueryResult {
+                columns: vec![Column::new("relname", TEXT_OID), Column::new("n", INT4_OID)],
+                rows: vec![
+                    vec![String::from("t1"), String::from("1")],
+                    vec![String::from("it's"), String::from("-2")],
+                ],
+            }])
+        }
+
+        fn notice(&mut self, message: String) {
+            self.notices.push(message);
+        }
+
+        fn commit(&mut self) -> Result<()> {
+            self.statements.push(String::from("COMMIT"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_execute_block() -> Result<()> {
+        let block = parse_block(
+            "DECLARE
+                 total integer := 0;
+                 r record;
+             BEGIN
+                 FOR r IN SELECT relname, n FROM t LOOP
+                     total := total + r.n;
+                     IF r.n > 0 THEN
+                         INSERT INTO log(name, n) VALUES(r.relname, r.n); -- ; in comment
+                     ELSIF r.n = -2 THEN
+                         RAISE NOTICE '% has % (%%)', r.relname, r.n;
+                     ELSE
+                         NULL;
+                     END IF;
+                 END LOOP;
+                 RAISE NOTICE 'total %, %', total, total IS NULL;
+             END;",
+        )?;
+
+        let mut recorder = Recorder::default();
+        execute(&block, Vec::new(), &mut recorder)?;
+        assert_eq!(
+            recorder.statements,
+            vec![
+                "SELECT relname, n FROM t",
+                "INSERT INTO log(name, n) VALUES('t1', 1)",
+            ]
+        );
+        assert_eq!(recorder.notices, vec!["it's has -2 (%)", "total -1, f"]);
+
+        let block = parse_block("BEGIN IF flag THEN COMMIT; END IF; PERFORM n * 2; END")?;
+        let mut recorder = Recorder::default();
+        let params = vec![
+            Param {
+                name: String::from("flag"),
+                typ: BOOL_OID,
+                value: Datum::Bool(true),
+            },
+            Param {
+                name: String::from("n"),
+                typ: INT4_OID,
+                value: Datum::Null,
+            },
+        ];
+        execute(&block, params, &mut recorder)?;
+        assert_eq!(recorder.statements, vec!["COMMIT", "SELECT NULL * 2"]);
+
+        let run = |body: &str| execute(&parse_block(body)?, Vec::new(), &mut Recorder::default());
+        assert_eq!(
+            run("BEGIN RAISE EXCEPTION 'failed %', 1 + 1; END")
+                .unwrap_err()
+                .to_string(),
+            "failed 2"
+        );
+        assert!(run("DECLARE b boolean; BEGIN b := 1; END").is_err());
+        assert!(run("BEGIN x := 1; END").is_err());
+        assert!(run("BEGIN ROLLBACK; END").is_err());
+        assert!(run("DECLARE r record; BEGIN INSERT INTO t VALUES(r.a); END").is_err());
+
+        assert!(parse_block("BEGIN IF true THEN NULL; END; END").is_err());
+        assert!(parse_block("BEGIN RAISE NOTICE '% %', 1; END").is_err());
+        assert!(parse_block("BEGIN x := ; END").is_err());
+        assert!(parse_block("BEGIN NULL; END; NULL;").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{bail, Result};
+use sqlparser::ast;
+
+use crate::storage::bufpage::ItemPointer;
+
+use super::diagnostic::closest_match;
+use crate::catalog::pg_type::TEXT_OID;
+
+use super::result::{Column, QueryResult};
+use super::Error;
+
+/// Name of the system column with the physical location of each tuple.
+pub const CTID: &str = "ctid";
+
+/// Where the values of a projected column come from.
+enum Source {
+    /// A column of the scanned result, by its index.
+    Column(usize),
+
+    /// The location of the scanned tuple.
+    Ctid,
+}
+
+/// Return the columns of result selected by the target list of a query. Only `*` and column
+/// references, optionally renamed by an alias, are supported.
+///
+/// The ctid system column can be selected only when ctids has the location of each row of
+/// result, otherwise it is reported as a missing column.
+pub fn project(
+    projection: &[ast::SelectItem],
+    result: QueryResult,
+    ctids: Option<&[ItemPointer]>,
+) -> Result<QueryResult> {
+    if let [ast::SelectItem::Wildcard] = projection {
+        return Ok(result);
+    }
+
+    let mut columns = Vec::new();
+    let mut sources = Vec::new();
+    for item in projection {
+        match item {
+            ast::SelectItem::Wildcard => {
+                for (i, column) in result.columns.iter().enumerate() {
+                    columns.push(column.clone());
+                    sources.push(Source::Column(i));
+                }
+            }
+            ast::SelectItem::UnnamedExpr(expr) => {
+                let name = column_name(expr)?;
+                let source = column_source(&result, ctids, &name)?;
+                columns.push(source_column(&result, &source));
+                sources.push(source);
+            }
+            ast::SelectItem::ExprWithAlias { expr, alias } => {
+                let name = column_name(expr)?;
+                let source = column_source(&result, ctids, &name)?;
+                columns.push(Column {
+                    name: alias.value.clone(),
+                    ..source_column(&result, &source)
+                });
+                sources.push(source);
+            }
+            item => bail!(Error::UnsupportedTargetEntry(item.to_string())),
+        }
+    }
+
+    let ctids = ctids.unwrap_or_default();
+    let rows = result
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            sources
+                .iter()
+                .map(|source| match source {
+                    Source::Column(column) => row[*column].clone(),
+                    Source::Ctid => ctids[i].to_string(),
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(QueryResult { columns, rows })
+}
+
+/// Return the name of the column referenced by expr. Qualified references use only the column
+/// name, since queries have a single relation on FROM clause.
+fn column_name(expr: &ast::Expr) -> Result<String> {
+    match expr {
+        ast::Expr::Identifier(ident) => Ok(ident.value.clone()),
+        ast::Expr::CompoundIdentifier(idents) if !idents.is_empty() => {
+            Ok(idents[idents.len() - 1].value.clone())
+        }
+        expr => bail!(Error::UnsupportedTargetEntry(expr.to_string())),
+    }
+}
+
+/// Return where the values of the given column name come from.
+fn column_source(
+    result: &QueryResult,
+    ctids: Option<&[ItemPointer]>,
+    name: &str,
+) -> Result<Source> {
+    if let Some(i) = result.columns.iter().position(|column| column.name == name) {
+        return Ok(Source::Column(i));
+    }
+    if name == CTID && ctids.is_some() {
+        return Ok(Source::Ctid);
+    }
+    let similar = closest_match(
+        name,
+        result.columns.iter().map(|column| column.name.as_str()),
+    );
+    bail!(Error::ColumnNotFound(
+        name.to_string(),
+        similar.map(str::to_string)
+    ))
+}
+
+/// Return the description of a column selected from the given source. The ctid is shown as text,
+/// since there is no tid type yet.
+fn source_column(result: &QueryResult, source: &Source) -> Column {
+    match source {
+        Source::Column(i) => result.columns[*i].clone(),
+        Source::Ctid => Column::new(CTID, TEXT_OID).not_null(),
+    }
+}
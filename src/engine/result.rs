@@ -0,0 +1,484 @@
+use std::fmt;
+
+use crate::catalog::pg_type::{BOOL_OID, INT4_OID, INT8_OID};
+use crate::Oid;
+
+/// Description of a column of a query result, like a field of the RowDescription message of
+/// Postgres. Clients derive how values are shown or encoded from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// Name of the column.
+    pub name: String,
+
+    /// Oid of the column type on pg_type.
+    pub type_oid: Oid,
+
+    /// Type modifier, e.g the length of varchar(n), or -1 if the type has none.
+    pub typmod: i32,
+
+    /// False if the column can't have NULL values.
+    pub nullable: bool,
+
+    /// Oid of the relation and number of the attribute that the column comes from, if the column
+    /// is a relation column.
+    pub source: Option<(Oid, usize)>,
+}
+
+impl Column {
+    /// Create a new nullable column of the given type that is not a relation column.
+    pub fn new(name: &str, type_oid: Oid) -> Self {
+        Self {
+            name: name.to_string(),
+            type_oid,
+            typmod: -1,
+            nullable: true,
+            source: None,
+        }
+    }
+
+    /// Set the relation and attribute number that the column comes from.
+    pub fn with_source(mut self, relid: Oid, attnum: usize) -> Self {
+        self.source = Some((relid, attnum));
+        self
+    }
+
+    /// Mark the column as never having NULL values.
+    pub fn not_null(mut self) -> Self {
+        self.nullable = false;
+        self
+    }
+
+    /// Return true if values of the column are numbers, which are right aligned by the shell and
+    /// encoded as numbers on JSON.
+    pub fn is_numeric(&self) -> bool {
+        matches!(self.type_oid, INT4_OID | INT8_OID)
+    }
+}
+
+/// Rows returned by a statement.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QueryResult {
+    /// Description of each column.
+    pub columns: Vec<Column>,
+
+    /// Values of each row formatted as text, in the same order of columns.
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Options of how the shell renders query results, like the `\pset` options of psql.
+#[derive(Debug, Clone)]
+pub struct DisplayOptions {
+    /// If true each row is shown as a list of column and value pairs instead of a table row.
+    pub expanded: bool,
+
+    /// Text shown in place of NULL values.
+    pub null: String,
+
+    /// Maximum number of characters of the values shown on tables. Longer values are truncated,
+    /// and can be read whole with expanded display.
+    pub max_width: usize,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            expanded: false,
+            null: String::from("∅"),
+            max_width: 40,
+        }
+    }
+}
+
+impl QueryResult {
+    /// Return the names of the columns.
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|column| column.name.clone())
+            .collect()
+    }
+
+    /// Return the footer shown below the rows by the shell, e.g `(3 rows)`.
+    pub fn footer(&self) -> String {
+        match self.rows.len() {
+            1 => String::from("(1 row)"),
+            rows => format!("({} rows)", rows),
+        }
+    }
+
+    /// Render the result as a psql like table, or as a list of records if expanded display is
+    /// set. Numeric columns are right aligned.
+    pub fn render(&self, options: &DisplayOptions) -> String {
+        if options.expanded {
+            return self.render_expanded(options);
+        }
+
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|value| display_value(value, options, true))
+                    .collect()
+            })
+            .collect();
+
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .chain(std::iter::once(column.name.chars().count()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let numeric: Vec<bool> = self.columns.iter().map(Column::is_numeric).collect();
+
+        // Like psql, column names are centered and values are left aligned unless they are
+        // numbers.
+        let header: Vec<String> = self
+            .columns
+            .iter()
+            .zip(&widths)
+            .map(|(column, width)| format!(" {:^width$} ", column.name, width = width))
+            .collect();
+        let separator: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+
+        let mut output = format!("{}\n{}\n", header.join("|"), separator.join("+"));
+        for row in &rows {
+            let values: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(i, (value, width))| {
+                    if numeric[i] {
+                        format!(" {:>width$} ", value, width = width)
+                    } else {
+                        format!(" {:<width$} ", value, width = width)
+                    }
+                })
+                .collect();
+            output.push_str(&values.join("|"));
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Format the result as CSV with a header line of the column names. Like Postgres, NULL is an
+    /// empty unquoted field.
+    pub fn to_csv(&self) -> String {
+        let mut output = String::new();
+        let header: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| csv_field(&column.name))
+            .collect();
+        output.push_str(&header.join(","));
+        output.push('\n');
+        for row in &self.rows {
+            let fields: Vec<String> = row
+                .iter()
+                .map(|value| match value.as_str() {
+                    "NULL" => String::new(),
+                    value => csv_field(value),
+                })
+                .collect();
+            output.push_str(&fields.join(","));
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Format the result as a JSON array with an object for each row, which maps column names to
+    /// values. Values of numeric and boolean columns are JSON numbers and booleans, other values
+    /// are strings, and NULL is null.
+    pub fn to_json(&self) -> String {
+        let rows: Vec<String> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let fields: Vec<String> = self
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, value)| {
+                        let value = match value.as_str() {
+                            "NULL" => String::from("null"),
+                            value if column.is_numeric() => value.to_string(),
+                            "true" | "false" if column.type_oid == BOOL_OID => value.to_string(),
+                            value => json_string(value),
+                        };
+                        format!("{}:{}", json_string(&column.name), value)
+                    })
+                    .collect();
+                format!("{{{}}}", fields.join(","))
+            })
+            .collect();
+        format!("[{}]", rows.join(","))
+    }
+
+    /// Render each row as a record with a line for each column, like the expanded display of
+    /// psql. Values are never truncated.
+    fn render_expanded(&self, options: &DisplayOptions) -> String {
+        let name_width = self
+            .columns
+            .iter()
+            .map(|column| column.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        let value_width = self
+            .rows
+            .iter()
+            .flatten()
+            .map(|value| display_value(value, options, false).chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut output = String::new();
+        for (i, row) in self.rows.iter().enumerate() {
+            // Like psql, the header marks where values start if the label is narrower than
+            // the column names.
+            let mut header = format!("-[ RECORD {} ]", i + 1);
+            if header.len() <= name_width {
+                header.push_str(&"-".repeat(name_width + 1 - header.len()));
+                header.push('+');
+            }
+            let width = name_width + 3 + value_width;
+            header.push_str(&"-".repeat(width.saturating_sub(header.len())));
+            output.push_str(&header);
+            output.push('\n');
+
+            for (column, value) in self.columns.iter().zip(row) {
+                output.push_str(&format!(
+                    "{:<width$} | {}\n",
+                    column.name,
+                    display_value(value, options, false),
+                    width = name_width
+                ));
+            }
+        }
+        output
+    }
+}
+
+/// Return how a value is shown by the shell, replacing NULL and truncating wide values with an
+/// ellipsis if truncate is set.
+fn display_value(value: &str, options: &DisplayOptions, truncate: bool) -> String {
+    if value == "NULL" {
+        return options.null.clone();
+    }
+    if truncate && value.chars().count() > options.max_width {
+        let truncated: String = value
+            .chars()
+            .take(options.max_width.saturating_sub(1))
+            .collect();
+        return format!("{}…", truncated);
+    }
+    value.to_string()
+}
+
+/// Quote a CSV field if it contains separators, quotes or line breaks, or if it is empty so it
+/// can't be confused with NULL.
+fn csv_field(value: &str) -> String {
+    if value.is_empty() || value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Return the value as a JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut output = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+impl fmt::Display for QueryResult {
+    /// Format the result as a psql like table.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(&DisplayOptions::default()))
+    }
+}
+
+/// Tag identifying the command completed by a statement, with the number of rows that it
+/// affected, like the CommandComplete message of Postgres.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandTag {
+    /// Name of the command, e.g `INSERT` or `CREATE TABLE`.
+    pub command: String,
+
+    /// Number of rows inserted or returned by the command, if it handles rows.
+    pub rows: Option<u64>,
+}
+
+impl CommandTag {
+    /// Create a tag of a command that does not handle rows.
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            rows: None,
+        }
+    }
+
+    /// Create a tag of a command that affected the given number of rows.
+    pub fn with_rows(command: &str, rows: u64) -> Self {
+        Self {
+            command: command.to_string(),
+            rows: Some(rows),
+        }
+    }
+}
+
+impl fmt::Display for CommandTag {
+    /// Format the tag like Postgres, e.g `INSERT 0 3`. The 0 of INSERT is the oid of the inserted
+    /// row on old Postgres versions, which is always 0 now.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.rows {
+            Some(rows) if self.command == "INSERT" => write!(f, "{} 0 {}", self.command, rows),
+            Some(rows) => write!(f, "{} {}", self.command, rows),
+            None => write!(f, "{}", self.command),
+        }
+    }
+}
+
+/// Result of a single statement.
+#[derive(Debug, PartialEq)]
+pub struct StatementResult {
+    /// Tag of the command completed by the statement.
+    pub tag: CommandTag,
+
+    /// Rows returned by the statement, which is empty unless the statement is a query.
+    pub results: Vec<QueryResult>,
+}
+
+impl StatementResult {
+    /// Create the result of a statement that returned no rows.
+    pub fn command(tag: CommandTag) -> Self {
+        Self {
+            tag,
+            results: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::pg_type::TEXT_OID;
+
+    #[test]
+    fn test_command_tag() {
+        assert_eq!(CommandTag::with_rows("INSERT", 3).to_string(), "INSERT 0 3");
+        assert_eq!(CommandTag::with_rows("SELECT", 2).to_string(), "SELECT 2");
+        assert_eq!(CommandTag::new("CREATE TABLE").to_string(), "CREATE TABLE");
+    }
+
+    #[test]
+    fn test_render_query_result() {
+        let result = QueryResult {
+            columns: vec![Column::new("id", INT4_OID), Column::new("name", TEXT_OID)],
+            rows: vec![
+                vec![String::from("1"), "x".repeat(50)],
+                vec![String::from("-20"), String::from("NULL")],
+            ],
+        };
+
+        let lines: Vec<String> = result
+            .render(&DisplayOptions::default())
+            .lines()
+            .map(|line| line.trim_end().to_string())
+            .collect();
+        assert_eq!(lines[2], format!("   1 | {}…", "x".repeat(39)));
+        assert_eq!(lines[3], " -20 | ∅");
+
+        let options = DisplayOptions {
+            expanded: true,
+            null: String::from("(null)"),
+            ..DisplayOptions::default()
+        };
+        assert_eq!(
+            result.render(&options),
+            format!(
+                "-[ RECORD 1 ]{}\nid   | 1\nname | {}\n-[ RECORD 2 ]{}\nid   | -20\nname | (null)\n",
+                "-".repeat(44),
+                "x".repeat(50),
+                "-".repeat(44)
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_expanded_wide_columns() {
+        let result = QueryResult {
+            columns: vec![Column::new("generate_series", INT8_OID)],
+            rows: vec![vec![String::from("1")]],
+        };
+        let options = DisplayOptions {
+            expanded: true,
+            ..DisplayOptions::default()
+        };
+        assert_eq!(
+            result.render(&options),
+            "-[ RECORD 1 ]---+--\ngenerate_series | 1\n"
+        );
+    }
+
+    #[test]
+    fn test_query_result_csv_and_json() {
+        let result = QueryResult {
+            columns: vec![
+                Column::new("id", INT4_OID),
+                Column::new("note", TEXT_OID),
+                Column::new("done", BOOL_OID),
+            ],
+            rows: vec![
+                vec![
+                    String::from("1"),
+                    String::from("say \"hi\", bye"),
+                    String::from("true"),
+                ],
+                vec![
+                    String::from("2"),
+                    String::from("NULL"),
+                    String::from("NULL"),
+                ],
+            ],
+        };
+        assert_eq!(
+            result.to_csv(),
+            "id,note,done\n1,\"say \"\"hi\"\", bye\",true\n2,,\n"
+        );
+        assert_eq!(
+            result.to_json(),
+            r#"[{"id":1,"note":"say \"hi\", bye","done":true},{"id":2,"note":null,"done":null}]"#
+        );
+        assert_eq!(QueryResult::default().to_json(), "[]");
+    }
+
+    #[test]
+    fn test_query_result_footer() {
+        let mut result = QueryResult {
+            columns: vec![Column::new("a", INT4_OID)],
+            rows: vec![vec![String::from("1")]],
+        };
+        assert_eq!(result.footer(), "(1 row)");
+        result.rows.clear();
+        assert_eq!(result.footer(), "(0 rows)");
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::{bail, Result};
+use log::debug;
+use sqlparser::ast::{self, ObjectName};
+
+use super::parser::Statement;
+use super::Error;
+
+/// Interface of rules applied by the rewrite phase, which transforms parsed statements before they
+/// are executed. It is where views would be expanded, and where embedders can restrict what each
+/// session can access, e.g redirecting each tenant of a multi-tenant application to its own
+/// relations.
+///
+/// Rewritten statements are still checked by the executor, so a rule can't make a read-only
+/// engine modify the database. WHERE clauses are not evaluated yet, so row filters can't be
+/// injected as predicates.
+pub trait RewriteRule {
+    /// Name of the rule, used to log the rules applied.
+    fn name(&self) -> &str;
+
+    /// Rewrite in place a statement executed on db_name. Returning error rejects the statement.
+    fn rewrite(&self, db_name: &str, stmt: &mut ast::Statement) -> Result<()>;
+}
+
+/// Hold the rewrite rules, which are applied to each statement in registration order.
+#[derive(Default)]
+pub struct Rewriter {
+    rules: Vec<Box<dyn RewriteRule>>,
+}
+
+impl Rewriter {
+    /// Register a new rewrite rule.
+    ///
+    /// Return error if another rule with the same name is already registered.
+    pub fn register(&mut self, rule: Box<dyn RewriteRule>) -> Result<()> {
+        if self.rules.iter().any(|other| other.name() == rule.name()) {
+            bail!(Error::DuplicateRewriteRule(rule.name().to_string()));
+        }
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    /// Apply all rules to the given statement. Statements not parsed by sqlparser are not
+    /// rewritten.
+    pub fn rewrite(&self, db_name: &str, stmt: Statement) -> Result<Statement> {
+        match stmt {
+            Statement::Sql(mut stmt) => {
+                for rule in &self.rules {
+                    rule.rewrite(db_name, &mut stmt)?;
+                    debug!("rewrite rule {} applied: {}", rule.name(), stmt);
+                }
+                Ok(Statement::Sql(stmt))
+            }
+            stmt => Ok(stmt),
+        }
+    }
+}
+
+/// Call f with the name of each relation that the statement reads or inserts into, so rules can
+/// rename them. Names of functions called on FROM clause are not visited.
+pub fn visit_relations<F>(stmt: &mut ast::Statement, f: &mut F)
+where
+    F: FnMut(&mut ObjectName),
+{
+    match stmt {
+        ast::Statement::Query(query) => visit_query_relations(query, f),
+        ast::Statement::Insert {
+            table_name, source, ..
+        } => {
+            f(table_name);
+            visit_query_relations(source, f);
+        }
+        ast::Statement::CreateTable {
+            query: Some(query), ..
+        } => visit_query_relations(query, f),
+        _ => {}
+    }
+}
+
+/// Call f with the name of each relation on the FROM clause of query, including its joins and
+/// subqueries.
+fn visit_query_relations<F>(query: &mut ast::Query, f: &mut F)
+where
+    F: FnMut(&mut ObjectName),
+{
+    if let ast::SetExpr::Select(select) = &mut query.body {
+        for table in &mut select.from {
+            visit_table_factor(&mut table.relation, f);
+            for join in &mut table.joins {
+                visit_table_factor(&mut join.relation, f);
+            }
+        }
+    }
+}
+
+fn visit_table_factor<F>(table: &mut ast::TableFactor, f: &mut F)
+where
+    F: FnMut(&mut ObjectName),
+{
+    match table {
+        ast::TableFactor::Table { name, args, .. } if args.is_empty() => f(name),
+        ast::TableFactor::Derived { subquery, .. } => visit_query_relations(subquery, f),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::parser::parse_sql;
+
+    #[test]
+    fn test_visit_relations() -> Result<()> {
+        let sql = "INSERT INTO a(x) SELECT * FROM b JOIN (SELECT * FROM c) AS d ON true; \
+                   SELECT * FROM generate_series(1, 2); CREATE TABLE e AS SELECT * FROM f";
+
+        let mut names = Vec::new();
+        for parsed in parse_sql(sql)? {
+            if let Statement::Sql(mut stmt) = parsed.stmt {
+                visit_relations(&mut stmt, &mut |name| names.push(name.to_string()));
+            }
+        }
+        assert_eq!(names, vec!["a", "b", "c", "f"]);
+
+        Ok(())
+    }
+}
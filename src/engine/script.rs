@@ -0,0 +1,63 @@
+use super::parser::skip_comments;
+use super::result::StatementResult;
+
+/// What to do when a statement of a script fails, like the ON_ERROR_STOP variable of psql.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnError {
+    /// Stop executing the script on the first failed statement.
+    Stop,
+
+    /// Report the failed statement and continue with the next ones.
+    Continue,
+}
+
+/// Error of a failed statement of a script.
+#[derive(thiserror::Error, Debug)]
+#[error("line {line}: {error}")]
+pub struct StatementError {
+    /// Line of the script where the statement starts, starting at 1.
+    pub line: usize,
+
+    /// Text of the statement.
+    pub statement: String,
+
+    /// Error raised by the statement, with a [super::Diagnostic] context if the statement is
+    /// invalid.
+    pub error: anyhow::Error,
+}
+
+/// Outcome of each statement of a script executed by [super::Engine::exec_script], in the same
+/// order of the script.
+pub type ScriptResult = Vec<Result<StatementResult, StatementError>>;
+
+impl StatementError {
+    /// Create the error of the statement text at the given byte offset of script.
+    pub fn new(script: &str, offset: usize, text: &str, error: anyhow::Error) -> Self {
+        // Leading whitespaces and comments are part of the statement text, but the statement
+        // starts on the first line after them.
+        let statement = skip_comments(text).trim_end();
+        let start = offset + (text.len() - skip_comments(text).len());
+
+        Self {
+            line: script[..start].matches('\n').count() + 1,
+            statement: statement.to_string(),
+            error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn test_statement_error() {
+        let script = "SELECT 1;\n-- t\n  /* all */ SELECT * FROM t;";
+        let text = "\n-- t\n  /* all */ SELECT * FROM t";
+        let err = StatementError::new(script, 9, text, anyhow!("failed"));
+        assert_eq!(err.line, 3);
+        assert_eq!(err.statement, "SELECT * FROM t");
+        assert_eq!(err.to_string(), "line 3: failed");
+    }
+}
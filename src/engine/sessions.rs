@@ -0,0 +1,118 @@
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Result;
+
+use crate::storage::temp::process_alive;
+use crate::Oid;
+
+/// Name of the directory of db data where the sessions connected to each database are registered.
+pub const SESSIONS_DIR_NAME: &str = "pg_sessions";
+
+/// Counter of the sessions registered by this process, so their file names are unique across
+/// threads.
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(0);
+
+/// A session connected to a database, registered by a file on the sessions directory of db data
+/// until it is dropped. Files are named after the database oid, the id of the process and a
+/// counter, e.g `16384.1234.0`, so the sessions of all threads and processes using the db data
+/// are visible.
+///
+/// Files left by crashed processes are ignored on Linux. Other platforms can't tell whether a
+/// process is running, so these files should be removed by hand to drop their databases.
+#[derive(Debug)]
+pub struct Session {
+    path: PathBuf,
+}
+
+impl Session {
+    /// Register a new session connected to the given database.
+    pub fn connect(db_data: &str, db_oid: Oid) -> Result<Self> {
+        let dir = sessions_dir(db_data);
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!(
+            "{}.{}.{}",
+            db_oid,
+            process::id(),
+            NEXT_SESSION.fetch_add(1, Ordering::Relaxed)
+        ));
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Return the directory where sessions of the given db data are registered.
+pub fn sessions_dir(db_data: &str) -> PathBuf {
+    Path::new(db_data).join(SESSIONS_DIR_NAME)
+}
+
+/// Return the number of sessions of running processes connected to the given database.
+pub fn count(db_data: &str, db_oid: Oid) -> Result<usize> {
+    let dir = sessions_dir(db_data);
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut count = 0;
+    for entry in fs::read_dir(&dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        let mut parts = name.split('.');
+        let oid = parts.next().and_then(|oid| oid.parse::<Oid>().ok());
+        let pid = parts.next().and_then(|pid| pid.parse::<u32>().ok());
+        if let (Some(oid), Some(pid)) = (oid, pid) {
+            if oid == db_oid && process_alive(pid) {
+                count += 1;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sessions() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_data = db_data.path().to_string_lossy().to_string();
+        assert_eq!(count(&db_data, 1)?, 0);
+
+        let session = Session::connect(&db_data, 1)?;
+        let other = std::thread::spawn({
+            let db_data = db_data.clone();
+            move || Session::connect(&db_data, 1)
+        })
+        .join()
+        .unwrap()?;
+        Session::connect(&db_data, 2)?;
+        assert_eq!(count(&db_data, 1)?, 2);
+        assert_eq!(count(&db_data, 2)?, 0);
+
+        // Sessions of processes that are not running are ignored.
+        fs::write(sessions_dir(&db_data).join("1.0.0"), b"")?;
+        let sessions = if cfg!(target_os = "linux") { 2 } else { 3 };
+        assert_eq!(count(&db_data, 1)?, sessions);
+
+        drop(session);
+        drop(other);
+        assert_eq!(count(&db_data, 1)?, sessions - 2);
+
+        Ok(())
+    }
+}
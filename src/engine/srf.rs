@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use sqlparser::ast;
+
+use crate::catalog::pg_type::{BOOL_OID, INT4_OID, INT8_OID, TEXT_OID};
+use crate::Oid;
+
+use super::result::{Column, QueryResult};
+use super::Error;
+
+/// A constant argument of a function called on FROM clause.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    /// A single literal value.
+    Value(ast::Value),
+
+    /// An array literal, e.g ARRAY[1, 2, 3].
+    Array(Vec<ast::Value>),
+}
+
+/// Interface of functions that return a set of rows, so they can be used as a row source on FROM
+/// clause by a function scan.
+pub trait SetReturningFunction {
+    /// Name used to call the function.
+    fn name(&self) -> &str;
+
+    /// Execute the function with the given arguments and return all rows that it produces.
+    fn call(&self, args: &[Arg]) -> Result<QueryResult>;
+}
+
+/// Builtin generate_series(start, stop[, step]) over integers, implementing both the int4 and int8
+/// overloads.
+pub struct GenerateSeries;
+
+impl SetReturningFunction for GenerateSeries {
+    fn name(&self) -> &str {
+        "generate_series"
+    }
+
+    fn call(&self, args: &[Arg]) -> Result<QueryResult> {
+        let mut ints = Vec::new();
+        for arg in args {
+            match arg {
+                Arg::Value(ast::Value::Number(value, _)) => ints.push(value.parse::<i64>()?),
+                _ => bail!(Error::InvalidArguments(self.name().to_string())),
+            }
+        }
+        match ints.as_slice() {
+            [start, stop] => generate_series(*start, *stop, 1),
+            [start, stop, step] => generate_series(*start, *stop, *step),
+            _ => bail!(Error::InvalidArguments(self.name().to_string())),
+        }
+    }
+}
+
+/// Builtin unnest(array[, ...]) that expands arrays to rows.
+pub struct Unnest;
+
+impl SetReturningFunction for Unnest {
+    fn name(&self) -> &str {
+        "unnest"
+    }
+
+    fn call(&self, args: &[Arg]) -> Result<QueryResult> {
+        let mut arrays = Vec::new();
+        for arg in args {
+            match arg {
+                Arg::Array(values) => arrays.push(values),
+                _ => bail!(Error::InvalidArguments(self.name().to_string())),
+            }
+        }
+        if arrays.is_empty() {
+            bail!(Error::InvalidArguments(self.name().to_string()));
+        }
+
+        // Like Postgres, multiple arrays are expanded side by side, and shorter arrays are padded
+        // with nulls.
+        let total_rows = arrays.iter().map(|values| values.len()).max().unwrap_or(0);
+        let rows = (0..total_rows)
+            .map(|i| {
+                arrays
+                    .iter()
+                    .map(|values| match values.get(i) {
+                        Some(value) => value_to_string(value),
+                        None => String::from("NULL"),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(QueryResult {
+            columns: arrays
+                .iter()
+                .map(|values| Column::new(self.name(), element_type(values)))
+                .collect(),
+            rows,
+        })
+    }
+}
+
+/// Hold all set-returning functions available, indexed by their name.
+pub struct FunctionRegistry {
+    functions: HashMap<String, Box<dyn SetReturningFunction>>,
+}
+
+impl FunctionRegistry {
+    /// Register a new set-returning function.
+    ///
+    /// Return error if another function with the same name is already registered.
+    pub fn register(&mut self, function: Box<dyn SetReturningFunction>) -> Result<()> {
+        let name = function.name().to_string();
+        if self.functions.contains_key(&name) {
+            bail!(Error::DuplicateFunction(name));
+        }
+        self.functions.insert(name, function);
+        Ok(())
+    }
+
+    /// Return true if a set-returning function with the given name is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Return the set-returning function of the given name.
+    pub fn get(&self, name: &str) -> Result<&dyn SetReturningFunction> {
+        match self.functions.get(name) {
+            Some(function) => Ok(function.as_ref()),
+            None => bail!(Error::FunctionNotFound(name.to_string())),
+        }
+    }
+}
+
+impl Default for FunctionRegistry {
+    /// Create a new registry containing the builtin set-returning functions.
+    fn default() -> Self {
+        let mut functions: HashMap<String, Box<dyn SetReturningFunction>> = HashMap::new();
+        for function in [
+            Box::new(GenerateSeries) as Box<dyn SetReturningFunction>,
+            Box::new(Unnest),
+        ] {
+            functions.insert(function.name().to_string(), function);
+        }
+        Self { functions }
+    }
+}
+
+/// Return a single column with the integer values from start to stop, both inclusive, incremented
+/// by step, as Postgres generate_series does.
+///
+/// Return error if step is zero. If step moves away from stop no rows are returned.
+pub fn generate_series(start: i64, stop: i64, step: i64) -> Result<QueryResult> {
+    if step == 0 {
+        bail!(Error::InvalidArguments(String::from(
+            "generate_series: step size cannot equal zero"
+        )));
+    }
+
+    let mut rows = Vec::new();
+    let mut value = start;
+    while (step > 0 && value <= stop) || (step < 0 && value >= stop) {
+        rows.push(vec![value.to_string()]);
+        value = match value.checked_add(step) {
+            Some(value) => value,
+            None => break,
+        };
+    }
+
+    Ok(QueryResult {
+        columns: vec![Column::new("generate_series", INT8_OID).not_null()],
+        rows,
+    })
+}
+
+/// Return the type of the elements of an array literal, like Postgres resolves the type of
+/// ARRAY[...]: integers are int4 unless some of them only fit on int8, and mixed elements are text.
+fn element_type(values: &[ast::Value]) -> Oid {
+    let values = values
+        .iter()
+        .filter(|value| !matches!(value, ast::Value::Null));
+    let mut typ = None;
+    for value in values {
+        let value_type = match value {
+            ast::Value::Number(n, _) if n.parse::<i32>().is_ok() => INT4_OID,
+            ast::Value::Number(n, _) if n.parse::<i64>().is_ok() => INT8_OID,
+            ast::Value::Boolean(_) => BOOL_OID,
+            _ => TEXT_OID,
+        };
+        typ = match (typ, value_type) {
+            (None, value_type) => Some(value_type),
+            (Some(INT4_OID), INT8_OID) | (Some(INT8_OID), INT4_OID) => Some(INT8_OID),
+            (Some(typ), value_type) if typ == value_type => Some(typ),
+            _ => Some(TEXT_OID),
+        };
+    }
+    typ.unwrap_or(TEXT_OID)
+}
+
+/// Return the text representation of a literal value, without quotes for strings.
+fn value_to_string(value: &ast::Value) -> String {
+    match value {
+        ast::Value::SingleQuotedString(value) => value.clone(),
+        ast::Value::Null => String::from("NULL"),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_series() -> Result<()> {
+        assert_eq!(
+            generate_series(1, 3, 1)?.rows,
+            vec![vec!["1"], vec!["2"], vec!["3"]]
+        );
+        assert_eq!(
+            generate_series(5, 1, -2)?.rows,
+            vec![vec!["5"], vec!["3"], vec!["1"]]
+        );
+        assert!(generate_series(3, 1, 1)?.rows.is_empty());
+        assert!(generate_series(1, 3, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unnest() -> Result<()> {
+        let number = |n: &str| ast::Value::Number(n.to_string(), false);
+        let result = FunctionRegistry::default().get("unnest")?.call(&[
+            Arg::Array(vec![number("1"), number("2")]),
+            Arg::Array(vec![ast::Value::SingleQuotedString(String::from("a"))]),
+        ])?;
+
+        assert_eq!(result.column_names(), vec!["unnest", "unnest"]);
+        assert_eq!(result.columns[0].type_oid, INT4_OID);
+        assert_eq!(result.columns[1].type_oid, TEXT_OID);
+        assert_eq!(result.rows, vec![vec!["1", "a"], vec!["2", "NULL"]]);
+        Ok(())
+    }
+}
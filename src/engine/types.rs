@@ -0,0 +1,279 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use anyhow::{bail, Result};
+
+use crate::catalog::pg_type::{format_type, BOOL_OID, INT4_OID, INT8_OID, TEXT_OID};
+use crate::Oid;
+
+use super::Error;
+
+/// Support functions of a data type, which convert its values between the internal
+/// representation stored on tuples and the text and binary formats used by clients, like the
+/// typinput, typoutput, typsend and typreceive functions of Postgres types.
+pub trait TypeIo {
+    /// Oid of the type on pg_type.
+    fn oid(&self) -> Oid;
+
+    /// Parse a value from its text representation into its internal representation.
+    fn input(&self, text: &str) -> Result<Vec<u8>>;
+
+    /// Format a value in internal representation as text.
+    fn output(&self, data: &[u8]) -> Result<String>;
+
+    /// Encode a value in internal representation with the binary format of the wire protocol,
+    /// which uses network byte order.
+    fn send(&self, data: &[u8]) -> Result<Vec<u8>>;
+
+    /// Decode a value in binary format into its internal representation.
+    fn receive(&self, data: &[u8]) -> Result<Vec<u8>>;
+
+    /// Compare two values in internal representation.
+    fn compare(&self, left: &[u8], right: &[u8]) -> Result<Ordering>;
+}
+
+/// Builtin int4 type, stored as 4 little endian bytes.
+pub struct Int4;
+
+impl TypeIo for Int4 {
+    fn oid(&self) -> Oid {
+        INT4_OID
+    }
+
+    fn input(&self, text: &str) -> Result<Vec<u8>> {
+        match text.trim().parse::<i32>() {
+            Ok(value) => Ok(bincode::serialize(&value)?),
+            Err(_) => bail!(Error::InvalidInteger(text.to_string())),
+        }
+    }
+
+    fn output(&self, data: &[u8]) -> Result<String> {
+        Ok(bincode::deserialize::<i32>(data)?.to_string())
+    }
+
+    fn send(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(bincode::deserialize::<i32>(data)?.to_be_bytes().to_vec())
+    }
+
+    fn receive(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match data.try_into() {
+            Ok(bytes) => Ok(bincode::serialize(&i32::from_be_bytes(bytes))?),
+            Err(_) => bail!(Error::InvalidBinaryFormat(format_type(self.oid()))),
+        }
+    }
+
+    fn compare(&self, left: &[u8], right: &[u8]) -> Result<Ordering> {
+        let left = bincode::deserialize::<i32>(left)?;
+        let right = bincode::deserialize::<i32>(right)?;
+        Ok(left.cmp(&right))
+    }
+}
+
+/// Builtin int8 type, stored as 8 little endian bytes.
+pub struct Int8;
+
+impl TypeIo for Int8 {
+    fn oid(&self) -> Oid {
+        INT8_OID
+    }
+
+    fn input(&self, text: &str) -> Result<Vec<u8>> {
+        match text.trim().parse::<i64>() {
+            Ok(value) => Ok(bincode::serialize(&value)?),
+            Err(_) => bail!(Error::InvalidInteger(text.to_string())),
+        }
+    }
+
+    fn output(&self, data: &[u8]) -> Result<String> {
+        Ok(bincode::deserialize::<i64>(data)?.to_string())
+    }
+
+    fn send(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(bincode::deserialize::<i64>(data)?.to_be_bytes().to_vec())
+    }
+
+    fn receive(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match data.try_into() {
+            Ok(bytes) => Ok(bincode::serialize(&i64::from_be_bytes(bytes))?),
+            Err(_) => bail!(Error::InvalidBinaryFormat(format_type(self.oid()))),
+        }
+    }
+
+    fn compare(&self, left: &[u8], right: &[u8]) -> Result<Ordering> {
+        let left = bincode::deserialize::<i64>(left)?;
+        let right = bincode::deserialize::<i64>(right)?;
+        Ok(left.cmp(&right))
+    }
+}
+
+/// Builtin bool type, stored as a single byte.
+pub struct Bool;
+
+impl TypeIo for Bool {
+    fn oid(&self) -> Oid {
+        BOOL_OID
+    }
+
+    /// Accept the same spellings of Postgres, e.g `t`, `yes` or `off`.
+    fn input(&self, text: &str) -> Result<Vec<u8>> {
+        let value = match text.trim().to_lowercase().as_str() {
+            "t" | "true" | "yes" | "on" | "1" => true,
+            "f" | "false" | "no" | "off" | "0" => false,
+            _ => bail!(Error::InvalidBoolean(text.to_string())),
+        };
+        Ok(bincode::serialize(&value)?)
+    }
+
+    fn output(&self, data: &[u8]) -> Result<String> {
+        Ok(bincode::deserialize::<bool>(data)?.to_string())
+    }
+
+    fn send(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(vec![bincode::deserialize::<bool>(data)? as u8])
+    }
+
+    /// Like Postgres, any byte other than zero is true.
+    fn receive(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match data {
+            [byte] => Ok(bincode::serialize(&(*byte != 0))?),
+            _ => bail!(Error::InvalidBinaryFormat(format_type(self.oid()))),
+        }
+    }
+
+    fn compare(&self, left: &[u8], right: &[u8]) -> Result<Ordering> {
+        let left = bincode::deserialize::<bool>(left)?;
+        let right = bincode::deserialize::<bool>(right)?;
+        Ok(left.cmp(&right))
+    }
+}
+
+/// Builtin text type, stored as its length followed by its UTF-8 bytes.
+pub struct Text;
+
+impl TypeIo for Text {
+    fn oid(&self) -> Oid {
+        TEXT_OID
+    }
+
+    fn input(&self, text: &str) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(text)?)
+    }
+
+    fn output(&self, data: &[u8]) -> Result<String> {
+        Ok(bincode::deserialize::<String>(data)?)
+    }
+
+    fn send(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(bincode::deserialize::<String>(data)?.into_bytes())
+    }
+
+    fn receive(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match std::str::from_utf8(data) {
+            Ok(text) => Ok(bincode::serialize(text)?),
+            Err(_) => bail!(Error::InvalidBinaryFormat(format_type(self.oid()))),
+        }
+    }
+
+    /// Texts are compared byte by byte, since there are no collations yet.
+    fn compare(&self, left: &[u8], right: &[u8]) -> Result<Ordering> {
+        let left = bincode::deserialize::<String>(left)?;
+        let right = bincode::deserialize::<String>(right)?;
+        Ok(left.cmp(&right))
+    }
+}
+
+/// Hold the support functions of all data types available, indexed by the type oid.
+pub struct TypeRegistry {
+    types: HashMap<Oid, Box<dyn TypeIo>>,
+}
+
+impl TypeRegistry {
+    /// Register the support functions of a new data type.
+    ///
+    /// Return error if the type oid is already registered.
+    pub fn register(&mut self, typ: Box<dyn TypeIo>) -> Result<()> {
+        let oid = typ.oid();
+        if self.types.contains_key(&oid) {
+            bail!(Error::DuplicateType(format_type(oid)));
+        }
+        self.types.insert(oid, typ);
+        Ok(())
+    }
+
+    /// Return the support functions of the given type oid.
+    pub fn get(&self, oid: Oid) -> Result<&dyn TypeIo> {
+        match self.types.get(&oid) {
+            Some(typ) => Ok(typ.as_ref()),
+            None => bail!(Error::TypeNotFound(format_type(oid))),
+        }
+    }
+}
+
+impl Default for TypeRegistry {
+    /// Create a new registry containing the builtin data types.
+    fn default() -> Self {
+        let mut types: HashMap<Oid, Box<dyn TypeIo>> = HashMap::new();
+        for typ in [
+            Box::new(Int4) as Box<dyn TypeIo>,
+            Box::new(Int8),
+            Box::new(Bool),
+            Box::new(Text),
+        ] {
+            types.insert(typ.oid(), typ);
+        }
+        Self { types }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_types() -> Result<()> {
+        let registry = TypeRegistry::default();
+
+        let int4 = registry.get(INT4_OID)?;
+        let value = int4.input(" -42 ")?;
+        assert_eq!(int4.output(&value)?, "-42");
+        assert_eq!(int4.send(&value)?, vec![0xff, 0xff, 0xff, 0xd6]);
+        assert_eq!(int4.receive(&int4.send(&value)?)?, value);
+        assert_eq!(int4.compare(&value, &int4.input("7")?)?, Ordering::Less);
+        assert!(int4.input("2147483648").is_err());
+        assert!(int4.receive(&[0, 1]).is_err());
+
+        let int8 = registry.get(INT8_OID)?;
+        let value = int8.input("2147483648")?;
+        assert_eq!(int8.send(&value)?, vec![0, 0, 0, 0, 0x80, 0, 0, 0]);
+        assert_eq!(
+            int8.output(&int8.receive(&int8.send(&value)?)?)?,
+            "2147483648"
+        );
+
+        let bool = registry.get(BOOL_OID)?;
+        assert_eq!(bool.output(&bool.input("yes")?)?, "true");
+        assert_eq!(bool.output(&bool.receive(&[2])?)?, "true");
+        assert_eq!(bool.send(&bool.input("off")?)?, vec![0]);
+        assert!(bool.input("maybe").is_err());
+
+        let text = registry.get(TEXT_OID)?;
+        let value = text.input("tinydb")?;
+        assert_eq!(text.output(&value)?, "tinydb");
+        assert_eq!(text.send(&value)?, b"tinydb");
+        assert_eq!(
+            text.compare(&value, &text.input("sqlite")?)?,
+            Ordering::Greater
+        );
+        assert!(text.receive(&[0xff]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_type() {
+        let mut registry = TypeRegistry::default();
+        assert!(registry.register(Box::new(Int4)).is_err());
+        assert!(registry.get(0).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::{bail, Result};
+
+use crate::catalog::pg_type::{INT4_OID, TEXT_OID};
+use crate::catalog::DatabaseCatalog;
+use crate::storage::BufferPool;
+
+use super::guc::{PgSettings, Setting};
+use super::pgstat::{PgStatProgress, PgStatUserTables, StatsCollector};
+use super::result::{Column, QueryResult};
+use super::Error;
+
+/// Schema of the builtin virtual tables, which are found without qualifying their names.
+pub const PG_CATALOG: &str = "pg_catalog";
+
+/// Schema of the virtual tables of the SQL standard information schema.
+pub const INFORMATION_SCHEMA: &str = "information_schema";
+
+/// Schema where user relations are created, since there are no schemas yet.
+const PUBLIC_SCHEMA: &str = "public";
+
+/// Rows produced by a virtual table scan.
+pub type Rows = Box<dyn Iterator<Item = Vec<String>>>;
+
+/// State of the engine available to virtual tables while they are scanned.
+pub struct ScanContext<'a> {
+    pub buffer_pool: &'a mut BufferPool,
+
+    /// Catalogs of the database that the session is connected to.
+    pub database: &'a DatabaseCatalog,
+
+    /// Activity counters of the relations accessed by the engine.
+    pub stats: &'a StatsCollector,
+
+    /// Runtime settings of the engine.
+    pub settings: &'a [Setting],
+}
+
+/// Interface of synthetic relations that are computed when scanned instead of being stored on heap
+/// files, like the pg_stat views, pg_settings and the information schema.
+pub trait VirtualTable {
+    /// Name used to query the table.
+    fn name(&self) -> &str;
+
+    /// Schema of the table. Only tables of [PG_CATALOG] can be queried without the schema.
+    fn schema(&self) -> &str {
+        PG_CATALOG
+    }
+
+    /// Return the columns of the rows produced by the table.
+    fn columns(&self) -> Vec<Column>;
+
+    /// Return the rows of the table, with one value for each column.
+    fn scan(&self, ctx: &mut ScanContext) -> Result<Rows>;
+}
+
+/// Scan all rows of the given virtual table.
+pub fn scan(table: &dyn VirtualTable, ctx: &mut ScanContext) -> Result<QueryResult> {
+    Ok(QueryResult {
+        columns: table.columns(),
+        rows: table.scan(ctx)?.collect(),
+    })
+}
+
+/// Hold all virtual tables available.
+pub struct VirtualTableRegistry {
+    tables: Vec<Box<dyn VirtualTable>>,
+}
+
+impl VirtualTableRegistry {
+    /// Register a new virtual table.
+    ///
+    /// Return error if another virtual table with the same schema and name is already registered.
+    pub fn register(&mut self, table: Box<dyn VirtualTable>) -> Result<()> {
+        if self.get(Some(table.schema()), table.name()).is_some() {
+            bail!(Error::DuplicateRelation(format!(
+                "{}.{}",
+                table.schema(),
+                table.name()
+            )));
+        }
+        self.tables.push(table);
+        Ok(())
+    }
+
+    /// Return the virtual table of the given name. Without schema, only tables of [PG_CATALOG] are
+    /// found, like the search path of Postgres.
+    pub fn get(&self, schema: Option<&str>, name: &str) -> Option<&dyn VirtualTable> {
+        let schema = schema.unwrap_or(PG_CATALOG);
+        self.tables
+            .iter()
+            .find(|table| table.schema() == schema && table.name() == name)
+            .map(|table| table.as_ref())
+    }
+}
+
+impl Default for VirtualTableRegistry {
+    /// Create a new registry containing the builtin virtual tables.
+    fn default() -> Self {
+        Self {
+            tables: vec![
+                Box::new(PgSettings),
+                Box::new(PgStatUserTables),
+                Box::new(PgStatProgress),
+                Box::new(InformationSchemaTables),
+                Box::new(InformationSchemaColumns),
+            ],
+        }
+    }
+}
+
+/// The information_schema.tables view, with one row for each relation of the current database.
+pub struct InformationSchemaTables;
+
+impl VirtualTable for InformationSchemaTables {
+    fn name(&self) -> &str {
+        "tables"
+    }
+
+    fn schema(&self) -> &str {
+        INFORMATION_SCHEMA
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![
+            Column::new("table_catalog", TEXT_OID).not_null(),
+            Column::new("table_schema", TEXT_OID).not_null(),
+            Column::new("table_name", TEXT_OID).not_null(),
+            Column::new("table_type", TEXT_OID).not_null(),
+        ]
+    }
+
+    fn scan(&self, ctx: &mut ScanContext) -> Result<Rows> {
+        let db_name = ctx.database.db_name().to_string();
+        let rows: Vec<Vec<String>> = ctx
+            .database
+            .get_relations(ctx.buffer_pool)?
+            .into_iter()
+            .map(|rel| {
+                vec![
+                    db_name.clone(),
+                    PUBLIC_SCHEMA.to_string(),
+                    rel.relname,
+                    String::from("BASE TABLE"),
+                ]
+            })
+            .collect();
+        Ok(Box::new(rows.into_iter()))
+    }
+}
+
+/// The information_schema.columns view, with one row for each column of the relations of the
+/// current database.
+pub struct InformationSchemaColumns;
+
+impl VirtualTable for InformationSchemaColumns {
+    fn name(&self) -> &str {
+        "columns"
+    }
+
+    fn schema(&self) -> &str {
+        INFORMATION_SCHEMA
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        vec![
+            Column::new("table_catalog", TEXT_OID).not_null(),
+            Column::new("table_schema", TEXT_OID).not_null(),
+            Column::new("table_name", TEXT_OID).not_null(),
+            Column::new("column_name", TEXT_OID).not_null(),
+            Column::new("ordinal_position", INT4_OID).not_null(),
+            Column::new("is_nullable", TEXT_OID).not_null(),
+            Column::new("data_type", TEXT_OID).not_null(),
+        ]
+    }
+
+    fn scan(&self, ctx: &mut ScanContext) -> Result<Rows> {
+        let db_name = ctx.database.db_name().to_string();
+        let mut rows = Vec::new();
+        for rel in ctx.database.get_relations(ctx.buffer_pool)? {
+            let attributes = ctx
+                .database
+                .get_attributes_from_relation(ctx.buffer_pool, &rel.relname)?;
+            for attr in attributes {
+                // All user columns are nullable int4, the only type supported yet.
+                rows.push(vec![
+                    db_name.clone(),
+                    PUBLIC_SCHEMA.to_string(),
+                    rel.relname.clone(),
+                    attr.attname,
+                    (attr.attnum + 1).to_string(),
+                    String::from("YES"),
+                    String::from("integer"),
+                ]);
+            }
+        }
+        Ok(Box::new(rows.into_iter()))
+    }
+}
@@ -0,0 +1,369 @@
+use std::cell::RefCell;
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+use anyhow::{bail, Result};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::storage::temp::TempFile;
+
+use super::Error;
+
+/// Default memory budget of each operator, 4MB like the default work_mem of Postgres.
+pub const DEFAULT_WORK_MEM: usize = 4 * 1024 * 1024;
+
+/// Smallest memory budget allowed, like the 64kB minimum of Postgres.
+pub const MIN_WORK_MEM: usize = 64 * 1024;
+
+/// Parse a work_mem value, e.g `64kB`, `4MB` or `1GB`. Like Postgres, values without unit are in
+/// kilobytes.
+///
+/// Return error if the value can't be parsed or it is below [MIN_WORK_MEM].
+pub fn parse_work_mem(value: &str) -> Result<usize> {
+    let value = value.trim();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits);
+    let multiplier = match unit.trim() {
+        "" | "kB" => 1024,
+        "MB" => 1024 * 1024,
+        "GB" => 1024 * 1024 * 1024,
+        _ => bail!(Error::InvalidWorkMem(value.to_string())),
+    };
+
+    match number
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+    {
+        Some(bytes) if bytes >= MIN_WORK_MEM => Ok(bytes),
+        _ => bail!(Error::InvalidWorkMem(value.to_string())),
+    }
+}
+
+/// Format a number of bytes as a work_mem value, using the largest unit that divides it.
+pub fn format_work_mem(bytes: usize) -> String {
+    // Units are powers of two, so they divide the bytes if their low bits are zero.
+    for (unit, size) in [("GB", 1024 * 1024 * 1024), ("MB", 1024 * 1024)] {
+        if bytes >= size && bytes & (size - 1) == 0 {
+            return format!("{}{}", bytes / size, unit);
+        }
+    }
+    format!("{}kB", bytes / 1024)
+}
+
+/// Memory usage tracked by an accountant.
+#[derive(Default)]
+struct Usage {
+    /// Memory budget of each operator, in bytes.
+    work_mem: usize,
+
+    /// Memory currently reserved by all operators, in bytes.
+    used: usize,
+
+    /// Highest memory reserved at once since the accountant was created, in bytes.
+    peak: usize,
+
+    /// Number of times that operators spilled their data to disk.
+    spills: u64,
+}
+
+/// Central accountant of the memory used by operators that hold many rows, like sorts, hash
+/// tables and aggregations. Each operator reserves its memory through a [Reservation], which is
+/// limited by work_mem, and spills to disk instead of growing beyond it.
+///
+/// Clones share the same usage, so the accountant can be handed to each operator of a statement.
+#[derive(Clone)]
+pub struct MemoryAccountant {
+    usage: Rc<RefCell<Usage>>,
+}
+
+impl MemoryAccountant {
+    /// Create a new accountant limiting each operator to work_mem bytes.
+    pub fn new(work_mem: usize) -> Self {
+        Self {
+            usage: Rc::new(RefCell::new(Usage {
+                work_mem,
+                ..Usage::default()
+            })),
+        }
+    }
+
+    /// Return the memory budget of each operator, in bytes.
+    pub fn work_mem(&self) -> usize {
+        self.usage.borrow().work_mem
+    }
+
+    /// Change the memory budget of the operators. Reservations already made are kept, but can't
+    /// grow until they are below the new budget.
+    pub fn set_work_mem(&self, work_mem: usize) {
+        self.usage.borrow_mut().work_mem = work_mem;
+    }
+
+    /// Return the memory currently reserved by all operators, in bytes.
+    pub fn used(&self) -> usize {
+        self.usage.borrow().used
+    }
+
+    /// Return the highest memory reserved at once by all operators, in bytes.
+    pub fn peak(&self) -> usize {
+        self.usage.borrow().peak
+    }
+
+    /// Return the number of times that operators spilled to disk.
+    pub fn spills(&self) -> u64 {
+        self.usage.borrow().spills
+    }
+
+    /// Start tracking the memory of a new operator.
+    pub fn reservation(&self) -> Reservation {
+        Reservation {
+            accountant: self.clone(),
+            size: 0,
+        }
+    }
+}
+
+/// Memory reserved by a single operator, released when it is dropped.
+pub struct Reservation {
+    accountant: MemoryAccountant,
+
+    /// Bytes reserved by the operator.
+    size: usize,
+}
+
+impl Reservation {
+    /// Return the bytes reserved by the operator.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Reserve more bytes for the operator.
+    ///
+    /// Return false, reserving nothing, if the operator would use more than work_mem. The operator
+    /// should then spill its data to disk and free its memory.
+    pub fn try_grow(&mut self, bytes: usize) -> bool {
+        let mut usage = self.accountant.usage.borrow_mut();
+        if self.size + bytes > usage.work_mem {
+            return false;
+        }
+        self.size += bytes;
+        usage.used += bytes;
+        usage.peak = usage.peak.max(usage.used);
+        true
+    }
+
+    /// Release bytes no longer used by the operator.
+    pub fn shrink(&mut self, bytes: usize) {
+        let bytes = bytes.min(self.size);
+        self.size -= bytes;
+        self.accountant.usage.borrow_mut().used -= bytes;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.shrink(self.size);
+    }
+}
+
+/// Function that creates the temporary file where an operator spills its data.
+pub type SpillFileFactory = Box<dyn FnMut() -> io::Result<TempFile>>;
+
+/// Buffer of values that keeps them in memory while they fit on work_mem, spilling them to a file
+/// once they don't. Values are read back in the order that they were pushed, so operators can
+/// materialize any number of rows with bounded memory.
+///
+/// Values are accounted by their serialized size, which is also the space they use once spilled.
+pub struct SpillBuffer<T> {
+    reservation: Reservation,
+
+    /// Values pushed after the last spill.
+    values: Vec<T>,
+
+    /// File with the spilled values, created by the first spill.
+    file: Option<BufWriter<TempFile>>,
+
+    /// Number of values on the spill file.
+    spilled: usize,
+
+    create_file: SpillFileFactory,
+}
+
+impl<T: Serialize + DeserializeOwned> SpillBuffer<T> {
+    /// Create a new empty buffer accounted by the given accountant. The spill file is only
+    /// created if the values don't fit on work_mem.
+    pub fn new(accountant: &MemoryAccountant, create_file: SpillFileFactory) -> Self {
+        Self {
+            reservation: accountant.reservation(),
+            values: Vec::new(),
+            file: None,
+            spilled: 0,
+            create_file,
+        }
+    }
+
+    /// Return the number of values pushed.
+    pub fn len(&self) -> usize {
+        self.spilled + self.values.len()
+    }
+
+    /// Return true if no value was pushed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return true if some values were spilled to disk.
+    pub fn is_spilled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Add a value at the end of the buffer, spilling the values in memory if it does not fit on
+    /// work_mem. A single value larger than work_mem is written directly to the spill file.
+    pub fn push(&mut self, value: T) -> Result<()> {
+        let size = bincode::serialized_size(&value)? as usize;
+        if !self.reservation.try_grow(size) {
+            self.spill()?;
+            if !self.reservation.try_grow(size) {
+                return self.write_value(&value);
+            }
+        }
+        self.values.push(value);
+        Ok(())
+    }
+
+    /// Call function f to each value in the order that they were pushed, consuming the buffer.
+    pub fn drain<F>(mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(T) -> Result<()>,
+    {
+        if let Some(file) = self.file.take() {
+            let mut file = file.into_inner()?;
+            file.seek(SeekFrom::Start(0))?;
+            let mut reader = BufReader::new(file);
+            for _ in 0..self.spilled {
+                f(bincode::deserialize_from(&mut reader)?)?;
+            }
+        }
+
+        for value in std::mem::take(&mut self.values) {
+            f(value)?;
+        }
+        Ok(())
+    }
+
+    /// Write all values in memory to the spill file, releasing their memory.
+    fn spill(&mut self) -> Result<()> {
+        for value in std::mem::take(&mut self.values) {
+            self.write_value(&value)?;
+        }
+        self.reservation.shrink(self.reservation.size());
+        self.reservation.accountant.usage.borrow_mut().spills += 1;
+        Ok(())
+    }
+
+    /// Append a single value to the spill file, creating it if needed.
+    fn write_value(&mut self, value: &T) -> Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(BufWriter::new((self.create_file)()?)),
+        };
+        bincode::serialize_into(file.by_ref(), value)?;
+        self.spilled += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_work_mem() -> Result<()> {
+        assert_eq!(parse_work_mem("4MB")?, DEFAULT_WORK_MEM);
+        assert_eq!(parse_work_mem("64kB")?, MIN_WORK_MEM);
+        assert_eq!(parse_work_mem("1024")?, 1024 * 1024);
+        assert_eq!(parse_work_mem(" 1 GB ")?, 1024 * 1024 * 1024);
+        assert!(parse_work_mem("32kB").is_err());
+        assert!(parse_work_mem("4mb").is_err());
+        assert!(parse_work_mem("MB").is_err());
+
+        assert_eq!(format_work_mem(DEFAULT_WORK_MEM), "4MB");
+        assert_eq!(format_work_mem(1536 * 1024), "1536kB");
+        assert_eq!(format_work_mem(2 * 1024 * 1024 * 1024), "2GB");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reservation() {
+        let accountant = MemoryAccountant::new(100);
+        let mut sort = accountant.reservation();
+        let mut hash = accountant.reservation();
+
+        assert!(sort.try_grow(60));
+        assert!(!sort.try_grow(41));
+        assert!(hash.try_grow(100));
+        assert_eq!(accountant.used(), 160);
+
+        sort.shrink(60);
+        assert_eq!(sort.size(), 0);
+        drop(hash);
+        assert_eq!(accountant.used(), 0);
+        assert_eq!(accountant.peak(), 160);
+    }
+
+    #[test]
+    fn test_spill_buffer() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_data = db_data.path().to_string_lossy().to_string();
+
+        let accountant = MemoryAccountant::new(64);
+        let files = Rc::new(RefCell::new(Vec::new()));
+        let created = files.clone();
+        let spill_db_data = db_data.clone();
+        let mut buffer = SpillBuffer::new(
+            &accountant,
+            Box::new(move || {
+                let file = TempFile::create(&spill_db_data)?;
+                created.borrow_mut().push(file.path().to_path_buf());
+                Ok(file)
+            }),
+        );
+
+        // Each row is serialized as its length and 3 values of 8 bytes, 32 bytes in total.
+        for i in 0..5_i64 {
+            buffer.push(vec![i, i * 10, i * 100])?;
+            assert!(accountant.used() <= accountant.work_mem());
+        }
+        assert_eq!(buffer.len(), 5);
+        assert!(buffer.is_spilled());
+        assert_eq!(accountant.spills(), 2);
+
+        // Rows larger than work_mem go straight to disk.
+        buffer.push((0..10).collect())?;
+
+        let mut rows = Vec::new();
+        buffer.drain(|row| {
+            rows.push(row);
+            Ok(())
+        })?;
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[4], vec![4, 40, 400]);
+        assert_eq!(rows[5].len(), 10);
+        assert_eq!(accountant.used(), 0);
+
+        // The spill file is removed once the buffer is drained.
+        assert_eq!(files.borrow().len(), 1);
+        assert!(!files.borrow()[0].exists());
+
+        let buffer =
+            SpillBuffer::<i32>::new(&accountant, Box::new(move || TempFile::create(&db_data)));
+        assert!(buffer.is_empty());
+        assert!(!buffer.is_spilled());
+
+        Ok(())
+    }
+}
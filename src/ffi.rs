@@ -0,0 +1,424 @@
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+use anyhow::{anyhow, Result};
+
+use crate::engine::{CommandTag, Engine, QueryResult, DEFAULT_BUFFER_POOL_SIZE};
+use crate::initdb::init_database;
+use crate::storage::BufferPool;
+use crate::Oid;
+
+/// Returned when a function succeeds.
+pub const TINYDB_OK: c_int = 0;
+
+/// Returned when a function fails. The error message is available through [tinydb_errmsg].
+pub const TINYDB_ERROR: c_int = 1;
+
+/// Returned by [tinydb_result_next] when there is a row available.
+pub const TINYDB_ROW: c_int = 100;
+
+/// Returned by [tinydb_result_next] when all rows were already consumed.
+pub const TINYDB_DONE: c_int = 101;
+
+thread_local! {
+    /// Message of the last error that happened on the current thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A database connection opened by [tinydb_open].
+///
+/// The functions of this module are the C API of tinydb, declared on `include/tinydb.h`. All of
+/// them report failures by returning [TINYDB_ERROR] or a null pointer, and the message of the last
+/// error that happened on the calling thread can be retrieved with [tinydb_errmsg].
+pub struct Tinydb {
+    engine: Engine,
+
+    /// Tag of the last statement executed by [tinydb_exec], if it succeeded.
+    last_tag: Option<(CommandTag, CString)>,
+}
+
+/// Rows returned by a query, which are consumed one at a time by [tinydb_result_next].
+pub struct TinydbResult {
+    /// Name and type oid of each column.
+    columns: Vec<(CString, Oid)>,
+    rows: Vec<Vec<CString>>,
+
+    /// Index of next row plus one, so 0 means that no row was fetched yet.
+    cursor: usize,
+}
+
+impl TinydbResult {
+    fn new(result: QueryResult) -> Result<Self> {
+        let columns = result
+            .columns
+            .into_iter()
+            .map(|column| Ok((CString::new(column.name)?, column.type_oid)))
+            .collect::<Result<_>>()?;
+        let rows = result
+            .rows
+            .into_iter()
+            .map(|row| row.into_iter().map(CString::new).collect())
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            columns,
+            rows,
+            cursor: 0,
+        })
+    }
+
+    /// Return the current row, if tinydb_result_next returned a row.
+    fn current_row(&self) -> Option<&Vec<CString>> {
+        match self.cursor {
+            0 => None,
+            cursor => self.rows.get(cursor - 1),
+        }
+    }
+}
+
+/// Open the database db_name stored on the db_data directory, initializing both if they don't
+/// exist yet.
+///
+/// Return null on error. The returned connection must be closed with [tinydb_close].
+///
+/// # Safety
+///
+/// db_data and db_name must be valid nul terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_open(
+    db_data: *const c_char,
+    db_name: *const c_char,
+) -> *mut Tinydb {
+    let result = catch_panic(|| -> Result<Tinydb> {
+        let db_data = CStr::from_ptr(db_data).to_str()?;
+        let db_name = CStr::from_ptr(db_name).to_str()?;
+
+        init_database(&Path::new(db_data).to_path_buf(), db_name)?;
+
+        let mut engine = Engine::new(BufferPool::new(DEFAULT_BUFFER_POOL_SIZE), db_data)?;
+        engine.use_database(db_name)?;
+        Ok(Tinydb {
+            engine,
+            last_tag: None,
+        })
+    });
+
+    match result {
+        Ok(db) => Box::into_raw(Box::new(db)),
+        Err(err) => {
+            set_last_error(err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Close a connection, flushing all changes to disk. Passing null is a no-op.
+///
+/// Return TINYDB_ERROR if the changes could not be flushed. The connection is freed anyway.
+///
+/// # Safety
+///
+/// db must be null or a connection returned by [tinydb_open] that was not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_close(db: *mut Tinydb) -> c_int {
+    if db.is_null() {
+        return TINYDB_OK;
+    }
+    match catch_panic(|| -> Result<()> {
+        drop(Box::from_raw(db));
+        Ok(())
+    }) {
+        Ok(()) => TINYDB_OK,
+        Err(err) => {
+            set_last_error(err);
+            TINYDB_ERROR
+        }
+    }
+}
+
+/// Execute the given SQL statements.
+///
+/// If result is not null, it is set to the rows of the last query executed, or null if no query
+/// was executed. The result must be freed with [tinydb_result_free].
+///
+/// # Safety
+///
+/// db must be a connection returned by [tinydb_open], sql a valid nul terminated string and
+/// result null or a valid pointer to write to.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_exec(
+    db: *mut Tinydb,
+    sql: *const c_char,
+    result: *mut *mut TinydbResult,
+) -> c_int {
+    if !result.is_null() {
+        *result = ptr::null_mut();
+    }
+
+    let db = &mut *db;
+    db.last_tag = None;
+    let last_result = catch_panic(|| -> Result<Option<TinydbResult>> {
+        let sql = CStr::from_ptr(sql).to_str()?;
+        let results = db.engine.exec_statements(sql)?;
+        if let Some(last) = results.last() {
+            let tag = CString::new(last.tag.to_string())?;
+            db.last_tag = Some((last.tag.clone(), tag));
+        }
+
+        match results.into_iter().flat_map(|result| result.results).last() {
+            Some(last_result) => Ok(Some(TinydbResult::new(last_result)?)),
+            None => Ok(None),
+        }
+    });
+
+    match last_result {
+        Ok(last_result) => {
+            if let (false, Some(last_result)) = (result.is_null(), last_result) {
+                *result = Box::into_raw(Box::new(last_result));
+            }
+            TINYDB_OK
+        }
+        Err(err) => {
+            set_last_error(err);
+            TINYDB_ERROR
+        }
+    }
+}
+
+/// Return the command tag of the last statement executed by [tinydb_exec], e.g `INSERT 0 3`, or
+/// null if the last call failed. The returned string is valid until the next call to
+/// [tinydb_exec] or [tinydb_close].
+///
+/// # Safety
+///
+/// db must be a connection returned by [tinydb_open] that was not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_command_tag(db: *const Tinydb) -> *const c_char {
+    let db = &*db;
+    match &db.last_tag {
+        Some((_, tag)) => tag.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Return the number of rows inserted or returned by the last statement executed by
+/// [tinydb_exec], or 0 if the statement does not handle rows or the last call failed.
+///
+/// # Safety
+///
+/// db must be a connection returned by [tinydb_open] that was not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_changes(db: *const Tinydb) -> u64 {
+    let db = &*db;
+    db.last_tag
+        .as_ref()
+        .and_then(|(tag, _)| tag.rows)
+        .unwrap_or_default()
+}
+
+/// Return the number of columns of a result.
+///
+/// # Safety
+///
+/// result must be a result returned by [tinydb_exec] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_result_column_count(result: *const TinydbResult) -> usize {
+    let result = &*result;
+    result.columns.len()
+}
+
+/// Return the name of the given column, or null if the column does not exist. The returned string
+/// is valid until the result is freed.
+///
+/// # Safety
+///
+/// result must be a result returned by [tinydb_exec] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_result_column_name(
+    result: *const TinydbResult,
+    column: usize,
+) -> *const c_char {
+    let result = &*result;
+    match result.columns.get(column) {
+        Some((name, _)) => name.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Return the oid of the type of the given column, e.g 23 for int4, or 0 if the column does not
+/// exist.
+///
+/// # Safety
+///
+/// result must be a result returned by [tinydb_exec] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_result_column_type(
+    result: *const TinydbResult,
+    column: usize,
+) -> Oid {
+    let result = &*result;
+    match result.columns.get(column) {
+        Some((_, type_oid)) => *type_oid,
+        None => 0,
+    }
+}
+
+/// Advance the result to the next row, returning TINYDB_ROW if there is a row available or
+/// TINYDB_DONE if all rows were consumed.
+///
+/// # Safety
+///
+/// result must be a result returned by [tinydb_exec] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_result_next(result: *mut TinydbResult) -> c_int {
+    let result = &mut *result;
+    if result.cursor < result.rows.len() {
+        result.cursor += 1;
+        TINYDB_ROW
+    } else {
+        result.cursor = result.rows.len() + 1;
+        TINYDB_DONE
+    }
+}
+
+/// Return the value of the given column on the current row formatted as text, or null if there
+/// is no current row or the column does not exist. The returned string is valid until the result
+/// is freed.
+///
+/// # Safety
+///
+/// result must be a result returned by [tinydb_exec] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_result_value(
+    result: *const TinydbResult,
+    column: usize,
+) -> *const c_char {
+    let result = &*result;
+    match result.current_row().and_then(|row| row.get(column)) {
+        Some(value) => value.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Free a result returned by [tinydb_exec]. Passing null is a no-op.
+///
+/// # Safety
+///
+/// result must be null or a result returned by [tinydb_exec] that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinydb_result_free(result: *mut TinydbResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+/// Return the message of the last error that happened on the calling thread, or null if no error
+/// happened. The returned string is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn tinydb_errmsg() -> *const c_char {
+    LAST_ERROR.with(|last_error| match &*last_error.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Call f, reporting panics as errors, since unwinding across the C API is undefined behaviour.
+fn catch_panic<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = match (
+                payload.downcast_ref::<&str>(),
+                payload.downcast_ref::<String>(),
+            ) {
+                (Some(message), _) => message.to_string(),
+                (_, Some(message)) => message.clone(),
+                _ => String::from("unknown panic"),
+            };
+            Err(anyhow!("internal error: {}", message))
+        }
+    }
+}
+
+/// Store the given error as the last error of the current thread.
+fn set_last_error(err: anyhow::Error) {
+    let message = CString::new(err.to_string().replace('\0', ""))
+        .expect("Expected error message without nul bytes");
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::pg_type::INT4_OID;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_ffi_exec_and_iterate_rows() {
+        let db_data = tempdir().unwrap();
+        let db_data = CString::new(db_data.path().to_string_lossy().as_bytes()).unwrap();
+        let db_name = CString::new("db").unwrap();
+
+        unsafe {
+            let db = tinydb_open(db_data.as_ptr(), db_name.as_ptr());
+            assert!(!db.is_null());
+
+            let sql = CString::new(
+                "CREATE TABLE t(a int, b int); INSERT INTO t(a, b) VALUES(1, 2); SELECT * FROM t;",
+            )
+            .unwrap();
+            let mut result = ptr::null_mut();
+            assert_eq!(tinydb_exec(db, sql.as_ptr(), &mut result), TINYDB_OK);
+            assert!(!result.is_null());
+
+            assert_eq!(tinydb_result_column_count(result), 2);
+            let column = CStr::from_ptr(tinydb_result_column_name(result, 1));
+            assert_eq!(column.to_str().unwrap(), "b");
+            assert_eq!(tinydb_result_column_type(result, 1), INT4_OID);
+            assert_eq!(tinydb_result_column_type(result, 2), 0);
+
+            assert!(tinydb_result_value(result, 0).is_null());
+            assert_eq!(tinydb_result_next(result), TINYDB_ROW);
+            let value = CStr::from_ptr(tinydb_result_value(result, 1));
+            assert_eq!(value.to_str().unwrap(), "2");
+            assert_eq!(tinydb_result_next(result), TINYDB_DONE);
+            assert!(tinydb_result_value(result, 0).is_null());
+
+            tinydb_result_free(result);
+
+            let sql = CString::new("INSERT INTO t(a, b) VALUES(3, 4), (5, 6);").unwrap();
+            assert_eq!(tinydb_exec(db, sql.as_ptr(), ptr::null_mut()), TINYDB_OK);
+            let tag = CStr::from_ptr(tinydb_command_tag(db));
+            assert_eq!(tag.to_str().unwrap(), "INSERT 0 2");
+            assert_eq!(tinydb_changes(db), 2);
+
+            let sql = CString::new("SELECT * FROM missing;").unwrap();
+            assert_eq!(tinydb_exec(db, sql.as_ptr(), ptr::null_mut()), TINYDB_ERROR);
+            assert!(tinydb_command_tag(db).is_null());
+            assert_eq!(tinydb_changes(db), 0);
+            let message = CStr::from_ptr(tinydb_errmsg());
+            assert_eq!(message.to_str().unwrap(), "relation missing does not exist");
+
+            let sql = CString::new("DROP TABLE t;").unwrap();
+            assert_eq!(tinydb_exec(db, sql.as_ptr(), ptr::null_mut()), TINYDB_ERROR);
+            let message = CStr::from_ptr(tinydb_errmsg());
+            assert_eq!(
+                message.to_str().unwrap(),
+                "DROP TABLE t is not supported yet"
+            );
+
+            assert_eq!(tinydb_close(db), TINYDB_OK);
+            assert_eq!(tinydb_close(ptr::null_mut()), TINYDB_OK);
+        }
+    }
+
+    #[test]
+    fn test_ffi_catch_panic() {
+        let err = catch_panic(|| -> Result<()> { panic!("boom") }).unwrap_err();
+        assert_eq!(err.to_string(), "internal error: boom");
+        assert_eq!(catch_panic(|| Ok(1)).unwrap(), 1);
+    }
+}
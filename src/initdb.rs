@@ -1,13 +1,134 @@
-use std::{fs::create_dir_all, path::PathBuf};
+use std::{
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Result;
 
+use crate::{
+    access::{
+        heap::{heap_create_storage, heap_insert, HeapTuple},
+        tableam::HEAP_TABLE_AM_OID,
+    },
+    catalog::{
+        new_database_oid, pg_attribute::PgAttribute, pg_audit::PgAudit, pg_class::PgClass,
+        pg_database::PgDatabase, pg_index::PgIndex, pg_policy::PgPolicy, pg_proc::PgProc,
+        pg_type::PgType, Catalog, GLOBAL_DB_NAME,
+    },
+    control::ControlFile,
+    storage::{rel::database_path, BufferPool},
+};
+
+/// Size of buffer pool used while bootstrapping the system catalogs.
+const BOOTSTRAP_BUFFER_POOL_SIZE: usize = 16;
+
 /// Initialize a empty database at the db_data path using db_name as the database name.
+///
+/// If db_data was not initialized yet, the cluster wide control file and shared catalogs are
+/// bootstrapped first. Otherwise the control file is validated to refuse data directories
+/// initialized by an incompatible version.
 pub fn init_database(db_data: &PathBuf, db_name: &str) -> Result<()> {
-    let db_path = db_data.join(db_name);
+    let mut buffer = BufferPool::new(BOOTSTRAP_BUFFER_POOL_SIZE);
+
+    if ControlFile::path(db_data).exists() {
+        ControlFile::read(db_data)?.validate()?;
+    } else {
+        bootstrap_cluster(&mut buffer, db_data)?;
+    }
+
+    if Catalog::new(&db_data.to_string_lossy())
+        .get_database_oid(&mut buffer, db_name)
+        .is_err()
+    {
+        bootstrap_database(&mut buffer, &db_data.to_string_lossy(), db_name)?;
+    }
+
+    buffer.flush_all_buffers()?;
+
+    Ok(())
+}
+
+/// Create the directory of a new database, named after its oid, bootstrap its system catalogs and
+/// register it on pg_database.
+pub fn bootstrap_database(buffer: &mut BufferPool, db_data: &str, db_name: &str) -> Result<()> {
+    let oid = new_database_oid(buffer, db_data)?;
+    create_dir_all(database_path(db_data, oid))?;
+
+    let catalogs = vec![
+        PgClass::get_relation(db_data, oid)?,
+        PgAttribute::get_relation(db_data, oid)?,
+        PgType::get_relation(db_data, oid)?,
+        PgIndex::get_relation(db_data, oid)?,
+        PgProc::get_relation(db_data, oid)?,
+        PgPolicy::get_relation(db_data, oid)?,
+        PgAudit::get_relation(db_data, oid)?,
+    ];
+    for catalog in &catalogs {
+        heap_create_storage(buffer, catalog, HEAP_TABLE_AM_OID)?;
+    }
+
+    let pg_type = PgType::get_relation(db_data, oid)?;
+    for typ in PgType::builtin_types() {
+        heap_insert(buffer, &pg_type, &HeapTuple::new(bincode::serialize(&typ)?))?;
+    }
 
-    if !db_path.exists() {
-        create_dir_all(db_path)?;
+    let pg_proc = PgProc::get_relation(db_data, oid)?;
+    for function in PgProc::builtin_functions() {
+        heap_insert(
+            buffer,
+            &pg_proc,
+            &HeapTuple::new(bincode::serialize(&function)?),
+        )?;
     }
+
+    let pg_database = PgDatabase::get_relation(db_data)?;
+    heap_insert(
+        buffer,
+        &pg_database,
+        &HeapTuple::new(bincode::serialize(&PgDatabase {
+            oid,
+            datname: db_name.to_string(),
+        })?),
+    )?;
+
+    Ok(())
+}
+
+/// Bootstrap the control file and the catalogs shared across all databases of db_data.
+fn bootstrap_cluster(buffer: &mut BufferPool, db_data: &Path) -> Result<()> {
+    create_dir_all(db_data.join(GLOBAL_DB_NAME))?;
+
+    ControlFile::default().write(db_data)?;
+
+    let pg_database = PgDatabase::get_relation(&db_data.to_string_lossy())?;
+    heap_create_storage(buffer, &pg_database, HEAP_TABLE_AM_OID)?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_init_database_bootstrap_catalogs() -> Result<()> {
+        let db_data = tempdir()?;
+
+        init_database(&db_data.path().to_path_buf(), "db1")?;
+        init_database(&db_data.path().to_path_buf(), "db2")?;
+
+        ControlFile::read(db_data.path())?.validate()?;
+
+        let mut buffer = BufferPool::new(BOOTSTRAP_BUFFER_POOL_SIZE);
+        let catalog = Catalog::new(&db_data.path().to_string_lossy());
+
+        let db1 = catalog.get_database_oid(&mut buffer, "db1")?;
+        let db2 = catalog.get_database_oid(&mut buffer, "db2")?;
+        assert_ne!(db1, db2);
+        assert!(database_path(&db_data.path().to_string_lossy(), db1).is_dir());
+        assert!(catalog.get_database_oid(&mut buffer, "db3").is_err());
+
+        Ok(())
+    }
+}
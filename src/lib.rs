@@ -4,10 +4,15 @@ pub mod initdb;
 
 use std::sync::atomic::{AtomicU64, Ordering};
 pub mod access;
+pub mod bench;
 pub mod catalog;
+pub mod check;
+pub mod control;
 pub mod engine;
+pub mod ffi;
 pub mod lru;
 pub mod storage;
+pub mod upgrade;
 
 /// First object id to assign when creating a new database cluster.
 const FIRST_NORMAL_OBJECT_ID: u64 = 10000;
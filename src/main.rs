@@ -1,38 +1,373 @@
-use std::path::PathBuf;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
 
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
-use tinydb::engine::Engine;
+use tinydb::bench::{self, BenchOptions};
+use tinydb::check::check_data_directory;
+use tinydb::engine::{Diagnostic, DisplayOptions, Engine, OnError, StatementResult};
 use tinydb::initdb::init_database;
 use tinydb::storage::BufferPool;
+use tinydb::upgrade::upgrade;
+
+use shell::completion::SqlHelper;
+use shell::pager::Pager;
+
+mod shell;
+
+/// Default path where database files are stored.
+const DEFAULT_DB_DATA: &str = "data";
+
+/// Default path where the benchmark data directory is created.
+const DEFAULT_BENCH_DB_DATA: &str = "bench_data";
+
+/// Default database name of the interactive shell.
+const DEFAULT_DB_NAME: &str = "tinydb";
+
+/// Environment variable with the minimum duration in milliseconds of statements to log.
+const LOG_MIN_DURATION_ENV: &str = "TINYDB_LOG_MIN_DURATION";
+
+/// Environment variable that makes `\i` stop on the first failed statement of the script.
+const ON_ERROR_STOP_ENV: &str = "TINYDB_ON_ERROR_STOP";
 
 fn main() {
     pretty_env_logger::init();
 
-    let default_db_name = "tinydb";
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("upgrade") => {
+            upgrade_data_directory(args.get(2).map_or(DEFAULT_DB_DATA, String::as_str))
+        }
+        Some("check") => check(args.get(2).map_or(DEFAULT_DB_DATA, String::as_str)),
+        Some("bench") => run_bench(&args[2..]),
+        Some("gen") => generate(&args[2..]),
+        Some("exec") => exec(&args[2..]),
+        _ => shell(),
+    }
+}
+
+/// Handle the `tinydb upgrade [db_data]` command, checking that the data directory can be read
+/// by this build.
+fn upgrade_data_directory(db_data: &str) {
+    match upgrade(Path::new(db_data)) {
+        Ok(checked) => println!(
+            "Data directory {} is up to date ({} files checked)",
+            db_data, checked
+        ),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Handle the `tinydb check [db_data]` command, reporting all corruptions found on the data
+/// directory.
+fn check(db_data: &str) {
+    match check_data_directory(Path::new(db_data)) {
+        Ok(corruptions) if corruptions.is_empty() => println!("No corruption found on {}", db_data),
+        Ok(corruptions) => {
+            for corruption in &corruptions {
+                println!("{}", corruption);
+            }
+            eprintln!("Found {} corruptions on {}", corruptions.len(), db_data);
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Handle the `tinydb bench [db_data] [transactions] [select_percent]` command, running the
+/// benchmark on a new data directory.
+fn run_bench(args: &[String]) {
+    let db_data = args.get(0).map_or(DEFAULT_BENCH_DB_DATA, String::as_str);
+
+    let mut options = BenchOptions::default();
+    if let Some(transactions) = args.get(1) {
+        options.transactions = transactions
+            .parse()
+            .expect("Invalid number of transactions");
+    }
+    if let Some(select_percent) = args.get(2) {
+        options.select_percent = select_percent.parse().expect("Invalid select percent");
+    }
+
+    match bench::run(Path::new(db_data), &options) {
+        Ok(report) => print!("{}", report),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Handle the `tinydb gen [db_data] [db_name] [table] [rows]` command, creating a new table
+/// filled with the integers from 1 to rows. The same arguments always generate the same data.
+fn generate(args: &[String]) {
+    let db_data = args.get(0).map_or(DEFAULT_DB_DATA, String::as_str);
+    let db_name = args.get(1).map_or(DEFAULT_DB_NAME, String::as_str);
+    let table = args.get(2).map_or("series", String::as_str);
+    let rows = args
+        .get(3)
+        .map_or(Ok(1000), |rows| rows.parse::<i32>())
+        .expect("Invalid number of rows");
+
+    let result = init_database(&PathBuf::from(db_data), db_name).and_then(|_| {
+        let buffer = BufferPool::new(120);
+        let mut engine = Engine::new(buffer, db_data)?;
+        engine.use_database(db_name)?;
+        engine.exec(&format!("CREATE TABLE {}(id int);", table))?;
+        engine.exec(&format!(
+            "INSERT INTO {}(id) SELECT * FROM generate_series(1, {});",
+            table, rows
+        ))
+    });
+
+    match result {
+        Ok(_) => println!("Generated {} rows on {}.{}", rows, db_name, table),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Exit code of `tinydb exec` when a statement fails.
+const EXIT_STATEMENT_FAILED: i32 = 1;
+
+/// Exit code of `tinydb exec` when its arguments are invalid or the database can't be opened.
+const EXIT_USAGE: i32 = 2;
+
+/// Output format of `tinydb exec`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputFormat {
+    /// Tables with row counts and command tags, like the shell.
+    Table,
+
+    /// A JSON array of row objects for each query, on its own line.
+    Json,
+
+    /// CSV with a header line for each query. Queries are separated by an empty line.
+    Csv,
+}
+
+/// Arguments of the `tinydb exec` command.
+struct ExecArgs {
+    db_data: String,
+    db_name: String,
+
+    /// SQL given by -c, executed as a script.
+    command: Option<String>,
+
+    /// Path of the script given by -f.
+    file: Option<String>,
+
+    format: OutputFormat,
+}
+
+/// Parse the arguments of `tinydb exec`, returning an error message if they are invalid.
+fn parse_exec_args(args: &[String]) -> Result<ExecArgs, String> {
+    let mut exec_args = ExecArgs {
+        db_data: DEFAULT_DB_DATA.to_string(),
+        db_name: DEFAULT_DB_NAME.to_string(),
+        command: None,
+        file: None,
+        format: OutputFormat::Table,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .cloned()
+            .ok_or_else(|| format!("{} expects a value", arg))?;
+        match arg.as_str() {
+            "--data-dir" => exec_args.db_data = value,
+            "--db" => exec_args.db_name = value,
+            "-c" => exec_args.command = Some(value),
+            "-f" => exec_args.file = Some(value),
+            "--format" => {
+                exec_args.format = match value.as_str() {
+                    "table" => OutputFormat::Table,
+                    "json" => OutputFormat::Json,
+                    "csv" => OutputFormat::Csv,
+                    _ => {
+                        return Err(format!(
+                            "unknown format {}, expected table, json or csv",
+                            value
+                        ))
+                    }
+                }
+            }
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    if exec_args.command.is_some() == exec_args.file.is_some() {
+        return Err(String::from("exactly one of -c or -f should be given"));
+    }
+    Ok(exec_args)
+}
+
+/// Handle the `tinydb exec [--data-dir D] [--db NAME] (-c SQL | -f FILE) [--format FORMAT]`
+/// command, executing SQL without the interactive shell, e.g on scripts and CI pipelines.
+fn exec(args: &[String]) {
+    let args = match parse_exec_args(args) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            eprintln!(
+                "Usage: tinydb exec [--data-dir D] [--db NAME] (-c SQL | -f FILE) [--format table|json|csv]"
+            );
+            process::exit(EXIT_USAGE);
+        }
+    };
+    process::exit(run_exec(&args));
+}
+
+/// Execute the SQL given to `tinydb exec` and return the exit code of the command. Execution
+/// stops on the first failed statement, returning [EXIT_STATEMENT_FAILED]. Scripts and databases
+/// that can't be opened return [EXIT_USAGE].
+///
+/// The engine is dropped before returning, so the changes of the executed statements are
+/// flushed before the process exits.
+fn run_exec(args: &ExecArgs) -> i32 {
+    let script = match &args.file {
+        Some(file) => match fs::read_to_string(file) {
+            Ok(script) => script,
+            Err(err) => {
+                eprintln!("Error: {}: {}", file, err);
+                return EXIT_USAGE;
+            }
+        },
+        None => args.command.clone().unwrap_or_default(),
+    };
+
+    let opened = Engine::new(BufferPool::new(120), &args.db_data).and_then(|mut engine| {
+        engine.use_database(&args.db_name)?;
+        Ok(engine)
+    });
+    let mut engine = match opened {
+        Ok(engine) => engine,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return EXIT_USAGE;
+        }
+    };
+
+    let results = match engine.exec_script(&script, OnError::Stop) {
+        Ok(results) => results,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            return EXIT_STATEMENT_FAILED;
+        }
+    };
+    for notice in engine.take_notices() {
+        eprintln!("NOTICE: {}", notice);
+    }
+
+    let mut first_query = true;
+    for result in results {
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                if let Some(file) = &args.file {
+                    eprint!("{}:{}: ", file, err.line);
+                }
+                print_error(&err.error, &script);
+                return EXIT_STATEMENT_FAILED;
+            }
+        };
+
+        match args.format {
+            OutputFormat::Table => {
+                print!("{}", format_result(&DisplayOptions::default(), &result))
+            }
+            OutputFormat::Json => {
+                for rows in &result.results {
+                    println!("{}", rows.to_json());
+                }
+            }
+            OutputFormat::Csv => {
+                for rows in &result.results {
+                    if !first_query {
+                        println!();
+                    }
+                    print!("{}", rows.to_csv());
+                    first_query = false;
+                }
+            }
+        }
+    }
+
+    0
+}
+
+/// Run the interactive shell connected to the default database.
+fn shell() {
+    let default_db_name = DEFAULT_DB_NAME;
 
     // Create a default tinydb database.
-    init_database(&PathBuf::from("data"), &default_db_name).expect("Failed init default database");
+    init_database(&PathBuf::from(DEFAULT_DB_DATA), &default_db_name)
+        .expect("Failed init default database");
 
-    let mut rl = Editor::<()>::new();
+    let mut rl = Editor::<SqlHelper>::new();
+    rl.set_helper(Some(SqlHelper::default()));
     if rl.load_history("history.txt").is_err() {
         println!("No previous history.");
     }
 
     let buffer = BufferPool::new(120);
-    let mut engine = Engine::new(buffer, "data");
+    let mut engine = Engine::new(buffer, DEFAULT_DB_DATA).expect("Failed to open data directory");
+    engine
+        .use_database(default_db_name)
+        .expect("Failed to connect to default database");
+    if let Ok(log_min_duration) = env::var(LOG_MIN_DURATION_ENV) {
+        let millis = log_min_duration
+            .parse::<u64>()
+            .expect("Invalid log min duration");
+        engine.set_log_min_duration(Some(Duration::from_millis(millis)));
+    }
+
+    let mut pager = Pager::from_env();
+    let mut display = DisplayOptions::default();
 
     println!("Connected at {} database", default_db_name);
     loop {
+        refresh_completion(&mut rl, &mut engine);
         let readline = rl.readline(">> ");
         match readline {
             Ok(line) => {
                 rl.add_history_entry(line.as_str());
-                if let Err(err) = engine.exec(&line, default_db_name) {
-                    eprintln!("Error: {:?}", err);
+                if let Some((command, arg)) = split_meta_command(&line) {
+                    match command {
+                        "\\c" | "\\connect" => connect(&mut engine, arg),
+                        "\\i" => include(&mut engine, &pager, &display, arg),
+                        "\\pager" => set_pager(&mut pager, arg),
+                        "\\x" => set_expanded(&mut display, arg),
+                        _ => eprintln!("Error: invalid command {}", command),
+                    }
                     continue;
                 }
-                println!("Ok");
+                let start = Instant::now();
+                let result = engine.exec_statements(&line);
+                let elapsed = start.elapsed();
+                print_notices(&mut engine);
+                match result {
+                    Ok(results) => {
+                        for result in results {
+                            print_result(&pager, &display, result);
+                        }
+                        println!("Time: {:.3} ms", elapsed.as_secs_f64() * 1000.0);
+                    }
+                    Err(err) => print_error(&err, &line),
+                }
             }
             Err(ReadlineError::Interrupted) => {
                 println!("CTRL-C");
@@ -49,3 +384,180 @@ fn main() {
     }
     rl.save_history("history.txt").unwrap();
 }
+
+/// Split a meta command line, e.g `\\c tinydb`, into its command and its trimmed argument. Return
+/// None if the line is not a meta command.
+fn split_meta_command(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if !line.starts_with('\\') {
+        return None;
+    }
+    match line.find(char::is_whitespace) {
+        Some(end) => Some((&line[..end], line[end..].trim())),
+        None => Some((line, "")),
+    }
+}
+
+/// Refresh the relation and column names completed by the shell, since the last statements could
+/// have changed them.
+fn refresh_completion(rl: &mut Editor<SqlHelper>, engine: &mut Engine) {
+    if let (Some(helper), Ok(relations)) = (rl.helper_mut(), engine.relations()) {
+        helper.set_relations(relations);
+    }
+}
+
+/// Handle the `\i` meta command executing the SQL script of the given file. Failed statements
+/// are reported with their line, and the script stops on the first one only if the
+/// TINYDB_ON_ERROR_STOP environment variable is set.
+fn include(engine: &mut Engine, pager: &Pager, display: &DisplayOptions, path: &str) {
+    let script = match fs::read_to_string(path) {
+        Ok(script) => script,
+        Err(err) => {
+            eprintln!("Error: {}: {}", path, err);
+            return;
+        }
+    };
+
+    let on_error = match env::var(ON_ERROR_STOP_ENV) {
+        Ok(_) => OnError::Stop,
+        Err(_) => OnError::Continue,
+    };
+    let results = engine.exec_script(&script, on_error);
+    print_notices(engine);
+    match results {
+        Ok(results) => {
+            for result in results {
+                match result {
+                    Ok(result) => print_result(pager, display, result),
+                    Err(err) => {
+                        eprint!("{}:{}: ", path, err.line);
+                        print_error(&err.error, &script);
+                    }
+                }
+            }
+        }
+        Err(err) => eprintln!("Error: {:?}", err),
+    }
+}
+
+/// Print the notices raised by the last statements executed.
+fn print_notices(engine: &mut Engine) {
+    for notice in engine.take_notices() {
+        println!("NOTICE: {}", notice);
+    }
+}
+
+/// Print the rows returned by a statement with their row count, followed by its command tag.
+/// Outputs that don't fit on the terminal are paged.
+fn print_result(pager: &Pager, display: &DisplayOptions, result: StatementResult) {
+    if let Err(err) = pager.show(&format_result(display, &result)) {
+        eprintln!("Error: {}", err);
+    }
+}
+
+/// Format the rows returned by a statement as tables with their row count, followed by its
+/// command tag.
+fn format_result(display: &DisplayOptions, result: &StatementResult) -> String {
+    let mut output = String::new();
+    for rows in &result.results {
+        // Rendered rows already end with a newline.
+        output.push_str(&format!("{}{}\n\n", rows.render(display), rows.footer()));
+    }
+    output.push_str(&format!("{}\n", result.tag));
+    output
+}
+
+/// Handle the `\pager [on|off]` meta command enabling or disabling the pager of long outputs.
+fn set_pager(pager: &mut Pager, mode: &str) {
+    let enabled = match mode {
+        "" | "on" => true,
+        "off" => false,
+        _ => {
+            eprintln!("Error: \\pager expects on or off");
+            return;
+        }
+    };
+    pager.set_enabled(enabled);
+    println!("Pager is {}", if enabled { "on" } else { "off" });
+}
+
+/// Handle the `\x [on|off]` meta command toggling the expanded display of rows, which shows each
+/// row as a record with a line for each column.
+fn set_expanded(display: &mut DisplayOptions, mode: &str) {
+    display.expanded = match mode {
+        "" => !display.expanded,
+        "on" => true,
+        "off" => false,
+        _ => {
+            eprintln!("Error: \\x expects on or off");
+            return;
+        }
+    };
+    println!(
+        "Expanded display is {}",
+        if display.expanded { "on" } else { "off" }
+    );
+}
+
+/// Print the error of a statement of sql, rendering its diagnostic if any.
+fn print_error(err: &anyhow::Error, sql: &str) {
+    match err.downcast_ref::<Diagnostic>() {
+        Some(diagnostic) => eprintln!("{}", diagnostic.render(sql)),
+        None => eprintln!("Error: {:?}", err),
+    }
+}
+
+/// Handle the `\c` meta command switching the database that the shell is connected to. If db_name
+/// is empty the current database is printed.
+fn connect(engine: &mut Engine, db_name: &str) {
+    if !db_name.is_empty() {
+        if let Err(err) = engine.use_database(db_name) {
+            eprintln!("Error: {:?}", err);
+            return;
+        }
+    }
+    if let Some(db_name) = engine.current_database() {
+        println!("Connected at {} database", db_name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_run_exec_exit_codes() {
+        let db_data = tempdir().unwrap();
+        init_database(&db_data.path().to_path_buf(), DEFAULT_DB_NAME).unwrap();
+
+        let exec_args = |command: &str| ExecArgs {
+            db_data: db_data.path().to_string_lossy().to_string(),
+            db_name: DEFAULT_DB_NAME.to_string(),
+            command: Some(command.to_string()),
+            file: None,
+            format: OutputFormat::Csv,
+        };
+
+        assert_eq!(run_exec(&exec_args("CREATE TABLE t(id int);")), 0);
+        for command in [
+            "DROP TABLE t;",
+            "UPDATE t SET id = 1;",
+            "SELECT * FROM missing;",
+        ] {
+            assert_eq!(run_exec(&exec_args(command)), EXIT_STATEMENT_FAILED);
+        }
+
+        let args = vec![String::from("-c")];
+        assert!(parse_exec_args(&args).is_err());
+    }
+    #[test]
+    fn test_split_meta_command() {
+        assert_eq!(split_meta_command("SELECT 1;"), None);
+        assert_eq!(split_meta_command(" \\c "), Some(("\\c", "")));
+        assert_eq!(split_meta_command("\\c  tinydb "), Some(("\\c", "tinydb")));
+        assert_eq!(split_meta_command("\\copy t"), Some(("\\copy", "t")));
+        assert_eq!(split_meta_command("\\xon"), Some(("\\xon", "")));
+        assert_eq!(split_meta_command("\\pager\toff"), Some(("\\pager", "off")));
+    }
+}
@@ -0,0 +1,204 @@
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+/// Keywords of the statements supported by the engine, completed by the shell.
+const KEYWORDS: &[&str] = &[
+    "ALL",
+    "AND",
+    "AS",
+    "BEGIN",
+    "CALL",
+    "COMMIT",
+    "CREATE",
+    "DATABASE",
+    "DECLARE",
+    "DO",
+    "DROP",
+    "ELSE",
+    "END",
+    "EXISTS",
+    "FALSE",
+    "FOR",
+    "FROM",
+    "IF",
+    "IN",
+    "INSERT",
+    "INTO",
+    "IS",
+    "LOOP",
+    "NOT",
+    "NULL",
+    "OR",
+    "POLICY",
+    "PROCEDURE",
+    "REPLACE",
+    "RESET",
+    "ROLE",
+    "SELECT",
+    "SET",
+    "SHOW",
+    "TABLE",
+    "THEN",
+    "TO",
+    "TRUE",
+    "USING",
+    "VALUES",
+];
+
+/// Keywords that are followed by a relation name, so only relations are completed after them.
+const RELATION_KEYWORDS: &[&str] = &["FROM", "INTO", "ON", "TABLE"];
+
+/// Helper of the shell line editor that completes SQL keywords and the names of the relations
+/// and columns of the current database.
+///
+/// Names are kept in a cache refreshed by the shell with [SqlHelper::set_relations], since
+/// completions can't access the engine while a line is edited.
+#[derive(Default)]
+pub struct SqlHelper {
+    /// Names of the relations with the names of their columns.
+    relations: Vec<(String, Vec<String>)>,
+}
+
+impl SqlHelper {
+    /// Replace the cached relation and column names, e.g after a statement created a relation.
+    pub fn set_relations(&mut self, relations: Vec<(String, Vec<String>)>) {
+        self.relations = relations;
+    }
+
+    /// Return the start position of the word before pos on line and its sorted completions.
+    ///
+    /// Words qualified by a relation, like `t.a`, are completed with the columns of the relation.
+    /// Words after FROM, INTO, ON or TABLE are completed with relation names. Other words are
+    /// completed with keywords, relations and the columns of the relations used on the line.
+    /// Keywords are completed in lowercase if the word is lowercase.
+    pub fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .map_or(0, |i| i + 1);
+        let word = &line[start..pos];
+
+        if let Some(dot) = word.rfind('.') {
+            let rel_name = &word[..dot];
+            let columns = self
+                .relations
+                .iter()
+                .filter(|(relname, _)| relname == rel_name)
+                .flat_map(|(_, columns)| columns.iter().cloned());
+            return (start + dot + 1, matching(&word[dot + 1..], columns));
+        }
+
+        let relations = self.relations.iter().map(|(relname, _)| relname.clone());
+        let previous = line[..start].split_whitespace().last().unwrap_or_default();
+        if RELATION_KEYWORDS
+            .iter()
+            .any(|keyword| keyword.eq_ignore_ascii_case(previous))
+        {
+            return (start, matching(word, relations));
+        }
+
+        let lowercase = word.chars().any(char::is_lowercase);
+        let keywords = KEYWORDS.iter().map(|keyword| {
+            if lowercase {
+                keyword.to_lowercase()
+            } else {
+                keyword.to_string()
+            }
+        });
+        let words: Vec<&str> = line
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .collect();
+        let columns = self
+            .relations
+            .iter()
+            .filter(|(relname, _)| words.contains(&relname.as_str()))
+            .flat_map(|(_, columns)| columns.iter().cloned());
+
+        (
+            start,
+            matching(word, keywords.chain(relations).chain(columns)),
+        )
+    }
+}
+
+/// Return the sorted and deduplicated names that start with prefix, ignoring case.
+fn matching(prefix: &str, names: impl Iterator<Item = String>) -> Vec<String> {
+    let prefix = prefix.to_lowercase();
+    let mut names: Vec<String> = names
+        .filter(|name| name.to_lowercase().starts_with(&prefix))
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+impl Completer for SqlHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for SqlHelper {
+    type Hint = String;
+}
+
+impl Highlighter for SqlHelper {}
+
+impl Validator for SqlHelper {}
+
+impl Helper for SqlHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let mut helper = SqlHelper::default();
+        helper.set_relations(vec![
+            (
+                String::from("users"),
+                vec![String::from("id"), String::from("age")],
+            ),
+            (String::from("orders"), vec![String::from("user_id")]),
+        ]);
+        let complete = |line: &str| helper.candidates(line, line.len());
+
+        assert_eq!(complete("SEL"), (0, vec![String::from("SELECT")]));
+        assert_eq!(complete("sel"), (0, vec![String::from("select")]));
+        assert_eq!(
+            complete("SELECT * FROM "),
+            (14, vec![String::from("orders"), String::from("users")])
+        );
+        assert_eq!(complete("insert into u"), (12, vec![String::from("users")]));
+        assert_eq!(
+            complete("SELECT a FROM users WHERE users.a"),
+            (32, vec![String::from("age")])
+        );
+        assert_eq!(
+            helper.candidates("SELECT u FROM users", 8),
+            (7, vec![String::from("users"), String::from("using")])
+        );
+        assert_eq!(
+            helper.candidates("SELECT us FROM orders", 9),
+            (
+                7,
+                vec![
+                    String::from("user_id"),
+                    String::from("users"),
+                    String::from("using")
+                ]
+            )
+        );
+        assert_eq!(complete("SELECT x"), (7, Vec::<String>::new()));
+    }
+}
@@ -0,0 +1,146 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::process::{Command, Stdio};
+
+/// Environment variable with the command used to page long outputs, e.g `less -S`.
+const PAGER_ENV: &str = "PAGER";
+
+/// Environment variable with the number of lines of the terminal.
+const LINES_ENV: &str = "LINES";
+
+/// Number of lines of a page if the terminal height is unknown.
+const DEFAULT_PAGE_LINES: usize = 24;
+
+/// Pager of the shell outputs that don't fit on the terminal, like the pager of psql. Outputs are
+/// piped to the $PAGER command if it is set, otherwise they are shown a page at a time by an
+/// internal pager.
+pub struct Pager {
+    /// Command that pages the outputs. If None the internal pager is used.
+    command: Option<String>,
+
+    /// Number of lines of the terminal, so longer outputs are paged.
+    page_lines: usize,
+
+    /// If false every output is written directly to stdout.
+    enabled: bool,
+}
+
+impl Pager {
+    /// Create a new pager configured by the PAGER and LINES environment variables.
+    pub fn from_env() -> Self {
+        let command = env::var(PAGER_ENV)
+            .ok()
+            .filter(|command| !command.trim().is_empty());
+        let page_lines = env::var(LINES_ENV)
+            .ok()
+            .and_then(|lines| lines.parse().ok())
+            .unwrap_or(DEFAULT_PAGE_LINES);
+
+        Self {
+            command,
+            page_lines,
+            enabled: true,
+        }
+    }
+
+    /// Enable or disable paging, e.g with the `\pager off` meta command.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Show the given output, paging it if it does not fit on the terminal.
+    pub fn show(&self, output: &str) -> io::Result<()> {
+        let stdout = io::stdout();
+        // One line of the terminal is left for the prompt.
+        if !self.enabled || output.lines().count() < self.page_lines {
+            return stdout.lock().write_all(output.as_bytes());
+        }
+
+        match &self.command {
+            Some(command) => run_pager(command, output),
+            None => page(
+                output,
+                self.page_lines,
+                &mut io::stdin().lock(),
+                &mut stdout.lock(),
+            ),
+        }
+    }
+}
+
+/// Pipe the output to the given pager command and wait until the user quits it.
+fn run_pager(command: &str, output: &str) -> io::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager closes its input if the user quits before reading the whole output.
+        match stdin.write_all(output.as_bytes()) {
+            Err(err) if err.kind() != io::ErrorKind::BrokenPipe => return Err(err),
+            _ => {}
+        }
+    }
+    child.wait()?;
+
+    Ok(())
+}
+
+/// Write the output to out a page at a time, waiting for a line of input before each following
+/// page. The rest of the output is skipped if the input is `q` or it is closed.
+fn page<R: BufRead, W: Write>(
+    output: &str,
+    page_lines: usize,
+    input: &mut R,
+    out: &mut W,
+) -> io::Result<()> {
+    let lines: Vec<&str> = output.lines().collect();
+    let mut pages = lines.chunks(page_lines.saturating_sub(1).max(1)).peekable();
+
+    while let Some(page) = pages.next() {
+        for line in page {
+            writeln!(out, "{}", line)?;
+        }
+        if pages.peek().is_none() {
+            break;
+        }
+
+        write!(out, "--More-- (Enter for next page, q to quit)")?;
+        out.flush()?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 || answer.trim().eq_ignore_ascii_case("q") {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page_output(output: &str, input: &str) -> io::Result<String> {
+        let mut out = Vec::new();
+        page(output, 3, &mut input.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_page() -> io::Result<()> {
+        let output = "1\n2\n3\n4\n5\n";
+        let prompt = "--More-- (Enter for next page, q to quit)";
+
+        assert_eq!(
+            page_output(output, "\n\n")?,
+            format!("1\n2\n{}3\n4\n{}5\n", prompt, prompt)
+        );
+        assert_eq!(page_output(output, "q\n")?, format!("1\n2\n{}", prompt));
+        assert_eq!(page_output(output, "")?, format!("1\n2\n{}", prompt));
+        assert_eq!(page_output("1\n2\n", "")?, "1\n2\n");
+
+        Ok(())
+    }
+}
@@ -5,11 +5,15 @@ use log::debug;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::convert::TryInto;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 use std::hash::Hash;
+#[cfg(debug_assertions)]
+use std::panic::Location;
 use std::rc::Rc;
+use tracing::{debug_span, field, trace_span};
 
 use super::rel::Relation;
+use crate::Oid;
 
 /// Represents errors that buffer pool can have.
 #[derive(thiserror::Error, Debug)]
@@ -21,6 +25,10 @@ pub enum Error {
     /// Represents that an page_num does not exists on buffer pool.
     #[error("Page {0} does no exists")]
     PageNotFound(PageNumber),
+
+    /// Represents that a page can not be discarded because it is still in use.
+    #[error("Page {0} is still pinned")]
+    PagePinned(PageNumber),
 }
 
 /// Bytes is a wrapper over a byte array that makes it easy to write, overwrite and reset that byte array.
@@ -108,6 +116,10 @@ pub struct BufferData {
 
     /// Reference counter to the page buffer.
     refcount: usize,
+
+    /// Call sites holding a pin on the page buffer, used to report leaked pins.
+    #[cfg(debug_assertions)]
+    pinned_at: Vec<&'static Location<'static>>,
 }
 
 impl BufferData {
@@ -117,6 +129,8 @@ impl BufferData {
             tag,
             is_dirty: false,
             refcount: 0,
+            #[cfg(debug_assertions)]
+            pinned_at: Vec::new(),
         }))
     }
 }
@@ -136,28 +150,74 @@ impl Hash for BufferTag {
         let rel = self.rel.borrow();
         state.write_u32(self.page_num);
         state.write(rel.db_data.as_bytes());
-        state.write(rel.db_name.as_bytes());
+        state.write_u64(rel.db_oid);
         state.write(rel.rel_name.as_bytes());
     }
 }
 
-impl PartialEq for BufferTag {
-    fn eq(&self, other: &Self) -> bool {
+impl BufferTag {
+    /// Check if the tag identifies a page of the given relation.
+    fn belongs_to(&self, other_rel: &Relation) -> bool {
         let rel = self.rel.borrow();
-        let other_rel = other.rel.borrow();
+        let other_rel = other_rel.borrow();
 
-        (self.page_num == other.page_num)
-            && (rel.db_data == other_rel.db_data)
-            && (rel.db_name == other_rel.db_name)
+        (rel.db_data == other_rel.db_data)
+            && (rel.db_oid == other_rel.db_oid)
             && (rel.rel_name == other_rel.rel_name)
     }
 }
 
+impl PartialEq for BufferTag {
+    fn eq(&self, other: &Self) -> bool {
+        (self.page_num == other.page_num) && self.belongs_to(&other.rel)
+    }
+}
+
 impl Eq for BufferTag {}
 
 /// A mutable reference counter to BufferData.
 pub type Buffer = Rc<RefCell<BufferData>>;
 
+/// A buffer that is still pinned, reported by [pinned_buffers](BufferPool::pinned_buffers).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinnedBuffer {
+    /// Name of relation that the page belongs.
+    pub rel_name: String,
+
+    /// Number of the pinned page.
+    pub page_num: PageNumber,
+
+    /// Number of pins held on the page.
+    pub refcount: usize,
+
+    /// Call sites holding the pins. Only tracked on debug builds, it is empty otherwise.
+    pub pinned_at: Vec<String>,
+}
+
+impl fmt::Display for PinnedBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "page {} of {} has {} pins",
+            self.page_num, self.rel_name, self.refcount
+        )?;
+        if !self.pinned_at.is_empty() {
+            write!(f, " taken at {}", self.pinned_at.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Block I/O counters of a single relation.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct BlockStats {
+    /// Number of pages read from disk.
+    pub blks_read: u64,
+
+    /// Number of pages found already on buffer pool.
+    pub blks_hit: u64,
+}
+
 /// BufferPool is responsible for fetching database pages from the disk and storing them in memory.
 /// The BufferPool can also write dirty pages out to disk when it is either explicitly instructed to do so
 /// or when it needs to evict a page to make space for a new page.
@@ -173,6 +233,12 @@ pub struct BufferPool {
 
     /// A map of buffer tag to a page buffer descriptor
     buffer_table: HashMap<BufferTag, Buffer>,
+
+    /// Buffer ids of page table slots released by victim that can be reused by new pages.
+    free_slots: Vec<usize>,
+
+    /// Block I/O counters indexed by database oid and relation oid.
+    block_stats: HashMap<(Oid, Oid), BlockStats>,
 }
 
 impl BufferPool {
@@ -183,6 +249,8 @@ impl BufferPool {
             lru: LRU::new(size),
             page_table: Vec::with_capacity(size),
             buffer_table: HashMap::with_capacity(size),
+            free_slots: Vec::new(),
+            block_stats: HashMap::new(),
         }
     }
 
@@ -191,12 +259,23 @@ impl BufferPool {
     /// If no buffer exists already, selects a replacement victim and evicts the old page.
     ///
     /// The returned buffer is pinned and is already marked as holding the desired page.
+    #[track_caller]
     pub fn fetch_buffer(&mut self, rel: &Relation, page_num: PageNumber) -> Result<Buffer> {
+        let span = trace_span!(
+            "fetch_buffer",
+            rel = %rel.borrow().rel_name,
+            page_num,
+            hit = field::Empty
+        );
+        let _enter = span.enter();
+
         let buf_tag = BufferTag {
             page_num,
             rel: rel.clone(),
         };
         if let Ok(buffer) = self.get_buffer(&buf_tag) {
+            span.record("hit", &true);
+            self.count_block_access(rel, true);
             debug!(
                 "Page {} exists on memory on buffer {}",
                 page_num,
@@ -205,12 +284,14 @@ impl BufferPool {
             self.pin_buffer(&buffer);
             Ok(buffer)
         } else {
-            if self.page_table.len() >= self.size {
+            span.record("hit", &false);
+            self.count_block_access(rel, false);
+            if self.buffer_table.len() >= self.size {
                 debug!("Buffer pool is at full capacity {}", self.size);
                 self.victim()?;
             }
             assert!(
-                self.page_table.len() < self.size && self.buffer_table.len() < self.size,
+                self.buffer_table.len() < self.size,
                 "Buffer pool exceeded the limit of {}",
                 self.size
             );
@@ -223,9 +304,19 @@ impl BufferPool {
                 .pager
                 .read_page(page_num, &mut page.bytes_mut())?;
 
-            // Add page on cache and pin the new buffer.
-            self.page_table.push(Rc::new(RefCell::new(page)));
-            let buffer = BufferData::new(self.page_table.len(), buf_tag.clone());
+            // Add page on cache, reusing a slot released by victim if any, and pin the new buffer.
+            let page = Rc::new(RefCell::new(page));
+            let bufid = match self.free_slots.pop() {
+                Some(bufid) => {
+                    self.page_table[bufid - 1] = page;
+                    bufid
+                }
+                None => {
+                    self.page_table.push(page);
+                    self.page_table.len()
+                }
+            };
+            let buffer = BufferData::new(bufid, buf_tag.clone());
             self.pin_buffer(&buffer);
             self.buffer_table.insert(buf_tag, buffer.clone());
 
@@ -233,6 +324,55 @@ impl BufferPool {
         }
     }
 
+    /// Return all buffers that are still pinned. Once all users of buffer pool are done with
+    /// their pages nothing should be returned, otherwise pins were leaked and these pages can't be
+    /// victimized anymore.
+    pub fn pinned_buffers(&self) -> Vec<PinnedBuffer> {
+        let mut pinned = Vec::new();
+        for (tag, buffer) in &self.buffer_table {
+            let buffer = buffer.borrow();
+            if buffer.refcount == 0 {
+                continue;
+            }
+
+            #[cfg(debug_assertions)]
+            let pinned_at = buffer
+                .pinned_at
+                .iter()
+                .map(|location| location.to_string())
+                .collect();
+            #[cfg(not(debug_assertions))]
+            let pinned_at = Vec::new();
+
+            pinned.push(PinnedBuffer {
+                rel_name: tag.rel.borrow().rel_name.clone(),
+                page_num: tag.page_num,
+                refcount: buffer.refcount,
+                pinned_at,
+            });
+        }
+        pinned
+    }
+
+    /// Return the block I/O counters of the given relation.
+    pub fn block_stats(&self, db_oid: Oid, oid: Oid) -> BlockStats {
+        self.block_stats
+            .get(&(db_oid, oid))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Increment the block I/O counters of the given relation.
+    fn count_block_access(&mut self, rel: &Relation, hit: bool) {
+        let rel = rel.borrow();
+        let stats = self.block_stats.entry((rel.db_oid, rel.oid)).or_default();
+        if hit {
+            stats.blks_hit += 1;
+        } else {
+            stats.blks_read += 1;
+        }
+    }
+
     /// Return the page contents from a buffer.
     pub fn get_page(&self, buffer: &Buffer) -> Page {
         self.page_table[buffer.borrow().id - 1].clone()
@@ -244,11 +384,92 @@ impl BufferPool {
     /// The returned buffer is pinned and is already marked as holding the new page.
     ///
     /// Return error if no new pages could be created, otherwise the buffer.
+    #[track_caller]
     pub fn alloc_buffer(&mut self, rel: &Relation) -> Result<Buffer> {
         let page_num = rel.borrow_mut().pager.allocate_page()?;
         self.fetch_buffer(rel, page_num)
     }
 
+    /// Physically write out all dirty pages of the given relation to disk, without writing the
+    /// pages of other relations.
+    pub fn flush_relation(&mut self, rel: &Relation) -> Result<()> {
+        let _span = debug_span!("flush_relation", rel = %rel.borrow().rel_name).entered();
+        let buffers: Vec<Buffer> = self
+            .buffer_table
+            .iter()
+            .filter(|(tag, buffer)| tag.belongs_to(rel) && buffer.borrow().is_dirty)
+            .map(|(_, buffer)| buffer.clone())
+            .collect();
+
+        for buffer in buffers {
+            self.flush_buffer(&buffer)?;
+            buffer.borrow_mut().is_dirty = false;
+        }
+        Ok(())
+    }
+
+    /// Remove all buffers of the given relation from buffer pool without writing them, e.g
+    /// because the relation is being dropped or its contents replaced.
+    ///
+    /// Return error if any page of the relation is still pinned.
+    pub fn drop_relation(&mut self, rel: &Relation) -> Result<()> {
+        self.discard_buffers(|tag| tag.belongs_to(rel))
+    }
+
+    /// Remove all buffers of all relations of the given database from buffer pool without writing
+    /// them, because the database is being dropped. Block I/O counters of its relations are
+    /// removed too.
+    ///
+    /// Return error if any page of the database is still pinned.
+    pub fn drop_database(&mut self, db_data: &str, db_oid: Oid) -> Result<()> {
+        self.discard_buffers(|tag| {
+            let rel = tag.rel.borrow();
+            rel.db_data == db_data && rel.db_oid == db_oid
+        })?;
+        self.block_stats.retain(|(oid, _), _| *oid != db_oid);
+        Ok(())
+    }
+
+    /// Truncate the given relation to keep only its first total_pages pages. Buffers of removed
+    /// pages are discarded without being written, otherwise flushing them would extend the file
+    /// again.
+    ///
+    /// Return error if any removed page is still pinned.
+    pub fn truncate_relation(&mut self, rel: &Relation, total_pages: PageNumber) -> Result<()> {
+        self.discard_buffers(|tag| tag.belongs_to(rel) && tag.page_num > total_pages)?;
+        rel.borrow_mut().pager.truncate(total_pages)
+    }
+
+    /// Remove from buffer pool, without writing, all buffers whose tag matches the predicate.
+    /// Their page table slots are released to new pages.
+    fn discard_buffers<P>(&mut self, predicate: P) -> Result<()>
+    where
+        P: Fn(&BufferTag) -> bool,
+    {
+        let tags: Vec<BufferTag> = self
+            .buffer_table
+            .keys()
+            .filter(|tag| predicate(tag))
+            .cloned()
+            .collect();
+
+        if let Some(tag) = tags
+            .iter()
+            .find(|tag| self.buffer_table[*tag].borrow().refcount > 0)
+        {
+            bail!(Error::PagePinned(tag.page_num));
+        }
+
+        for tag in tags {
+            if let Some(buffer) = self.buffer_table.remove(&tag) {
+                // Pin removes the buffer from the replacer, so it is never choosen for victim.
+                self.lru.pin(&tag);
+                self.free_slots.push(buffer.borrow().id);
+            }
+        }
+        Ok(())
+    }
+
     /// Make the buffer available for replacement. The buffer is also unpined on lru if the ref count is 0.
     ///
     /// Return error if the buffer does not exists on buffer pool, None otherwise.
@@ -258,6 +479,11 @@ impl BufferPool {
         buffer.is_dirty = buffer.is_dirty || is_dirty;
         buffer.refcount -= 1;
 
+        // Unpins don't know which pin they release, so assume that the most recent one is
+        // released first, which matches the nested fetch and unpin calls used everywhere.
+        #[cfg(debug_assertions)]
+        buffer.pinned_at.pop();
+
         if buffer.refcount == 0 {
             self.lru.unpin(&buffer.tag);
         }
@@ -265,9 +491,12 @@ impl BufferPool {
     }
 
     /// Make buffer unavailable for replacement.
+    #[track_caller]
     fn pin_buffer(&mut self, buffer: &Buffer) {
         let mut buffer = buffer.borrow_mut();
         buffer.refcount += 1;
+        #[cfg(debug_assertions)]
+        buffer.pinned_at.push(Location::caller());
         self.lru.pin(&buffer.tag);
     }
 
@@ -291,6 +520,7 @@ impl BufferPool {
     /// Physically write out a all shared pages stored on buffer pool to disk.
     pub fn flush_all_buffers(&mut self) -> Result<()> {
         debug!("Flushing all buffers to disk");
+        let _span = debug_span!("flush_all_buffers", buffers = self.buffer_table.len()).entered();
         for (_, buf) in self.buffer_table.iter() {
             let page = self.get_page(&buf);
 
@@ -305,8 +535,9 @@ impl BufferPool {
     }
 
     /// Use the LRU replacement policy to choose a page to victim. This function panic if the LRU
-    /// don't have any page id to victim. Otherwise the page will be removed from page table. If
-    /// the choosen page is dirty victim will flush to disk before removing from page table.
+    /// don't have any page id to victim. Otherwise the page will be removed from buffer table and
+    /// its page table slot released to a new page. If the choosen page is dirty victim will flush
+    /// to disk before removing from buffer table.
     fn victim(&mut self) -> Result<()> {
         let buf_tag = self
             .lru
@@ -327,8 +558,8 @@ impl BufferPool {
         }
 
         let bufid = buffer.borrow().id;
-        self.page_table.remove(bufid);
         self.buffer_table.remove(&buf_tag);
+        self.free_slots.push(bufid);
 
         Ok(())
     }
@@ -346,7 +577,10 @@ impl BufferPool {
 
 #[cfg(test)]
 mod tests {
-    use crate::{catalog::new_relation_oid, storage::rel::RelationData};
+    use crate::{
+        catalog::new_relation_oid,
+        storage::rel::{database_path, RelationData},
+    };
 
     use super::*;
 
@@ -419,6 +653,101 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_buffer_pool_reuse_victim_slot() -> Result<()> {
+        let relation = test_relation(10);
+        let mut buffer_pool = BufferPool::new(3);
+
+        // Each page of test relation is filled with its page number minus one, so pages read
+        // after some victims should still have their own contents.
+        for page_num in 1..=10 {
+            let buffer = buffer_pool.fetch_buffer(&relation, page_num)?;
+            let page = buffer_pool.get_page(&buffer);
+            assert_eq!(page.borrow().bytes(), [page_num as u8 - 1; PAGE_SIZE]);
+            buffer_pool.unpin_buffer(buffer, false)?;
+        }
+
+        assert_eq!(buffer_pool.page_table.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_pool_truncate_relation() -> Result<()> {
+        let relation = test_relation(5);
+        let mut buffer_pool = BufferPool::new(10);
+
+        // Dirty the last page, which should not be written back after truncation.
+        let buffer = buffer_pool.fetch_buffer(&relation, 5)?;
+        buffer_pool.unpin_buffer(buffer, true)?;
+
+        let buffer = buffer_pool.fetch_buffer(&relation, 4)?;
+        assert!(buffer_pool.truncate_relation(&relation, 3).is_err());
+        buffer_pool.unpin_buffer(buffer, false)?;
+
+        buffer_pool.truncate_relation(&relation, 3)?;
+        buffer_pool.flush_all_buffers()?;
+        assert_eq!(relation.borrow().pager.size()?, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_pool_flush_and_drop_relation() -> Result<()> {
+        let relation = test_relation(2);
+        let other_relation = test_relation(2);
+        let mut buffer_pool = BufferPool::new(10);
+
+        for rel in [&relation, &other_relation] {
+            let buffer = buffer_pool.fetch_buffer(rel, 1)?;
+            buffer_pool
+                .get_page(&buffer)
+                .borrow_mut()
+                .write([9; PAGE_SIZE]);
+            buffer_pool.unpin_buffer(buffer, true)?;
+        }
+
+        // Only the pages of the flushed relation are written to disk.
+        buffer_pool.flush_relation(&relation)?;
+        let mut page = [0; PAGE_SIZE];
+        relation.borrow_mut().pager.read_page(1, &mut page)?;
+        assert_eq!(page, [9; PAGE_SIZE]);
+        other_relation.borrow_mut().pager.read_page(1, &mut page)?;
+        assert_eq!(page, [0; PAGE_SIZE]);
+
+        // Dropped pages are discarded, so the next fetch reads them from disk again.
+        buffer_pool.drop_relation(&other_relation)?;
+        let buffer = buffer_pool.fetch_buffer(&other_relation, 1)?;
+        assert_eq!(
+            buffer_pool.get_page(&buffer).borrow().bytes(),
+            [0; PAGE_SIZE]
+        );
+        buffer_pool.unpin_buffer(buffer, false)?;
+        assert_eq!(buffer_pool.buffer_table.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_pool_pinned_buffers() -> Result<()> {
+        let relation = test_relation(2);
+        let mut buffer_pool = BufferPool::new(10);
+
+        let buffer = buffer_pool.fetch_buffer(&relation, 1)?;
+        let leaked = buffer_pool.fetch_buffer(&relation, 2)?;
+        buffer_pool.unpin_buffer(buffer, false)?;
+
+        let pinned = buffer_pool.pinned_buffers();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].page_num, 2);
+        if cfg!(debug_assertions) {
+            assert!(pinned[0].pinned_at[0].contains("buffer.rs"));
+        }
+
+        buffer_pool.unpin_buffer(leaked, false)?;
+        assert!(buffer_pool.pinned_buffers().is_empty());
+        Ok(())
+    }
+
     #[test]
     fn test_buffer_pool_fetch_page_from_memory() -> Result<()> {
         let mut buffer = BufferPool::new(10);
@@ -448,13 +777,14 @@ mod tests {
     fn test_relation(pages: usize) -> Relation {
         use rand::prelude::random;
 
-        let db_data = String::from("");
-        let db_name = std::env::temp_dir().to_str().unwrap().to_string();
+        let db_data = std::env::temp_dir().to_str().unwrap().to_string();
+        let db_oid = random::<u32>() as Oid;
+        std::fs::create_dir_all(database_path(&db_data, db_oid)).unwrap();
         let rel_name = format!("tinydb-tempfile-test-{}", random::<i32>()).to_string();
 
-        let oid = new_relation_oid(&db_data, &db_name);
+        let oid = new_relation_oid(&db_data, db_oid);
         let relation =
-            RelationData::open(oid, &db_data, &db_name, &rel_name).expect("Error to open relation");
+            RelationData::open(oid, &db_data, db_oid, &rel_name).expect("Error to open relation");
 
         for i in 0..pages {
             let page_number = relation.borrow_mut().pager.allocate_page().unwrap();
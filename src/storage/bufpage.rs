@@ -1,8 +1,12 @@
+use std::fmt;
 use std::mem::size_of;
 
 use serde::{Deserialize, Serialize};
 
-use super::{buffer::Page, pager::PAGE_SIZE};
+use super::{
+    buffer::Page,
+    pager::{PageNumber, PAGE_SIZE},
+};
 
 /// Represents the fixed size of a page header.
 pub const PAGE_HEADER_SIZE: usize = size_of::<PageHeader>();
@@ -49,9 +53,34 @@ pub struct ItemId {
     pub length: u16,
 }
 
+impl ItemId {
+    /// Return true if the line pointer does not point to any tuple, e.g because the tuple was
+    /// removed. Used line pointers never point to the page header at offset 0.
+    pub fn is_unused(&self) -> bool {
+        self.offset == 0
+    }
+}
+
 /// Size of an item id on heap page.
 pub const ITEM_ID_SIZE: usize = size_of::<ItemId>();
 
+/// Identify the physical location of a tuple by its page number and line pointer number
+/// (starting at 1). An item pointer with page number 0 does not point to any tuple.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ItemPointer {
+    /// Page number where the tuple is stored.
+    pub page_num: PageNumber,
+
+    /// Line pointer number of the tuple inside the page.
+    pub offset: OffsetNumber,
+}
+
+impl fmt::Display for ItemPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({},{})", self.page_num, self.offset)
+    }
+}
+
 /// Add a new item to a page. The page header start_free_space and end_free_space is also updated
 /// to point to the new offsets after the item is inserted on in-memory page.
 pub fn page_add_item(page: &Page, item: &Vec<u8>) -> Result<(), bincode::Error> {
@@ -79,6 +108,19 @@ pub fn page_add_item(page: &Page, item: &Vec<u8>) -> Result<(), bincode::Error>
     Ok(())
 }
 
+/// Mark the line pointer with the given number (starting at 1) as unused. The line pointer is
+/// kept, so the numbers of the other items don't change, and the tuple space is not reclaimed.
+pub fn page_remove_item(page: &Page, offset: OffsetNumber) -> Result<(), bincode::Error> {
+    let item_id = ItemId {
+        offset: 0,
+        length: 0,
+    };
+    let item_id_offset = PAGE_HEADER_SIZE + (offset as usize - 1) * ITEM_ID_SIZE;
+    page.borrow_mut()
+        .write_at(&bincode::serialize(&item_id)?, item_id_offset);
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use std::{cell::RefCell, rc::Rc};
@@ -108,6 +150,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_page_remove_item() -> Result<(), bincode::Error> {
+        let page = Rc::new(RefCell::new(Bytes::<PAGE_SIZE>::new()));
+        page.borrow_mut()
+            .write_at(&bincode::serialize(&PageHeader::default())?, 0);
+
+        page_add_item(&page, &bincode::serialize(&150)?)?;
+        page_add_item(&page, &bincode::serialize(&151)?)?;
+        page_remove_item(&page, 1)?;
+
+        let data = page.borrow().bytes();
+        let first = bincode::deserialize::<ItemId>(&data[PAGE_HEADER_SIZE..])?;
+        let second = bincode::deserialize::<ItemId>(&data[PAGE_HEADER_SIZE + ITEM_ID_SIZE..])?;
+        assert!(first.is_unused());
+        assert!(!second.is_unused());
+        assert_eq!(PageHeader::new(&page)?.start_free_space, 32);
+
+        Ok(())
+    }
+
     #[test]
     fn test_item_id_size() {
         assert_eq!(ITEM_ID_SIZE, 4, "Item id size should have 4 bytes long");
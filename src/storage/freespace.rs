@@ -1,10 +1,44 @@
 use anyhow::Result;
 
-use super::{buffer::Buffer, rel::Relation, BufferPool};
+use super::{
+    buffer::Buffer,
+    bufpage::{PageHeader, ITEM_ID_SIZE},
+    pager::{PageNumber, INVALID_PAGE_NUMBER, PAGE_SIZE},
+    rel::Relation,
+    BufferPool,
+};
 
-/// Try to find a page in the given relation with at least the specified amount of free space.
+/// Try to find a page in the given relation with at least the specified amount of free space,
+/// plus the space of its line pointer. The hint page is tried first and, if it is full or
+/// invalid, a new page is allocated at the end of relation.
 ///
+/// Return the page number and the pinned buffer of the page found.
+//
 // TODO: Implement visibility map to find free page to add a new tuple
-pub fn get_page_with_free_space(buffer: &mut BufferPool, rel: &Relation) -> Result<Buffer> {
-    buffer.fetch_buffer(rel, 1)
+pub fn get_page_with_free_space(
+    buffer: &mut BufferPool,
+    rel: &Relation,
+    hint: PageNumber,
+    size: usize,
+) -> Result<(PageNumber, Buffer)> {
+    if hint != INVALID_PAGE_NUMBER {
+        let buf_id = buffer.fetch_buffer(rel, hint)?;
+        let header = PageHeader::new(&buffer.get_page(&buf_id))?;
+        let free_space = header
+            .end_free_space
+            .saturating_sub(header.start_free_space);
+        if free_space as usize >= size + ITEM_ID_SIZE {
+            return Ok((hint, buf_id));
+        }
+        buffer.unpin_buffer(buf_id, false)?;
+    }
+
+    let page_num = rel.borrow_mut().pager.allocate_page()?;
+    let buf_id = buffer.fetch_buffer(rel, page_num)?;
+
+    let mut data = bincode::serialize(&PageHeader::default())?;
+    data.resize(PAGE_SIZE, u8::default());
+    buffer.get_page(&buf_id).borrow_mut().write_from_vec(data);
+
+    Ok((page_num, buf_id))
 }
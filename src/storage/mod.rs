@@ -3,5 +3,7 @@ pub mod bufpage;
 pub mod freespace;
 pub mod pager;
 pub mod rel;
+pub mod smgr;
+pub mod temp;
 
 pub use buffer::BufferPool;
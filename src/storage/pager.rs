@@ -2,25 +2,31 @@ use anyhow::{bail, Result};
 use log::debug;
 use serde::{Deserialize, Serialize};
 use std::convert::TryInto;
-use std::fs::{File, OpenOptions};
-use std::io::{
-    prelude::{Read, Write},
-    Seek, SeekFrom,
-};
 use std::path::Path;
+use tracing::trace_span;
+
+use super::smgr::{FileStorage, Storage};
 
 /// Represents the tinydb header size.
-const HEADER_SIZE: usize = 100;
+pub const HEADER_SIZE: usize = 100;
 
 /// Represents the size that a Page can have on database file.
 pub const PAGE_SIZE: usize = 8192;
 
+/// Size of the region at the start of a file reserved for its header, since the first page is
+/// stored after [HEADER_SIZE] pages.
+pub const HEADER_REGION_SIZE: u64 = (HEADER_SIZE * PAGE_SIZE) as u64;
+
 /// Represents the first N bytes of the file.
 pub const MAGIC_BYTES_SIZE: usize = 6;
 
 /// Represents the first [MAGIC_BYTES_SIZE] of file.
 pub const MAGIC_BYTES: &[u8; MAGIC_BYTES_SIZE] = b"Tinydb";
 
+/// Version of the on-disk format of database files. Files written with another version can't be
+/// read, and there are no migrations between versions yet, so the data directory must be recreated.
+pub const FORMAT_VERSION: u32 = 2;
+
 /// Represents that a MemPage doest not exists on disk.
 pub const INVALID_PAGE_NUMBER: PageNumber = 0;
 
@@ -52,6 +58,63 @@ pub enum Error {
     /// is different than [MAGIC_BYTES].
     #[error("Corrupted database file")]
     CorruptedFile,
+
+    /// The database file was written with a format version different than [FORMAT_VERSION].
+    #[error(
+        "Incompatible file format version {0}, expected {}; the data directory must be recreated",
+        FORMAT_VERSION
+    )]
+    IncompatibleVersion(u32),
+
+    /// The database file doesn't cover its header region, e.g because it was truncated.
+    #[error("Truncated database file of {len} bytes, expected at least {expected} bytes")]
+    TruncatedFile { len: u64, expected: u64 },
+
+    /// A write failed because a fault was injected with [inject_fault](Pager::inject_fault).
+    #[cfg(any(test, feature = "fault-injection"))]
+    #[error("Injected fault on write of page {0}")]
+    InjectedFault(PageNumber),
+}
+
+/// Kind of fault that can be injected on a pager write.
+#[cfg(any(test, feature = "fault-injection"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// The write fails without writing anything to the file.
+    Fail,
+
+    /// Only the first N bytes of the page are written before the write fails, simulating a torn
+    /// page after a crash.
+    Truncate(usize),
+}
+
+/// A fault that will be triggered on some future write of a pager.
+#[cfg(any(test, feature = "fault-injection"))]
+#[derive(Debug)]
+struct FaultInjection {
+    /// Number of writes that still succeed before the fault is triggered.
+    remaining_writes: usize,
+
+    /// What happens with the write that triggers the fault.
+    fault: Fault,
+}
+
+/// Return the minimum length that a database file with the given length should have to hold its
+/// header and whole pages, or None if the length is valid, e.g to report files that were
+/// truncated before opening them. Empty files and files with only a header are valid.
+pub fn truncated_len(len: u64) -> Option<u64> {
+    let page_size = PAGE_SIZE as u64;
+    if len == 0 || len == HEADER_SIZE as u64 {
+        None
+    } else if len < HEADER_SIZE as u64 {
+        Some(HEADER_SIZE as u64)
+    } else if len < HEADER_REGION_SIZE {
+        Some(HEADER_REGION_SIZE)
+    } else if len % page_size != 0 {
+        Some((len / page_size + 1) * page_size)
+    } else {
+        None
+    }
 }
 
 /// A in memory representation of a pager file header.
@@ -62,6 +125,9 @@ pub enum Error {
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Header {
     magic: [u8; MAGIC_BYTES_SIZE],
+
+    /// Format version used to write the file.
+    version: u32,
 }
 
 impl Header {
@@ -90,12 +156,18 @@ impl Header {
     pub fn deserialize(data: &HeaderData) -> Result<Self> {
         Ok(bincode::deserialize(data)?)
     }
+
+    /// Return the format version used to write the file.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
 }
 
 impl Default for Header {
     fn default() -> Self {
         Self {
             magic: MAGIC_BYTES.clone(),
+            version: FORMAT_VERSION,
         }
     }
 }
@@ -110,8 +182,12 @@ impl Default for Header {
 /// More specifically, pages are read into a MemPage structure.
 #[derive(Debug)]
 pub struct Pager {
-    file: File,
+    storage: Box<dyn Storage>,
     total_pages: u32,
+
+    /// Fault to trigger on a future write, if any.
+    #[cfg(any(test, feature = "fault-injection"))]
+    fault_injection: Option<FaultInjection>,
 }
 
 impl Pager {
@@ -122,14 +198,22 @@ impl Pager {
     /// pager is given a filename for a file that does not exist) then this
     /// function will initialize the file header using the default values.
     pub fn open(filename: &Path) -> Result<Self> {
-        let file = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .open(filename)?;
+        Self::open_storage(Box::new(FileStorage::open(filename)?))
+    }
+
+    /// Open an existing database file only for reading. Unlike [Pager::open], a missing file is
+    /// an error instead of being created.
+    pub fn open_read_only(filename: &Path) -> Result<Self> {
+        Self::open_storage(Box::new(FileStorage::open_read_only(filename)?))
+    }
+
+    /// Open the given storage for paged access, initializing the header if the storage is empty.
+    pub fn open_storage(storage: Box<dyn Storage>) -> Result<Self> {
         let mut pager = Self {
-            file,
+            storage,
             total_pages: 0,
+            #[cfg(any(test, feature = "fault-injection"))]
+            fault_injection: None,
         };
         pager.total_pages = pager.size()?;
 
@@ -145,10 +229,17 @@ impl Pager {
     /// and updates the in-memory MemPage struct passed on page arg.
     /// Any changes done to a MemPage will not be effective until call
     /// the [write_page](Pager::write_page] with that MemPage.
+    ///
+    /// If the page is beyond the last known page, the file size is computed again before
+    /// failing, since the file could be appended by another process (e.g a primary database
+    /// writing to a file that a read-only standby is reading).
     pub fn read_page(&mut self, page_number: PageNumber, page: &mut MemPage) -> Result<()> {
+        let _span = trace_span!("read_page", page_number).entered();
+        if page_number > self.total_pages {
+            self.total_pages = self.size()?;
+        }
         self.validate_page(page_number)?;
-        self.file.seek(SeekFrom::Start(self.offset(page_number)))?;
-        let count = self.file.read(page)?;
+        let count = self.storage.read_at(self.offset(page_number), page)?;
         debug!("Read {} bytes from page {}", count, page_number);
         Ok(())
     }
@@ -156,44 +247,66 @@ impl Pager {
     /// Write a page to file. This pager writes the in-memory copy of a
     /// page (stored in a MemPage struct) back to disk.
     pub fn write_page(&mut self, number: PageNumber, page: &MemPage) -> Result<()> {
+        let _span = trace_span!("write_page", page_number = number).entered();
         self.validate_page(number)?;
-        self.file.seek(SeekFrom::Start(self.offset(number)))?;
-        let count = self.file.write(page)?;
-        debug!("Wrote {} bytes to page {}", count, number);
+
+        #[cfg(any(test, feature = "fault-injection"))]
+        self.trigger_fault(number, page)?;
+
+        self.storage.write_at(self.offset(number), page)?;
+        debug!("Wrote {} bytes to page {}", page.len(), number);
         Ok(())
     }
 
     /// Allocate an extra page on the file and returns the page number
     pub fn allocate_page(&mut self) -> Result<u32> {
+        // The same file may be opened by other pagers that allocated pages since the last time
+        // that total_pages was read, so shared storages allocate after the current last page on
+        // disk.
+        if self.storage.is_shared() {
+            self.total_pages = self.size()?;
+        }
         self.total_pages += 1;
         self.write_page(self.total_pages, &[0; PAGE_SIZE])?;
         Ok(self.total_pages)
     }
 
+    /// Truncate the file to keep only its first total_pages pages, so the space of removed pages is
+    /// returned to the operating system. Nothing is done if the file is not bigger than that.
+    pub fn truncate(&mut self, total_pages: PageNumber) -> Result<()> {
+        if total_pages >= self.size()? {
+            return Ok(());
+        }
+        self.storage.set_len(self.offset(total_pages + 1))?;
+        self.total_pages = total_pages;
+        Ok(())
+    }
+
     /// Reads the header of database file and returns it in a byte array.
     /// Note that this function can be called even if the page size is unknown,
     /// since the chidb header always occupies the first 100 bytes of the file.
     pub fn read_header(&mut self) -> Result<Header> {
-        self.file.seek(SeekFrom::Start(0))?;
         let mut header = [0; HEADER_SIZE];
-        self.file.read(&mut header)?;
+        self.storage.read_at(0, &mut header)?;
         Ok(Header::deserialize(&header)?)
     }
 
     /// Write the header on database file. Note that the write_header function will
     /// always override the current header data if exists.
     pub fn write_header(&mut self, header: &Header) -> Result<()> {
-        self.file.seek(SeekFrom::Start(0))?;
-        self.file.write(&header.serialize()?)?;
+        self.storage.write_at(0, &header.serialize()?)?;
         Ok(())
     }
 
     /// Computes the number of pages in a file.
+    ///
+    /// A partial page at the end of file, e.g of a torn write while allocating it, is not counted.
+    /// Files that don't cover their header region fail with [Error::TruncatedFile].
     pub fn size(&self) -> Result<u32> {
-        let len = self.file.metadata()?.len();
-        if len == 0 || len as usize - HEADER_SIZE == 0 {
+        let len = self.storage.len()?;
+        if len == 0 || len == HEADER_SIZE as u64 {
             // If len is equal 0 means that the file is empty.
-            // If len - HEADER_SIZE is equal 0 means that the
+            // If len is equal HEADER_SIZE means that the
             // file doest not have any page, so in both case
             // return 0.
             return Ok(0);
@@ -201,7 +314,49 @@ impl Pager {
         // Otherwise we calculate the total of
         // pages in file and finally substract with the
         // HEADER_SIZE to get the total of pages in file.
-        Ok((len as u32 / PAGE_SIZE as u32) - HEADER_SIZE as u32)
+        match (len / PAGE_SIZE as u64).checked_sub(HEADER_SIZE as u64) {
+            Some(total_pages) => Ok(total_pages as u32),
+            None => bail!(Error::TruncatedFile {
+                len,
+                expected: HEADER_REGION_SIZE,
+            }),
+        }
+    }
+
+    /// Make the Nth page write from now on fail, where N starts at 1. Writes before it succeed
+    /// as usual, and the fault is triggered only once.
+    ///
+    /// This is only available for tests and with the `fault-injection` feature, so crash
+    /// recovery and torn page handling can be tested deterministically.
+    #[cfg(any(test, feature = "fault-injection"))]
+    pub fn inject_fault(&mut self, write_num: usize, fault: Fault) {
+        assert!(write_num > 0, "Expected write number to start at 1");
+        self.fault_injection = Some(FaultInjection {
+            remaining_writes: write_num - 1,
+            fault,
+        });
+    }
+
+    /// Trigger the injected fault if the current write is the faulty one.
+    #[cfg(any(test, feature = "fault-injection"))]
+    fn trigger_fault(&mut self, number: PageNumber, page: &MemPage) -> Result<()> {
+        let injection = match &mut self.fault_injection {
+            Some(injection) => injection,
+            None => return Ok(()),
+        };
+
+        if injection.remaining_writes > 0 {
+            injection.remaining_writes -= 1;
+            return Ok(());
+        }
+
+        if let Fault::Truncate(len) = injection.fault {
+            self.storage
+                .write_at(self.offset(number), &page[..len.min(PAGE_SIZE)])?;
+        }
+        self.fault_injection = None;
+
+        bail!(Error::InjectedFault(number));
     }
 
     /// Check if a pager number is valid to this database file buffer.
@@ -220,7 +375,7 @@ impl Pager {
 
     /// Check if file buffer is empty.
     fn is_empty(&self) -> Result<bool> {
-        Ok(self.file.metadata()?.len() == 0)
+        self.storage.is_empty()
     }
 
     /// Check if the header data is valid on disk.
@@ -232,6 +387,10 @@ impl Pager {
             bail!(Error::CorruptedFile);
         }
 
+        if header.version != FORMAT_VERSION {
+            bail!(Error::IncompatibleVersion(header.version));
+        }
+
         Ok(())
     }
 
@@ -244,6 +403,8 @@ impl Pager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::smgr::MemoryStorage;
+    use std::io::Write;
     use tempfile::NamedTempFile;
 
     #[test]
@@ -299,6 +460,58 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_pager_size_truncated_file() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        let mut pager = Pager::open(file.path())?;
+        pager.allocate_page()?;
+
+        file.as_file().set_len(4000)?;
+        let err = pager.size().unwrap_err();
+        assert_eq!(
+            Error::TruncatedFile {
+                len: 4000,
+                expected: HEADER_REGION_SIZE,
+            },
+            err.downcast::<Error>()?
+        );
+        assert!(pager.read_page(2, &mut [0; PAGE_SIZE]).is_err());
+        assert!(Pager::open(file.path()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncated_len() {
+        let page_size = PAGE_SIZE as u64;
+        assert_eq!(truncated_len(0), None);
+        assert_eq!(truncated_len(HEADER_SIZE as u64), None);
+        assert_eq!(truncated_len(HEADER_REGION_SIZE), None);
+        assert_eq!(truncated_len(HEADER_REGION_SIZE + 2 * page_size), None);
+        assert_eq!(truncated_len(50), Some(HEADER_SIZE as u64));
+        assert_eq!(truncated_len(4000), Some(HEADER_REGION_SIZE));
+        assert_eq!(
+            truncated_len(HEADER_REGION_SIZE + page_size + 10),
+            Some(HEADER_REGION_SIZE + 2 * page_size)
+        );
+    }
+
+    #[test]
+    fn test_allocate_page_shared_file() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        let mut pager = Pager::open(file.path())?;
+        let mut other = Pager::open(file.path())?;
+        assert_eq!(1, pager.allocate_page()?);
+        assert_eq!(2, other.allocate_page()?);
+        assert_eq!(3, pager.allocate_page()?);
+
+        // Allocation on a truncated file fails instead of overriding the header region.
+        file.as_file().set_len(4000)?;
+        assert!(pager.allocate_page().is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_write_read_pages() -> Result<()> {
         let mut pager = open_test_pager()?;
@@ -343,6 +556,103 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_incompatible_version() -> Result<()> {
+        let mut file = NamedTempFile::new()?;
+        let header = Header {
+            magic: MAGIC_BYTES.clone(),
+            version: FORMAT_VERSION + 1,
+        };
+        file.write(&header.serialize()?)?;
+        let result = Pager::open(file.path());
+
+        let err = result.unwrap_err();
+        assert_eq!(
+            Error::IncompatibleVersion(FORMAT_VERSION + 1),
+            err.downcast::<Error>().unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_storage_pager() -> Result<()> {
+        let mut pager = Pager::open_storage(Box::new(MemoryStorage::default()))?;
+        assert_eq!(pager.read_header()?, Header::default());
+
+        for i in 0..5 {
+            let page_number = pager.allocate_page()?;
+            pager.write_page(page_number, &[i; PAGE_SIZE])?;
+        }
+        assert_eq!(5, pager.size()?);
+
+        let mut page = [0; PAGE_SIZE];
+        pager.read_page(3, &mut page)?;
+        assert_eq!([2; PAGE_SIZE], page);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate() -> Result<()> {
+        let mut pager = open_test_pager()?;
+        for i in 0..5 {
+            let page_number = pager.allocate_page()?;
+            pager.write_page(page_number, &[i; PAGE_SIZE])?;
+        }
+
+        pager.truncate(2)?;
+        assert_eq!(2, pager.size()?);
+        assert!(pager.read_page(3, &mut [0; PAGE_SIZE]).is_err());
+
+        // Pages allocated after truncation reuse the removed page numbers.
+        assert_eq!(3, pager.allocate_page()?);
+        let mut page = [1; PAGE_SIZE];
+        pager.read_page(3, &mut page)?;
+        assert_eq!([0; PAGE_SIZE], page);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inject_fault() -> Result<()> {
+        let mut pager = open_test_pager()?;
+        let page_number = pager.allocate_page()?;
+
+        pager.inject_fault(2, Fault::Fail);
+        pager.write_page(page_number, &[1; PAGE_SIZE])?;
+
+        let err = pager.write_page(page_number, &[2; PAGE_SIZE]).unwrap_err();
+        assert_eq!(
+            Error::InjectedFault(page_number),
+            err.downcast::<Error>().unwrap()
+        );
+
+        let mut page = [0; PAGE_SIZE];
+        pager.read_page(page_number, &mut page)?;
+        assert_eq!([1; PAGE_SIZE], page);
+
+        // The fault is triggered only once.
+        pager.write_page(page_number, &[2; PAGE_SIZE])?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inject_torn_page() -> Result<()> {
+        let mut pager = open_test_pager()?;
+        let page_number = pager.allocate_page()?;
+
+        pager.inject_fault(1, Fault::Truncate(100));
+        assert!(pager.write_page(page_number, &[1; PAGE_SIZE]).is_err());
+
+        let mut page = [0; PAGE_SIZE];
+        pager.read_page(page_number, &mut page)?;
+        assert_eq!([1; 100], page[..100]);
+        assert_eq!([0; PAGE_SIZE - 100], page[100..]);
+
+        Ok(())
+    }
+
     #[test]
     fn test_open_new_pager() -> Result<()> {
         let mut pager = open_test_pager()?;
@@ -351,6 +661,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_read_page_appended_by_other_pager() -> Result<()> {
+        let file = NamedTempFile::new()?;
+        let mut reader = Pager::open(file.path())?;
+
+        // Append a new page using another file handle.
+        let mut writer = Pager::open(file.path())?;
+        let page_number = writer.allocate_page()?;
+        let page_data = [7; PAGE_SIZE];
+        writer.write_page(page_number, &page_data)?;
+
+        let mut page = [0; PAGE_SIZE];
+        reader.read_page(page_number, &mut page)?;
+
+        assert_eq!(page_data, page);
+        Ok(())
+    }
+
     fn open_test_pager() -> Result<Pager> {
         let file = NamedTempFile::new()?;
         Pager::open(file.path())
@@ -1,10 +1,31 @@
 use anyhow::Result;
-use std::{cell::RefCell, path::Path, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
+use crate::access::reloptions::RelOptions;
+use crate::catalog::{GLOBAL_DB_NAME, GLOBAL_DB_OID};
+use crate::lru::LRU;
 use crate::Oid;
 
 use super::pager::Pager;
 
+/// Maximum number of relations that the relation cache keeps open.
+pub const MAX_CACHED_RELATIONS: usize = 128;
+
+thread_local! {
+    /// Relations opened by this thread indexed by their file path.
+    static RELATION_CACHE: RefCell<RelationCache> =
+        RefCell::new(RelationCache::new(MAX_CACHED_RELATIONS));
+
+    /// Number of read-only users of each db data on this thread, e.g read-only engines. Relation
+    /// files of these db data are opened without write permissions and never created.
+    static READ_ONLY_DATA: RefCell<HashMap<String, usize>> = RefCell::default();
+}
+
 /// Relation provide all information that we need to know to physically access a database relation.
 pub struct RelationData {
     /// Oid of relation.
@@ -13,29 +34,221 @@ pub struct RelationData {
     /// Path where database files are stored.
     pub db_data: String,
 
-    /// Name of database that this relation belongs.
-    pub db_name: String,
+    /// Oid of database that this relation belongs.
+    pub db_oid: Oid,
 
     /// Name of this relation.
     pub rel_name: String,
 
     /// File pager handle.
     pub pager: Pager,
+
+    /// Options parsed from pg_class.reloptions, set when the relation is opened through the
+    /// catalog. Relations opened directly, like system catalogs, have the default options.
+    pub options: RelOptions,
 }
 
 /// A mutable reference counter to an RelationData.
 pub type Relation = Rc<RefCell<RelationData>>;
 
 impl RelationData {
-    /// Open any relation to the given db data path and db name and relation name.
-    pub fn open(oid: Oid, db_data: &str, db_name: &str, rel_name: &str) -> Result<Relation> {
-        let pager = Pager::open(&Path::new(db_data).join(db_name).join(oid.to_string()))?;
-        Ok(Rc::new(RefCell::new(RelationData {
+    /// Open any relation to the given db data path and db oid and relation name. The relation file
+    /// is stored at `<db_data>/<db_oid>/<oid>`, so renaming a relation don't move its file.
+    ///
+    /// Opened relations are cached, so opening the same relation again reuses its pager and file
+    /// handle instead of opening the file again.
+    pub fn open(oid: Oid, db_data: &str, db_oid: Oid, rel_name: &str) -> Result<Relation> {
+        let path = relation_path(db_data, db_oid, oid);
+        if let Some(rel) = RELATION_CACHE.with(|cache| cache.borrow_mut().get(&path)) {
+            return Ok(rel);
+        }
+
+        let pager = if is_read_only(db_data) {
+            Pager::open_read_only(&path)?
+        } else {
+            Pager::open(&path)?
+        };
+        let rel = Rc::new(RefCell::new(RelationData {
             oid,
             pager,
             db_data: db_data.to_string(),
-            db_name: db_name.to_string(),
+            db_oid,
             rel_name: rel_name.to_string(),
-        })))
+            options: RelOptions::default(),
+        }));
+        RELATION_CACHE.with(|cache| cache.borrow_mut().insert(path, rel.clone()));
+
+        Ok(rel)
+    }
+}
+
+/// Open the relation files of db_data only for reading until [release_read_only] is called as
+/// many times as this function, so missing files are errors instead of being created. Relations
+/// of db_data already opened for writing are closed.
+pub fn acquire_read_only(db_data: &str) {
+    let users = READ_ONLY_DATA.with(|data| {
+        let mut data = data.borrow_mut();
+        let users = data.entry(db_data.to_string()).or_default();
+        *users += 1;
+        *users
+    });
+    if users == 1 {
+        RELATION_CACHE.with(|cache| cache.borrow_mut().remove_all(Path::new(db_data)));
+    }
+}
+
+/// Release a read-only use of db_data acquired by [acquire_read_only]. Once all uses are released,
+/// relation files of db_data are opened for writing again.
+pub fn release_read_only(db_data: &str) {
+    let released = READ_ONLY_DATA.with(|data| {
+        let mut data = data.borrow_mut();
+        match data.get_mut(db_data) {
+            Some(users) if *users > 1 => {
+                *users -= 1;
+                false
+            }
+            Some(_) => {
+                data.remove(db_data);
+                true
+            }
+            None => false,
+        }
+    });
+    if released {
+        RELATION_CACHE.with(|cache| cache.borrow_mut().remove_all(Path::new(db_data)));
+    }
+}
+
+/// Return true if relation files of db_data should be opened only for reading.
+fn is_read_only(db_data: &str) -> bool {
+    READ_ONLY_DATA.with(|data| data.borrow().contains_key(db_data))
+}
+
+/// Remove all relations of the given database from the relation cache, closing their files unless
+/// they are still referenced somewhere else.
+pub fn evict_database(db_data: &str, db_oid: Oid) {
+    let path = database_path(db_data, db_oid);
+    RELATION_CACHE.with(|cache| cache.borrow_mut().remove_all(&path));
+}
+
+/// Return the directory where relations of the given database are stored. Relations shared across
+/// all databases are stored on the global directory.
+pub fn database_path(db_data: &str, db_oid: Oid) -> PathBuf {
+    if db_oid == GLOBAL_DB_OID {
+        Path::new(db_data).join(GLOBAL_DB_NAME)
+    } else {
+        Path::new(db_data).join(db_oid.to_string())
+    }
+}
+
+/// Return the path of the file that stores the given relation.
+pub fn relation_path(db_data: &str, db_oid: Oid, oid: Oid) -> PathBuf {
+    database_path(db_data, db_oid).join(oid.to_string())
+}
+
+/// Cache of open relations with a limited size. When the cache is full the least recently opened
+/// relation is removed from it.
+///
+/// Note that removed relations still referenced somewhere else, e.g by buffers of the buffer
+/// pool, keep their file open until all references are dropped.
+struct RelationCache {
+    /// Maximum number of cached relations.
+    size: usize,
+
+    /// Cached relations indexed by their file path.
+    relations: HashMap<PathBuf, Relation>,
+
+    /// Replacer used to find the relation to remove when the cache is full.
+    lru: LRU<PathBuf>,
+}
+
+impl RelationCache {
+    /// Create a new empty cache that holds at most size relations.
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            relations: HashMap::with_capacity(size),
+            lru: LRU::new(size),
+        }
+    }
+
+    /// Return the cached relation of the given path, marking it as recently used.
+    fn get(&mut self, path: &Path) -> Option<Relation> {
+        let rel = self.relations.get(path)?.clone();
+        self.touch(path);
+        Some(rel)
+    }
+
+    /// Add a new relation on cache, removing the least recently used one if the cache is full.
+    fn insert(&mut self, path: PathBuf, rel: Relation) {
+        if self.relations.len() >= self.size {
+            if let Some(victim) = self.lru.victim() {
+                self.relations.remove(&victim);
+            }
+        }
+        self.touch(&path);
+        self.relations.insert(path, rel);
+    }
+
+    /// Remove all cached relations stored inside the given directory.
+    fn remove_all(&mut self, dir: &Path) {
+        let paths: Vec<PathBuf> = self
+            .relations
+            .keys()
+            .filter(|path| path.starts_with(dir))
+            .cloned()
+            .collect();
+
+        for path in paths {
+            self.relations.remove(&path);
+            // Pin removes the path from the replacer, so it is never choosen for victim.
+            self.lru.pin(&path);
+        }
+    }
+
+    /// Move the given path to the most recently used position of replacer.
+    fn touch(&mut self, path: &Path) {
+        let path = path.to_path_buf();
+        self.lru.pin(&path);
+        self.lru.unpin(&path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_relation_cache() -> Result<()> {
+        let dir = tempdir()?;
+        let db_data = dir.path().to_string_lossy().to_string();
+        std::fs::create_dir(database_path(&db_data, 1))?;
+        assert_eq!(
+            relation_path(&db_data, 1, 2),
+            dir.path().join("1").join("2")
+        );
+
+        let rel = RelationData::open(2, &db_data, 1, "t1")?;
+        assert!(Rc::ptr_eq(&rel, &RelationData::open(2, &db_data, 1, "t1")?));
+
+        evict_database(&db_data, 1);
+        assert!(!Rc::ptr_eq(
+            &rel,
+            &RelationData::open(2, &db_data, 1, "t1")?
+        ));
+
+        let mut cache = RelationCache::new(2);
+        for oid in 1..=3 {
+            cache.insert(dir.path().join(oid.to_string()), rel.clone());
+        }
+        assert!(cache.get(&dir.path().join("1")).is_none());
+        assert!(cache.get(&dir.path().join("3")).is_some());
+
+        // Relation 2 is the least recently used now, since 3 was just read.
+        cache.insert(dir.path().join("4"), rel);
+        assert!(cache.get(&dir.path().join("2")).is_none());
+        assert!(cache.get(&dir.path().join("3")).is_some());
+        Ok(())
     }
 }
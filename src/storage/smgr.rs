@@ -0,0 +1,200 @@
+use anyhow::{bail, Context, Result};
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{
+    self,
+    prelude::{Read, Write},
+    Seek, SeekFrom,
+};
+use std::path::Path;
+
+/// Errors related with storages.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    /// The storage was opened without write permissions.
+    #[error("cannot write to a read-only file")]
+    ReadOnly,
+}
+
+/// Storage where a pager reads and writes the bytes of a database file.
+///
+/// The pager only needs positional reads and writes, so alternative storages (e.g in memory or
+/// browser storage on platforms without a file system) can be plugged in by implementing this
+/// trait and opening the pager with [open_storage](super::pager::Pager::open_storage).
+pub trait Storage: Debug {
+    /// Read bytes starting at the given offset into buf, returning the number of bytes read. Reading
+    /// beyond the end of storage is not an error, but reads less bytes than buf length.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize>;
+
+    /// Write all bytes of buf starting at the given offset, growing the storage if needed.
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<()>;
+
+    /// Return the size of storage in bytes.
+    fn len(&self) -> Result<u64>;
+
+    /// Truncate or extend the storage to the given size in bytes. Extended bytes are zeroed.
+    fn set_len(&mut self, len: u64) -> Result<()>;
+
+    /// Check if the storage does not have any byte.
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Check if the storage can be written by other pagers at the same time, e.g a file opened by
+    /// other threads or processes. Pagers compute the size of shared storages again before
+    /// allocating pages, instead of trusting the pages that they know.
+    fn is_shared(&self) -> bool {
+        false
+    }
+}
+
+/// Storage backed by a file on disk.
+#[derive(Debug)]
+pub struct FileStorage {
+    file: File,
+
+    /// If true the file was opened without write permissions.
+    read_only: bool,
+}
+
+impl FileStorage {
+    /// Open the given file for reading and writing, creating it if does not exist.
+    ///
+    /// If there is no permission to write the file it is opened as read-only, so data directories
+    /// without write permissions can still be queried.
+    pub fn open(filename: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(filename);
+
+        match file {
+            Ok(file) => Ok(Self {
+                file,
+                read_only: false,
+            }),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                Self::open_read_only(filename)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Open an existing file only for reading. Any write fails with [Error::ReadOnly].
+    ///
+    /// Return error if the file does not exist.
+    pub fn open_read_only(filename: &Path) -> Result<Self> {
+        let file = File::open(filename)
+            .with_context(|| format!("could not open file \"{}\"", filename.display()))?;
+        Ok(Self {
+            file,
+            read_only: true,
+        })
+    }
+}
+
+impl Storage for FileStorage {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        Ok(self.file.read(buf)?)
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<()> {
+        if self.read_only {
+            bail!(Error::ReadOnly);
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(buf)?;
+        Ok(())
+    }
+
+    fn len(&self) -> Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    fn set_len(&mut self, len: u64) -> Result<()> {
+        if self.read_only {
+            bail!(Error::ReadOnly);
+        }
+        self.file.set_len(len)?;
+        Ok(())
+    }
+
+    fn is_shared(&self) -> bool {
+        true
+    }
+}
+
+/// Storage that keep all bytes in memory. All data is lost when the storage is dropped.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    data: Vec<u8>,
+}
+
+impl Storage for MemoryStorage {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let offset = offset as usize;
+        if offset >= self.data.len() {
+            return Ok(0);
+        }
+        let count = buf.len().min(self.data.len() - offset);
+        buf[..count].copy_from_slice(&self.data[offset..offset + count]);
+        Ok(count)
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<()> {
+        let offset = offset as usize;
+        if self.data.len() < offset + buf.len() {
+            self.data.resize(offset + buf.len(), 0);
+        }
+        self.data[offset..offset + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn len(&self) -> Result<u64> {
+        Ok(self.data.len() as u64)
+    }
+
+    fn set_len(&mut self, len: u64) -> Result<()> {
+        self.data.resize(len as usize, 0);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_storage_read_only() -> Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        FileStorage::open(file.path())?.write_at(0, &[1, 2, 3])?;
+
+        let mut storage = FileStorage::open_read_only(file.path())?;
+        let mut buf = [0; 3];
+        storage.read_at(0, &mut buf)?;
+        assert_eq!(buf, [1, 2, 3]);
+
+        let err = storage.write_at(0, &[4]).unwrap_err();
+        assert_eq!(Error::ReadOnly, err.downcast::<Error>().unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_storage_read_write() -> Result<()> {
+        let mut storage = MemoryStorage::default();
+        assert!(storage.is_empty()?);
+
+        storage.write_at(4, &[1, 2, 3])?;
+        assert_eq!(storage.len()?, 7);
+
+        let mut buf = [9; 5];
+        assert_eq!(storage.read_at(3, &mut buf)?, 4);
+        assert_eq!(buf, [0, 1, 2, 3, 9]);
+
+        assert_eq!(storage.read_at(10, &mut buf)?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::Result;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Name of the directory of db data where temporary files are created.
+pub const TEMP_DIR_NAME: &str = "pgsql_tmp";
+
+/// Prefix of the names of temporary files, which are followed by the id of the process that
+/// created them and a counter, e.g `pgsql_tmp1234.5`.
+const TEMP_FILE_PREFIX: &str = "pgsql_tmp";
+
+/// Counter of the temporary files created by this process, so their names are unique across
+/// threads.
+static NEXT_TEMP_FILE: AtomicU64 = AtomicU64::new(0);
+
+/// Return the directory where temporary files of the given db data are created.
+pub fn temp_dir(db_data: &str) -> PathBuf {
+    Path::new(db_data).join(TEMP_DIR_NAME)
+}
+
+/// Remove the temporary files left on db data by processes that are not running anymore, e.g
+/// because they crashed before dropping them. Files of running processes are kept, since they may
+/// be used by other engines over the same db data, including the engines of other threads of this
+/// process.
+///
+/// Return the number of removed files.
+pub fn remove_temp_files(db_data: &str) -> Result<usize> {
+    let dir = temp_dir(db_data);
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let pid = name
+            .strip_prefix(TEMP_FILE_PREFIX)
+            .and_then(|name| name.split('.').next())
+            .and_then(|pid| pid.parse::<u32>().ok());
+        match pid {
+            Some(pid) if !process_alive(pid) => {}
+            _ => continue,
+        }
+
+        fs::remove_file(&path)?;
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+/// Return true if the process with the given id is running. Only Linux exposes the running
+/// processes without system calls, so other platforms consider all processes alive.
+pub fn process_alive(pid: u32) -> bool {
+    if pid == process::id() {
+        return true;
+    }
+    if cfg!(target_os = "linux") {
+        Path::new("/proc").join(pid.to_string()).exists()
+    } else {
+        true
+    }
+}
+
+/// Temporary file used by operators that don't fit on memory, like spilled sorts and hash
+/// tables. The file is removed when it is dropped, and by [remove_temp_files] if the process
+/// crashes before that.
+#[derive(Debug)]
+pub struct TempFile {
+    file: File,
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Create a new empty temporary file on the temporary directory of the given db data,
+    /// creating the directory if needed.
+    pub fn create(db_data: &str) -> io::Result<Self> {
+        let dir = temp_dir(db_data);
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!(
+            "{}{}.{}",
+            TEMP_FILE_PREFIX,
+            process::id(),
+            NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        Ok(Self { file, path })
+    }
+
+    /// Return the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // The file may be already removed by another process cleaning up the db data, which is
+        // fine since the open handle kept its data readable.
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Read for TempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for TempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_temp_file() -> Result<()> {
+        let db_data = tempdir()?;
+        let db_data = db_data.path().to_string_lossy().to_string();
+        assert_eq!(remove_temp_files(&db_data)?, 0);
+
+        let mut file = TempFile::create(&db_data)?;
+        let path = file.path().to_path_buf();
+        assert!(path.starts_with(temp_dir(&db_data)));
+
+        file.write_all(b"tinydb")?;
+        file.seek(SeekFrom::Start(0))?;
+        let mut data = String::new();
+        file.read_to_string(&mut data)?;
+        assert_eq!(data, "tinydb");
+
+        // Files of this process are in use, so they are not removed.
+        assert_eq!(remove_temp_files(&db_data)?, 0);
+        drop(file);
+        assert!(!path.exists());
+
+        // Files left by a crashed process are removed, files of running processes and other
+        // files are kept.
+        let dir = temp_dir(&db_data);
+        fs::write(dir.join(format!("{}0.1", TEMP_FILE_PREFIX)), b"")?;
+        fs::write(dir.join(format!("{}1.1", TEMP_FILE_PREFIX)), b"")?;
+        fs::write(dir.join("README"), b"")?;
+        let removed = if cfg!(target_os = "linux") { 1 } else { 0 };
+        assert_eq!(remove_temp_files(&db_data)?, removed);
+        assert_eq!(fs::read_dir(&dir)?.count(), 3 - removed);
+
+        Ok(())
+    }
+}
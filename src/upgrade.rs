@@ -0,0 +1,192 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::control::ControlFile;
+use crate::engine::sessions::SESSIONS_DIR_NAME;
+use crate::storage::pager::{truncated_len, Header, FORMAT_VERSION, HEADER_SIZE};
+use crate::storage::temp::TEMP_DIR_NAME;
+
+/// Errors related with data directory upgrades.
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("{0} is not a tinydb data directory: control file not found")]
+    NotDataDirectory(String),
+
+    #[error("{path} was written with format version {found}, and there is no migration to format version {expected}; the data directory must be recreated")]
+    NoMigration {
+        path: String,
+        found: u32,
+        expected: u32,
+    },
+
+    #[error("{0} is corrupted: relation file is truncated or has a partial page")]
+    TruncatedRelation(String),
+}
+
+/// Upgrade the data directory at db_data to the current [FORMAT_VERSION].
+///
+/// The control file and every relation file are checked. There are no migrations between format
+/// versions yet, so files written by another version are reported as incompatible with the path
+/// of the offending file, instead of failing later with deserialization errors.
+///
+/// Return the number of files that were checked.
+pub fn upgrade(db_data: &Path) -> Result<usize> {
+    let control_path = ControlFile::path(db_data);
+    if !control_path.exists() {
+        bail!(Error::NotDataDirectory(db_data.display().to_string()));
+    }
+
+    // The format version is always the first field of control file.
+    let data = fs::read(&control_path)?;
+    check_version(&control_path, bincode::deserialize::<u32>(&data)?)?;
+    ControlFile::read(db_data)?.validate()?;
+
+    let mut checked = 1;
+    for db_dir in fs::read_dir(db_data)? {
+        let db_dir = db_dir?.path();
+        // Temporary files and sessions don't have a header and are never kept across versions.
+        if !db_dir.is_dir()
+            || db_dir.ends_with(TEMP_DIR_NAME)
+            || db_dir.ends_with(SESSIONS_DIR_NAME)
+        {
+            continue;
+        }
+
+        for rel_file in fs::read_dir(&db_dir)? {
+            let rel_path = rel_file?.path();
+            if rel_path == control_path {
+                continue;
+            }
+
+            // Relation files should cover their header region and only have whole pages.
+            let mut file = File::open(&rel_path)?;
+            let len = file.metadata()?.len();
+            if len < HEADER_SIZE as u64 || truncated_len(len).is_some() {
+                bail!(Error::TruncatedRelation(rel_path.display().to_string()));
+            }
+
+            let mut data = [0; HEADER_SIZE];
+            file.read_exact(&mut data)?;
+            check_version(&rel_path, Header::deserialize(&data)?.version())?;
+
+            checked += 1;
+        }
+    }
+
+    Ok(checked)
+}
+
+/// Check that a file written with the given version can be read by this build.
+fn check_version(path: &Path, version: u32) -> Result<()> {
+    if version != FORMAT_VERSION {
+        bail!(Error::NoMigration {
+            path: path.display().to_string(),
+            found: version,
+            expected: FORMAT_VERSION,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{pg_class, Catalog};
+    use crate::initdb::init_database;
+    use crate::storage::pager::{MAGIC_BYTES, PAGE_SIZE};
+    use crate::storage::rel::relation_path;
+    use crate::storage::temp::TempFile;
+    use crate::storage::BufferPool;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_upgrade_current_data_directory() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        // Temporary files are skipped.
+        let _file = TempFile::create(&db_data.path().to_string_lossy())?;
+
+        assert!(upgrade(db_data.path())? > 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_incompatible_relation_file() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        // Override the pg_class header with a header without format version.
+        let db_oid = Catalog::new(&db_data.path().to_string_lossy())
+            .get_database_oid(&mut BufferPool::new(1), "db")?;
+        let rel_path = relation_path(
+            &db_data.path().to_string_lossy(),
+            db_oid,
+            pg_class::RELATION_OID,
+        );
+        let mut header = MAGIC_BYTES.to_vec();
+        header.resize(HEADER_SIZE, 0);
+        OpenOptions::new()
+            .write(true)
+            .open(&rel_path)?
+            .write_all(&header)?;
+
+        let err = upgrade(db_data.path()).unwrap_err();
+        assert_eq!(
+            Error::NoMigration {
+                path: rel_path.display().to_string(),
+                found: 0,
+                expected: FORMAT_VERSION,
+            },
+            err.downcast::<Error>().unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_truncated_relation_file() -> Result<()> {
+        let db_data = tempdir()?;
+        init_database(&db_data.path().to_path_buf(), "db")?;
+
+        let db_oid = Catalog::new(&db_data.path().to_string_lossy())
+            .get_database_oid(&mut BufferPool::new(1), "db")?;
+        let rel_path = relation_path(
+            &db_data.path().to_string_lossy(),
+            db_oid,
+            pg_class::RELATION_OID,
+        );
+
+        // Cut the file in the middle of its last page, inside the header region and before the
+        // end of header.
+        let len = fs::metadata(&rel_path)?.len();
+        for len in [len - PAGE_SIZE as u64 / 2, 4000, 0] {
+            OpenOptions::new()
+                .write(true)
+                .open(&rel_path)?
+                .set_len(len)?;
+
+            let err = upgrade(db_data.path()).unwrap_err();
+            assert_eq!(
+                Error::TruncatedRelation(rel_path.display().to_string()),
+                err.downcast::<Error>().unwrap()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_not_data_directory() -> Result<()> {
+        let db_data = tempdir()?;
+        let err = upgrade(db_data.path()).unwrap_err();
+        assert!(matches!(
+            err.downcast::<Error>().unwrap(),
+            Error::NotDataDirectory(_)
+        ));
+        Ok(())
+    }
+}